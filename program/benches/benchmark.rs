@@ -1,105 +1,131 @@
-use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
-
-use gc_representation_rs::shared::{MemoryManager, Stack};
-
-use gc_representation_rs::stop_copy::StopAndCopyHeap;
-use gc_representation_rs::{link_heap, make_garbage, mark_compact::*};
+use criterion::measurement::WallTime;
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion};
+
+use gc_representation_rs::shared::{
+    Children, MemoryManager, Node, NodePointer, Padded, Payload, Stack,
+};
+
+use gc_representation_rs::adaptive::AdaptiveHeap;
+use gc_representation_rs::concurrent::ConcurrentMarkSweepHeap;
+use gc_representation_rs::flat::{Inline, ARITY};
+use gc_representation_rs::garbage_first::GarbageFirstHeap;
+use gc_representation_rs::generational::GenerationalHeap;
+use gc_representation_rs::mark_region::MarkRegionHeap;
+use gc_representation_rs::mark_sweep::MarkSweepHeap;
+use gc_representation_rs::parallel;
+use gc_representation_rs::stop_copy::{CopyOrder, StopAndCopyHeap};
+use gc_representation_rs::treadmill::TreadmillHeap;
+use gc_representation_rs::{
+    link_heap, link_heap_sized, make_garbage, make_garbage_sized, mark_compact::*, SizeDistribution,
+};
 
 use rand::prelude::*;
 use rand_pcg::Pcg64;
 
 use std::env;
 
-fn collect<T: MemoryManager>(stack: &mut Stack, heap: &mut T) {
+fn collect<P: Payload, T: MemoryManager<P>>(stack: &mut Stack, heap: &mut T) {
     heap.collect(stack).unwrap()
 }
 
-// enum MemoryType {
-//     MarkCompact(Memory<MarkCompactHeap>),
-//     StopAndCopy(Memory<StopAndCopyHeap>),
-// }
-// struct Memory<T: MemoryManager> {
-//     label: Option<String>,
-//     stack: Stack,
-//     heap: T,
-// }
+/// a linked heap and its stack, along with the name it shows up as in the
+/// benchmark groups
+struct Memory<T: MemoryManager> {
+    label: &'static str,
+    stack: Stack,
+    heap: T,
+    // the rng right after linking, which we subsequently clone for
+    // `make_garbage` in the later benchmarks
+    rng: Pcg64,
+}
 
-fn random_benchmark_init(c: &mut Criterion) {
-    // holds the two different types of heaps we have
+impl<T: MemoryManager + Clone> Memory<T> {
+    fn init(label: &'static str, mut heap: T) -> Self {
+        const STACK_SIZE: usize = 1;
+        // initializing the stack
+        let mut stack = Stack::new(STACK_SIZE);
+        // every heap gets linked with the same seed, so they all end up with
+        // the same graph
+        let mut rng = Pcg64::seed_from_u64(1234);
+        link_heap(&mut stack, &mut heap, &mut rng).unwrap();
+        Self {
+            label,
+            stack,
+            heap,
+            rng,
+        }
+    }
 
-    // initialize this rng which we will subsequently clone mutable references
-    // of in the later benchmarks
-    let mut rng = Pcg64::seed_from_u64(1234);
-    // {
-    // create marksweep thingy
-    const STACK_SIZE: usize = 1;
-    let heap_size: usize = env::var("HEAP_SIZE").unwrap().parse::<usize>().unwrap();
-    // initializing the stack
-    let mut m_stack = Stack::new(STACK_SIZE);
-    // initializing the heap
-    let mut m_heap = MarkCompactHeap::init(heap_size);
-
-    // initialize the thing with a new seed
-
-    // link the heap (actually changes rng)
-    link_heap(&mut m_stack, &mut m_heap, &mut rng).unwrap();
-
-    // make the memory type now own the heap and the stack of this thing
-    // question: does this just update a pointer ref, or is memory actually
-    // moved here?
-    //
-    // seems like structs exist on stack by default but if you have a vector
-    // in a struct, then that field points to something allocated on the
-    // heap
-    //
-    // in this case, moving the heap would move the vectors that we actually
-    // care about, but the intergers are gonna be copied by value
-    // memory_types.push(MemoryType::MarkCompact(Memory {
-    //     label: Some(String::from("Mark Compact")),
-    //     stack,
-    //     heap,
-    // }))
-    // }
-    // stop copy
-    // {
-    // const STACK_SIZE: usize = 1;
-    // let heap_size: usize = env::var("HEAP_SIZE").unwrap().parse::<usize>().unwrap() * 2;
-    // initializing the stack
-    let mut s_stack = Stack::new(STACK_SIZE);
-    // initializing the heap
-    let mut s_heap = StopAndCopyHeap::init(heap_size * 2);
-    // get_heap(&mut stack, &mut heap, heap_size / 2).unwrap();
+    /// clones the linked heap and removes `size` worth of garbage from it
+    fn garbage(&self, size: f32) -> (Stack, T) {
+        let mut stack = self.stack.clone();
+        let mut heap = self.heap.clone();
 
-    let mut rng = Pcg64::seed_from_u64(1234);
-    link_heap(&mut s_stack, &mut s_heap, &mut rng).unwrap();
-    // memory_types.push(MemoryType::StopAndCopy(Memory {
-    //     label: Some(String::from("Stop and Copy")),
-    //     stack,
-    //     heap,
-    // }))
-    // }
-    random_benchmark(c, m_stack, m_heap, s_stack, s_heap, rng);
+        make_garbage(&mut stack, &mut heap, size, &mut self.rng.clone()).unwrap();
+
+        (stack, heap)
+    }
 }
 
-fn random_benchmark(
-    c: &mut Criterion,
-    m_stack: Stack,
-    m_heap: MarkCompactHeap,
-    s_stack: Stack,
-    s_heap: StopAndCopyHeap,
-    rng: Pcg64,
-) {
+fn random_benchmark_init(c: &mut Criterion) {
+    let heap_size: usize = env::var("HEAP_SIZE").unwrap().parse::<usize>().unwrap();
+
+    let m = Memory::init("Mark-Compact", MarkCompactHeap::init(heap_size));
+    let m_dfs: Memory<MarkCompactHeap> = Memory::init(
+        "Mark-Compact (DFS)",
+        MarkCompactHeap::init_ordered(heap_size, MarkOrder::Dfs),
+    );
+    let m_two_finger: Memory<MarkCompactHeap> = Memory::init(
+        "Mark-Compact (Two-Finger)",
+        MarkCompactHeap::init_compacting(heap_size, Compaction::TwoFinger),
+    );
+    let m_threaded: Memory<MarkCompactHeap> = Memory::init(
+        "Mark-Compact (Threaded)",
+        MarkCompactHeap::init_compacting(heap_size, Compaction::Threaded),
+    );
+    let m_compressor: Memory<MarkCompactHeap> = Memory::init(
+        "Mark-Compact (Compressor)",
+        MarkCompactHeap::init_compacting(heap_size, Compaction::Compressor),
+    );
+    // stop and copy needs double the memory
+    let s = Memory::init("Stop-Copy", StopAndCopyHeap::init(heap_size * 2));
+    let s_dfs: Memory<StopAndCopyHeap> = Memory::init(
+        "Stop-Copy (DFS)",
+        StopAndCopyHeap::init_ordered(heap_size * 2, CopyOrder::Dfs),
+    );
+    let s_hierarchical: Memory<StopAndCopyHeap> = Memory::init(
+        "Stop-Copy (Hierarchical)",
+        StopAndCopyHeap::init_ordered(heap_size * 2, CopyOrder::Hierarchical { block_size: 64 }),
+    );
+    let ms = Memory::init("Mark-Sweep", MarkSweepHeap::init(heap_size));
+    let g = Memory::init("Generational", GenerationalHeap::init(heap_size));
+    let r = Memory::init("Mark-Region", MarkRegionHeap::init(heap_size));
+    let t = Memory::init("Treadmill", TreadmillHeap::init(heap_size));
+    let gf = Memory::init("Garbage-First", GarbageFirstHeap::init(heap_size));
+    let cms = Memory::init(
+        "Concurrent Mark-Sweep",
+        ConcurrentMarkSweepHeap::init(heap_size),
+    );
+    let a = Memory::init("Adaptive", AdaptiveHeap::init(heap_size));
+    // with the children inline
+    let m_flat = Memory::init(
+        "Mark-Compact (Flat)",
+        MarkCompactHeap::<Option<u32>, Inline<ARITY>>::init_payload(heap_size),
+    );
+    let s_flat = Memory::init(
+        "Stop-Copy (Flat)",
+        StopAndCopyHeap::<Option<u32>, Inline<ARITY>>::init_payload(heap_size * 2),
+    );
+
     let input_data: Vec<(f32, f32)> = [
         0., 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0, 1.1, 1.2, 1.3, 1.4, 1.5,
     ]
     .iter()
     .map(|size| {
         // pick any algorithm from mark compact
-        let mut stack = m_stack.clone();
-        let mut heap = m_heap.clone();
+        let (mut stack, mut heap) = m.garbage(*size);
 
         // dead to live ratio
-        make_garbage(&mut stack, &mut heap, *size, &mut rng.clone()).unwrap();
         collect(&mut stack, &mut heap);
 
         (
@@ -112,115 +138,393 @@ fn random_benchmark(
 
     dbg!(&input_data);
 
+    // how much memory each node takes up, depending on how it gets compacted
+    for compaction in [
+        Compaction::Lisp2,
+        Compaction::TwoFinger,
+        Compaction::Threaded,
+        Compaction::Compressor,
+    ] {
+        println!(
+            "{:?}: {} bytes per node against {} for a Node, {} of which are for compaction",
+            compaction,
+            compaction.node_size(),
+            std::mem::size_of::<Node>(),
+            compaction.node_overhead()
+        );
+    }
+
     let mut group = c.benchmark_group(
         "Time Taken to Collect Garbage with Various Garbage Amounts (Higher is Worse)",
     );
+    collect_benchmark(&mut group, &m, &input_data);
+    collect_benchmark(&mut group, &m_dfs, &input_data);
+    collect_benchmark(&mut group, &m_two_finger, &input_data);
+    collect_benchmark(&mut group, &m_threaded, &input_data);
+    collect_benchmark(&mut group, &m_compressor, &input_data);
+    collect_benchmark(&mut group, &s, &input_data);
+    collect_benchmark(&mut group, &s_dfs, &input_data);
+    collect_benchmark(&mut group, &s_hierarchical, &input_data);
+    collect_benchmark(&mut group, &ms, &input_data);
+    collect_benchmark(&mut group, &g, &input_data);
+    collect_benchmark(&mut group, &r, &input_data);
+    collect_benchmark(&mut group, &t, &input_data);
+    collect_benchmark(&mut group, &gf, &input_data);
+    collect_benchmark(&mut group, &cms, &input_data);
+    collect_benchmark(&mut group, &a, &input_data);
+    collect_benchmark(&mut group, &m_flat, &input_data);
+    collect_benchmark(&mut group, &s_flat, &input_data);
+    group.finish();
 
+    let mut group = c.benchmark_group(
+        "Time Taken to Traverse Data Via BFS After Removing Garbage (Higher is Worse)",
+    );
+    bfs_benchmark(&mut group, &m, &input_data);
+    bfs_benchmark(&mut group, &m_two_finger, &input_data);
+    bfs_benchmark(&mut group, &m_threaded, &input_data);
+    bfs_benchmark(&mut group, &m_compressor, &input_data);
+    bfs_benchmark(&mut group, &s, &input_data);
+    bfs_benchmark(&mut group, &s_dfs, &input_data);
+    bfs_benchmark(&mut group, &s_hierarchical, &input_data);
+    bfs_benchmark(&mut group, &ms, &input_data);
+    bfs_benchmark(&mut group, &g, &input_data);
+    bfs_benchmark(&mut group, &r, &input_data);
+    bfs_benchmark(&mut group, &t, &input_data);
+    bfs_benchmark(&mut group, &gf, &input_data);
+    bfs_benchmark(&mut group, &cms, &input_data);
+    bfs_benchmark(&mut group, &a, &input_data);
+    bfs_benchmark(&mut group, &m_flat, &input_data);
+    bfs_benchmark(&mut group, &s_flat, &input_data);
+    group.finish();
+
+    let mut group = c.benchmark_group(
+        "Time Taken to Traverse Data Via DFS After Removing Garbage (Higher is Worse)",
+    );
+    dfs_benchmark(&mut group, &m, &input_data);
+    dfs_benchmark(&mut group, &m_two_finger, &input_data);
+    dfs_benchmark(&mut group, &m_threaded, &input_data);
+    dfs_benchmark(&mut group, &m_compressor, &input_data);
+    dfs_benchmark(&mut group, &s, &input_data);
+    dfs_benchmark(&mut group, &s_dfs, &input_data);
+    dfs_benchmark(&mut group, &s_hierarchical, &input_data);
+    dfs_benchmark(&mut group, &ms, &input_data);
+    dfs_benchmark(&mut group, &g, &input_data);
+    dfs_benchmark(&mut group, &r, &input_data);
+    dfs_benchmark(&mut group, &t, &input_data);
+    dfs_benchmark(&mut group, &gf, &input_data);
+    dfs_benchmark(&mut group, &cms, &input_data);
+    dfs_benchmark(&mut group, &a, &input_data);
+    dfs_benchmark(&mut group, &m_flat, &input_data);
+    dfs_benchmark(&mut group, &s_flat, &input_data);
+    group.finish();
+}
+
+fn collect_benchmark<T: MemoryManager + Clone>(
+    group: &mut BenchmarkGroup<WallTime>,
+    memory: &Memory<T>,
+    input_data: &[(f32, f32)],
+) {
     for (size, ratio) in input_data.iter() {
-        group.bench_with_input(
-            BenchmarkId::new("Mark-Compact", ratio),
-            ratio,
-            |b, _ratio| {
-                b.iter_batched(
-                    || {
-                        let mut stack = m_stack.clone();
-                        let mut heap = m_heap.clone();
+        group.bench_with_input(BenchmarkId::new(memory.label, ratio), ratio, |b, _ratio| {
+            b.iter_batched(
+                || memory.garbage(*size),
+                |(mut stack, mut heap)| collect(&mut stack, &mut heap),
+                criterion::BatchSize::SmallInput,
+            )
+        });
+    }
+}
+
+fn bfs_benchmark<T: MemoryManager + Clone>(
+    group: &mut BenchmarkGroup<WallTime>,
+    memory: &Memory<T>,
+    input_data: &[(f32, f32)],
+) {
+    for (size, ratio) in input_data.iter() {
+        // clone the stack and heap (necessary), and create our own garbage
+        // from the heap that should already be linked
+        let (mut stack, mut heap) = memory.garbage(*size);
+        collect(&mut stack, &mut heap);
+
+        group.bench_with_input(BenchmarkId::new(memory.label, ratio), ratio, |b, _ratio| {
+            b.iter(|| stack.sum_bfs(&heap))
+        });
+    }
+}
+
+fn dfs_benchmark<T: MemoryManager + Clone>(
+    group: &mut BenchmarkGroup<WallTime>,
+    memory: &Memory<T>,
+    input_data: &[(f32, f32)],
+) {
+    for (size, ratio) in input_data.iter() {
+        let (mut stack, mut heap) = memory.garbage(*size);
+        collect(&mut stack, &mut heap);
 
-                        make_garbage(&mut stack, &mut heap, *size, &mut rng.clone()).unwrap();
+        group.bench_with_input(BenchmarkId::new(memory.label, ratio), ratio, |b, _ratio| {
+            b.iter(|| stack.sum_dfs(&heap))
+        });
+    }
+}
+
+fn parallel_benchmark_init(c: &mut Criterion) {
+    let heap_size: usize = env::var("HEAP_SIZE").unwrap().parse::<usize>().unwrap();
 
-                        (stack, heap)
-                    },
+    let mut group = c.benchmark_group(
+        "Time Taken to Collect Garbage with Various Marking Threads (Higher is Worse)",
+    );
+    for threads in 1..=parallel::available_threads() {
+        let m: Memory<MarkCompactHeap> = Memory::init(
+            "Mark-Compact",
+            MarkCompactHeap::init_parallel(heap_size, threads),
+        );
+        group.bench_with_input(
+            BenchmarkId::new(m.label, threads),
+            &threads,
+            |b, _threads| {
+                b.iter_batched(
+                    || m.garbage(0.5),
                     |(mut stack, mut heap)| collect(&mut stack, &mut heap),
                     criterion::BatchSize::SmallInput,
                 )
             },
         );
-        group.bench_with_input(BenchmarkId::new("Stop-Copy", ratio), ratio, |b, _ratio| {
-            b.iter_batched(
-                || {
-                    let mut stack = s_stack.clone();
-                    let mut heap = s_heap.clone();
-
-                    make_garbage(&mut stack, &mut heap, *size, &mut rng.clone()).unwrap();
-
-                    (stack, heap)
-                },
-                |(mut stack, mut heap)| collect(&mut stack, &mut heap),
-                criterion::BatchSize::SmallInput,
-            )
-        });
     }
     group.finish();
 
     let mut group = c.benchmark_group(
-        "Time Taken to Traverse Data Via BFS After Removing Garbage (Higher is Worse)",
+        "Time Taken to Collect Garbage with Various Copying Threads (Higher is Worse)",
     );
+    for threads in 1..=parallel::available_threads() {
+        let s: Memory<StopAndCopyHeap> = Memory::init(
+            "Stop-Copy",
+            StopAndCopyHeap::init_parallel(heap_size * 2, threads),
+        );
+        group.bench_with_input(
+            BenchmarkId::new(s.label, threads),
+            &threads,
+            |b, _threads| {
+                b.iter_batched(
+                    || s.garbage(0.5),
+                    |(mut stack, mut heap)| collect(&mut stack, &mut heap),
+                    criterion::BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
 
-    for (size, ratio) in input_data.iter() {
-        {
-            // clone the stack and heap (necessary)
-            let mut stack = m_stack.clone();
-            let mut heap = m_heap.clone();
-
-            // create our own garbage from the heap that should already be linked
-            make_garbage(&mut stack, &mut heap, *size, &mut rng.clone()).unwrap();
-            collect(&mut stack, &mut heap);
-            // println!("{}", heap.free);
-
-            group.bench_with_input(
-                BenchmarkId::new("Mark-Compact", ratio),
-                ratio,
-                |b, _ratio| b.iter(|| stack.sum_bfs(&heap)),
-            );
-        }
-        {
-            let mut stack = s_stack.clone();
-            let mut heap = s_heap.clone();
-
-            make_garbage(&mut stack, &mut heap, *size, &mut rng.clone()).unwrap();
-            collect(&mut stack, &mut heap);
+fn sized_benchmark_init(c: &mut Criterion) {
+    let heap_size: usize = env::var("HEAP_SIZE").unwrap().parse::<usize>().unwrap();
 
-            group.bench_with_input(BenchmarkId::new("Stop-Copy", ratio), ratio, |b, _ratio| {
-                b.iter(|| stack.sum_bfs(&heap))
-            });
-        }
+    let mut group = c.benchmark_group(
+        "Time Taken to Collect Garbage with Various Object Sizes (Higher is Worse)",
+    );
+    for (label, sizes) in [
+        ("fixed", SizeDistribution::Fixed(0)),
+        ("uniform", SizeDistribution::Uniform { min: 0, max: 16 }),
+        (
+            "bimodal",
+            SizeDistribution::Bimodal {
+                small: 0,
+                large: 64,
+                ratio: 0.1,
+            },
+        ),
+    ] {
+        // enough room for the average node, which takes up one slot plus
+        // its payload, with a little to spare
+        let size = (heap_size as f32 * (1. + sizes.mean()) * 1.1) as usize;
+        large_collect_benchmark(
+            &mut group,
+            "Mark-Compact",
+            label,
+            MarkCompactHeap::init(size),
+            sizes,
+        );
+        large_collect_benchmark(
+            &mut group,
+            "Stop-Copy",
+            label,
+            StopAndCopyHeap::init(size * 2),
+            sizes,
+        );
+        large_collect_benchmark(
+            &mut group,
+            "Mark-Compact (Flat)",
+            label,
+            MarkCompactHeap::<Option<u32>, Inline<ARITY>>::init_payload(size),
+            sizes,
+        );
+        large_collect_benchmark(
+            &mut group,
+            "Stop-Copy (Flat)",
+            label,
+            StopAndCopyHeap::<Option<u32>, Inline<ARITY>>::init_payload(size * 2),
+            sizes,
+        );
     }
     group.finish();
+}
 
+fn large_benchmark_init(c: &mut Criterion) {
     let mut group = c.benchmark_group(
-        "Time Taken to Traverse Data Via DFS After Removing Garbage (Higher is Worse)",
+        "Time Taken to Collect Garbage with and without a Large Object Space (Higher is Worse)",
+    );
+    large_layout_benchmark::<Vec<NodePointer>>(&mut group, ["Mark-Compact", "Stop-Copy"]);
+    large_layout_benchmark::<Inline<ARITY>>(
+        &mut group,
+        ["Mark-Compact (Flat)", "Stop-Copy (Flat)"],
     );
+    group.finish();
+}
 
-    for (size, ratio) in input_data.iter() {
-        {
-            // clone the stack and heap (necessary)
-            let mut stack = m_stack.clone();
-            let mut heap = m_heap.clone();
-
-            // create our own garbage from the heap that should already be linked
-            make_garbage(&mut stack, &mut heap, *size, &mut rng.clone()).unwrap();
-            collect(&mut stack, &mut heap);
-
-            group.bench_with_input(
-                BenchmarkId::new("Mark-Compact", ratio),
-                ratio,
-                |b, _ratio| b.iter(|| stack.sum_dfs(&heap)),
-            );
-        }
-        {
-            let mut stack = s_stack.clone();
-            let mut heap = s_heap.clone();
+/// benchmarks mark compact and stop and copy with nodes laying their
+/// children out as `C`, with and without a large object space, printing how
+/// much each collection has to move
+fn large_layout_benchmark<C: Children>(
+    group: &mut BenchmarkGroup<WallTime>,
+    [m_name, s_name]: [&'static str; 2],
+) {
+    let heap_size: usize = env::var("HEAP_SIZE").unwrap().parse::<usize>().unwrap();
+    const THRESHOLD: usize = 32;
+    let sizes = SizeDistribution::Bimodal {
+        small: 0,
+        large: 64,
+        ratio: 0.1,
+    };
+    // without a large object space, room for all of them in the rest of the
+    // heap, with a little to spare
+    let size = (heap_size as f32 * (1. + sizes.mean()) * 1.1) as usize;
+    // and with one, enough room for the small nodes in the rest of the heap,
+    // and for the large ones in the large object space
+    let small_size = heap_size * 11 / 10;
+    let large_size = (heap_size as f32 * 0.1 * (1 + 64) as f32 * 1.5) as usize;
+    let bytes = |copied: usize| copied * std::mem::size_of::<Node<Option<u32>, C>>();
+
+    for (label, heap) in [
+        (
+            "without",
+            MarkCompactHeap::<Option<u32>, C>::init_payload(size),
+        ),
+        (
+            "with",
+            MarkCompactHeap::init_large(small_size, THRESHOLD, large_size),
+        ),
+    ] {
+        let (mut stack, mut heap) = large_collect_benchmark(group, m_name, label, heap, sizes);
+        let copied = heap.copied;
+        collect(&mut stack, &mut heap);
+        println!(
+            "{} {} a large object space: {} bytes copied per collection",
+            m_name,
+            label,
+            bytes(heap.copied - copied)
+        );
+    }
+    // stop and copy needs double the memory
+    for (label, heap) in [
+        (
+            "without",
+            StopAndCopyHeap::<Option<u32>, C>::init_payload(size * 2),
+        ),
+        (
+            "with",
+            StopAndCopyHeap::init_large(small_size * 2, THRESHOLD, large_size),
+        ),
+    ] {
+        let (mut stack, mut heap) = large_collect_benchmark(group, s_name, label, heap, sizes);
+        let copied = heap.copied;
+        collect(&mut stack, &mut heap);
+        println!(
+            "{} {} a large object space: {} bytes copied per collection",
+            s_name,
+            label,
+            bytes(heap.copied - copied)
+        );
+    }
+}
+
+/// links `heap` up with nodes sized by `sizes`, makes garbage, and benchmarks
+/// collecting it, handing back the heap and its stack from before collecting
+fn large_collect_benchmark<T: MemoryManager + Clone>(
+    group: &mut BenchmarkGroup<WallTime>,
+    name: &'static str,
+    label: &'static str,
+    mut heap: T,
+    sizes: SizeDistribution,
+) -> (Stack, T) {
+    let heap_size: usize = env::var("HEAP_SIZE").unwrap().parse::<usize>().unwrap();
+    let mut stack = Stack::new(1);
+    let mut rng = Pcg64::seed_from_u64(1234);
+    let nodes = link_heap_sized(&mut stack, &mut heap, heap_size, sizes, &mut rng).unwrap();
+    make_garbage_sized(&mut heap, &nodes, 0.5, &mut rng).unwrap();
+
+    group.bench_with_input(BenchmarkId::new(name, label), &label, |b, _label| {
+        b.iter_batched(
+            || (stack.clone(), heap.clone()),
+            |(mut stack, mut heap)| collect(&mut stack, &mut heap),
+            criterion::BatchSize::SmallInput,
+        )
+    });
+    (stack, heap)
+}
 
-            make_garbage(&mut stack, &mut heap, *size, &mut rng.clone()).unwrap();
-            collect(&mut stack, &mut heap);
+fn payload_benchmark_init(c: &mut Criterion) {
+    let heap_size: usize = env::var("HEAP_SIZE").unwrap().parse::<usize>().unwrap();
 
-            group.bench_with_input(BenchmarkId::new("Stop-Copy", ratio), ratio, |b, _ratio| {
-                b.iter(|| stack.sum_dfs(&heap))
-            });
-        }
-    }
+    let mut group = c.benchmark_group(
+        "Time Taken to Collect Garbage with Various Payload Sizes (Higher is Worse)",
+    );
+    payload_benchmark::<Padded<0>>(&mut group, heap_size);
+    payload_benchmark::<Padded<64>>(&mut group, heap_size);
+    payload_benchmark::<Padded<256>>(&mut group, heap_size);
     group.finish();
 }
 
-criterion_group!(benches, random_benchmark_init);
+/// copying only moves what survives, while sliding moves everything after
+/// the first hole, so the bigger the nodes the more that matters
+fn payload_benchmark<P: Payload>(group: &mut BenchmarkGroup<WallTime>, heap_size: usize) {
+    let padding = std::mem::size_of::<P>() - std::mem::size_of::<Option<u32>>();
+    // stop and copy needs double the memory
+    let m = (
+        "Mark-Compact",
+        MarkCompactHeap::<P>::init_payload(heap_size),
+    );
+    let s = (
+        "Stop-Copy",
+        StopAndCopyHeap::<P>::init_payload(heap_size * 2),
+    );
+    payload_collect_benchmark(group, m, padding);
+    payload_collect_benchmark(group, s, padding);
+}
+
+fn payload_collect_benchmark<P: Payload, T: MemoryManager<P> + Clone>(
+    group: &mut BenchmarkGroup<WallTime>,
+    (label, mut heap): (&'static str, T),
+    padding: usize,
+) {
+    let mut stack = Stack::new(1);
+    let mut rng = Pcg64::seed_from_u64(1234);
+    link_heap(&mut stack, &mut heap, &mut rng).unwrap();
+    make_garbage(&mut stack, &mut heap, 0.5, &mut rng).unwrap();
+
+    group.bench_with_input(BenchmarkId::new(label, padding), &padding, |b, _padding| {
+        b.iter_batched(
+            || (stack.clone(), heap.clone()),
+            |(mut stack, mut heap)| collect(&mut stack, &mut heap),
+            criterion::BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(
+    benches,
+    random_benchmark_init,
+    parallel_benchmark_init,
+    sized_benchmark_init,
+    large_benchmark_init,
+    payload_benchmark_init
+);
 criterion_main!(benches);
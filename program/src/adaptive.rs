@@ -0,0 +1,301 @@
+use crate::large::LargeObjectSpace;
+use crate::mark_compact::MarkCompactHeap;
+use crate::shared::{Barriers, MemoryManager, Node, NodePointer, Nodes, Stack};
+use crate::stop_copy::StopAndCopyHeap;
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// which collector the adaptive heap runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Cheney's copying collector, which only touches what survives, but can
+    /// only allocate into half of the heap
+    Copying,
+    /// Lisp2 sliding compaction, which can allocate into the whole heap, but
+    /// has to go over all of it every collection
+    Compacting,
+}
+
+/// when the adaptive heap picks which collector
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptivePolicy {
+    /// copy if at most this much of what was allocated survived the last
+    /// collection...
+    pub copy_survival: f32,
+    /// ...and what survived takes up at most this much of the whole heap.
+    /// Anything past a half can't be copied at all
+    pub copy_occupancy: f32,
+}
+
+impl Default for AdaptivePolicy {
+    fn default() -> Self {
+        Self {
+            copy_survival: 0.5,
+            copy_occupancy: 0.25,
+        }
+    }
+}
+
+/// what the adaptive heap measured after a collection, and what it decided to
+/// do about it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decision {
+    pub algorithm: Algorithm,
+    // how much of what was allocated survived
+    pub survival_rate: f32,
+    pub live: usize,
+    // how many slots are left to allocate into before the next collection
+    pub free: usize,
+    // the algorithm that the next collection is going to use
+    pub next: Algorithm,
+}
+
+/// The paper concludes that which collector wins is dependent on the
+/// situation, so this heap measures the situation after every collection and
+/// picks a collector for the next one. When most nodes die, copying wins,
+/// since it only ever touches what survives. When most of them survive, or
+/// memory is tight, compacting wins, since it doesn't have to keep half of
+/// the heap empty to copy into.
+///
+/// The collectors are just the `StopAndCopyHeap` and the `MarkCompactHeap`,
+/// which take turns owning the same memory. Compacting always slides
+/// everything down to the bottom of the heap, so switching to copying
+/// afterwards just means handing the memory over with the first half as
+/// to-space. Copying leaves everything in one of the halves, which the
+/// compactor slides down to the bottom the next time it collects. A large
+/// object space comes after the rest of the heap, and gets handed over along
+/// with it
+#[derive(Clone)]
+pub struct AdaptiveHeap {
+    // whichever of these is running the next collection owns the memory,
+    // and the other one's memory is empty
+    pub copying: StopAndCopyHeap,
+    pub compacting: MarkCompactHeap,
+    // the algorithm that the next collection is going to use
+    pub algorithm: Algorithm,
+    pub policy: AdaptivePolicy,
+    // every decision that's been made, oldest first
+    pub history: Vec<Decision>,
+}
+
+impl AdaptiveHeap {
+    /// starts off compacting, since nothing's been measured yet and
+    /// compacting can use the whole heap
+    pub fn init(size: usize) -> Self {
+        Self::init_with_policy(size, AdaptivePolicy::default())
+    }
+
+    pub fn init_with_policy(size: usize, policy: AdaptivePolicy) -> Self {
+        Self::init_with_heap(MarkCompactHeap::init(size), policy)
+    }
+
+    /// the same as `init`, along with a large object space of `large_size`
+    /// slots, for nodes taking up more than `threshold` slots
+    pub fn init_large(size: usize, threshold: usize, large_size: usize) -> Self {
+        Self::init_with_heap(
+            MarkCompactHeap::init_large(size, threshold, large_size),
+            AdaptivePolicy::default(),
+        )
+    }
+
+    fn init_with_heap(compacting: MarkCompactHeap, policy: AdaptivePolicy) -> Self {
+        // the copying heap's memory gets handed over from the compactor
+        let mut copying = StopAndCopyHeap::init(0);
+        copying.extent = compacting.end() / 2;
+        Self {
+            copying,
+            compacting,
+            algorithm: Algorithm::Compacting,
+            policy,
+            history: Vec::new(),
+        }
+    }
+
+    /// the large object space, if there is one, from whichever heap owns the
+    /// memory
+    fn large(&self) -> Option<&LargeObjectSpace> {
+        match self.algorithm {
+            Algorithm::Copying => self.copying.large.as_ref(),
+            Algorithm::Compacting => self.compacting.large.as_ref(),
+        }
+    }
+
+    fn large_mut(&mut self) -> Option<&mut LargeObjectSpace> {
+        match self.algorithm {
+            Algorithm::Copying => self.copying.large.as_mut(),
+            Algorithm::Compacting => self.compacting.large.as_mut(),
+        }
+    }
+
+    /// where the rest of the heap ends, and the large object space starts
+    pub fn end(&self) -> usize {
+        match self.algorithm {
+            Algorithm::Copying => self
+                .large()
+                .map_or(self.copying.committed_memory.len(), |large| large.start),
+            Algorithm::Compacting => self.compacting.end(),
+        }
+    }
+
+    /// first fit in the large object space, collecting if nothing fits. The
+    /// heap that owns the memory would collect by itself, so this goes
+    /// around it
+    fn alloc_large(&mut self, node: Node, size: usize, stack: &mut Stack) -> Result<NodePointer> {
+        let mut node_pointer = self.large_mut().unwrap().alloc(size);
+        if node_pointer.is_none() {
+            self.collect(stack)?;
+            node_pointer = self.large_mut().unwrap().alloc(size);
+        }
+        let node_pointer =
+            node_pointer.ok_or("gg collection didn't result in any amount of garbage collected")?;
+        *self.get_mut(node_pointer).unwrap() = node;
+        Ok(node_pointer)
+    }
+
+    /// gives the memory to the copying collector, with everything that's been
+    /// compacted to the bottom of the heap sitting in to-space
+    fn start_copying(&mut self) {
+        let copying = &mut self.copying;
+        copying.committed_memory = std::mem::take(&mut self.compacting.committed_memory);
+        copying.large = self.compacting.large.take();
+        copying.to_space = 0;
+        copying.from_space = copying.extent;
+        copying.free = self.compacting.free;
+        copying.top = copying.extent;
+        copying.scan = 0;
+    }
+
+    /// gives the memory to the compactor, which allocates on top of whichever
+    /// half was copied into, since it doesn't mind what's underneath
+    fn start_compacting(&mut self) {
+        self.compacting.committed_memory = std::mem::take(&mut self.copying.committed_memory);
+        self.compacting.large = self.copying.large.take();
+        self.compacting.free = self.copying.free;
+    }
+
+    /// where the space that's being allocated into starts, and where it ends
+    fn space(&self) -> (usize, usize) {
+        match self.algorithm {
+            Algorithm::Copying => (self.copying.to_space, self.copying.top),
+            Algorithm::Compacting => (0, self.compacting.end()),
+        }
+    }
+
+    /// the next slot that gets allocated into
+    fn next_free(&self) -> usize {
+        match self.algorithm {
+            Algorithm::Copying => self.copying.free,
+            Algorithm::Compacting => self.compacting.free,
+        }
+    }
+
+    /// picks the algorithm for the next collection, based on how this one went
+    fn decide(&self, survival_rate: f32, live: usize) -> Algorithm {
+        let occupancy = live as f32 / self.heap_size().max(1) as f32;
+        if survival_rate <= self.policy.copy_survival
+            && occupancy <= self.policy.copy_occupancy
+            && live <= self.copying.extent
+        {
+            Algorithm::Copying
+        } else {
+            Algorithm::Compacting
+        }
+    }
+}
+
+impl MemoryManager for AdaptiveHeap {
+    // the heap that owns the memory does the allocating, but the collecting
+    // goes through here, so that it can be measured
+    fn alloc(&mut self, node: Node, stack: &mut Stack) -> Result<NodePointer> {
+        if self.next_free() >= self.space().1 {
+            // we need to run gc
+            self.collect(stack)?;
+        }
+        if self.next_free() >= self.space().1 {
+            return Err("gg collection didn't result in any amount of garbage collected".into());
+        }
+        match self.algorithm {
+            Algorithm::Copying => self.copying.alloc(node, stack),
+            Algorithm::Compacting => self.compacting.alloc(node, stack),
+        }
+    }
+
+    // nodes are all the same size, unless they're big enough for the large
+    // object space
+    fn alloc_sized(&mut self, node: Node, size: usize, stack: &mut Stack) -> Result<NodePointer> {
+        match self.large() {
+            Some(large) if 1 + size > large.threshold => self.alloc_large(node, 1 + size, stack),
+            _ if size > 0 => Err("this heap's nodes are all the same size".into()),
+            _ => self.alloc(node, stack),
+        }
+    }
+
+    // runs whichever collector was picked last time, then picks the next one
+    fn collect(&mut self, stack: &mut Stack) -> Result<()> {
+        // large objects don't get moved by either collector, so they don't
+        // count towards which one to pick
+        let used = self.next_free() - self.space().0;
+        let algorithm = self.algorithm;
+        match algorithm {
+            Algorithm::Copying => self.copying.collect(stack)?,
+            Algorithm::Compacting => self.compacting.collect(stack)?,
+        }
+        let live = self.next_free() - self.space().0;
+        let survival_rate = if used == 0 {
+            0.
+        } else {
+            live as f32 / used as f32
+        };
+
+        self.algorithm = self.decide(survival_rate, live);
+        match (algorithm, self.algorithm) {
+            (Algorithm::Compacting, Algorithm::Copying) => self.start_copying(),
+            (Algorithm::Copying, Algorithm::Compacting) => self.start_compacting(),
+            _ => {}
+        }
+        self.history.push(Decision {
+            algorithm,
+            survival_rate,
+            live,
+            free: self.space().1 - self.next_free(),
+            next: self.algorithm,
+        });
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn node_pointer_from_usize(&self, idx: usize) -> NodePointer {
+        NodePointer::from(self.space().0 + idx)
+    }
+
+    #[inline(always)]
+    fn free(&self) -> usize {
+        // report the number of slots in use
+        self.next_free() - self.space().0 + self.large().map_or(0, |large| large.used())
+    }
+
+    fn heap_size(&self) -> usize {
+        self.end()
+    }
+}
+
+impl Barriers for AdaptiveHeap {}
+
+impl Nodes for AdaptiveHeap {
+    type Children = Vec<NodePointer>;
+
+    #[inline(always)]
+    fn get(&self, node_pointer: NodePointer) -> Option<&Node> {
+        match self.algorithm {
+            Algorithm::Copying => self.copying.get(node_pointer),
+            Algorithm::Compacting => self.compacting.get(node_pointer),
+        }
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self, node_pointer: NodePointer) -> Option<&mut Node> {
+        match self.algorithm {
+            Algorithm::Copying => self.copying.get_mut(node_pointer),
+            Algorithm::Compacting => self.compacting.get_mut(node_pointer),
+        }
+    }
+}
@@ -0,0 +1,442 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use crate::large::LargeObjectSpace;
+use crate::shared::{Barriers, Edges, MemoryManager, Node, NodePointer, Pauses, Stack};
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// the parts of the heap that the marking thread shares with the mutator
+pub struct Shared {
+    pub committed_memory: Vec<UnsafeCell<Node>>,
+    // the mutator takes a node's lock whenever it writes to it, and the
+    // marking thread takes it whenever it reads from it
+    pub locks: Vec<Mutex<()>>,
+    pub marks: Vec<AtomicBool>,
+    // every reference that the write barrier saw getting overwritten while
+    // marking, which the marking thread still has to mark from
+    pub satb: Mutex<Vec<NodePointer>>,
+}
+
+// the mutator is the only thing that ever writes to a node, and it holds the
+// node's lock while it does. The marking thread only ever reads nodes, and
+// holds the node's lock while it does, so the two never race. The mutator
+// reading a node without the lock is fine, since it's the only writer
+unsafe impl Sync for Shared {}
+
+impl Shared {
+    fn init(size: usize) -> Self {
+        Self {
+            committed_memory: (0..size)
+                .map(|_| UnsafeCell::new(Node::default()))
+                .collect(),
+            locks: (0..size).map(|_| Mutex::new(())).collect(),
+            marks: (0..size).map(|_| AtomicBool::new(false)).collect(),
+            satb: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// marks everything reachable from `worklist`, then everything reachable
+    /// from what the write barrier logged in the meantime, until there's
+    /// nothing left in either
+    fn mark(&self, mut worklist: Vec<NodePointer>) {
+        loop {
+            while let Some(node_pointer) = worklist.pop() {
+                let idx = usize::from(node_pointer);
+                if self.marks[idx].swap(true, Ordering::Relaxed) {
+                    continue;
+                }
+                let _lock = self.locks[idx].lock().unwrap();
+                let node = unsafe { &*self.committed_memory[idx].get() };
+                worklist.extend(node.children.iter().copied());
+            }
+            let logged = std::mem::take(&mut *self.satb.lock().unwrap());
+            if logged.is_empty() {
+                break;
+            }
+            worklist = logged;
+        }
+    }
+}
+
+/// This mark-sweep algorithm marks on a background thread, while the mutator
+/// carries on allocating and changing edges. Once enough of the heap is in
+/// use, `alloc` takes a snapshot of the roots and hands it off to a marking
+/// thread, which marks everything that was reachable when the snapshot was
+/// taken (snapshot-at-the-beginning, or SATB).
+///
+/// The mutator can still cut an edge to something the marking thread hasn't
+/// gotten to yet and put it somewhere that's already been marked, so the
+/// write barrier logs every reference that gets overwritten or removed, and
+/// the marking thread marks from those too. Anything allocated while marking
+/// is marked straight away. Once the marking thread runs out of work, the next
+/// allocation does a short remark pause, which marks from whatever got logged
+/// since, then sweeps everything that isn't marked into the free list.
+///
+/// Edges have to be changed through `add_child`, `set_child`, `remove_child`
+/// and `pop_child`. `get_mut` can't be made safe while the marking thread is
+/// reading the heap, so it waits for marking to finish first.
+///
+/// The large object space shares its slots, locks and mark bits with the
+/// rest of the heap, so the marking thread doesn't have to know it's there.
+/// The remark pause hands the marks over to it before sweeping it
+pub struct ConcurrentMarkSweepHeap {
+    pub shared: Arc<Shared>,
+    // slots that are free to be allocated into, lowest address at the end,
+    // just like the mark-sweep heap
+    pub free_list: Vec<NodePointer>,
+    // start marking once this many slots are in use
+    pub trigger: usize,
+    pub marker: Option<JoinHandle<()>>,
+    // whether there's a cycle in progress, which is from when the snapshot is
+    // taken until the remark pause is over
+    pub marking: bool,
+    // how many cycles have marked concurrently
+    pub cycles: usize,
+    pub pauses: Pauses,
+    // nodes bigger than its threshold go in here instead, which comes after
+    // the rest of the heap
+    pub large: Option<LargeObjectSpace>,
+}
+
+impl ConcurrentMarkSweepHeap {
+    /// starts marking once half of the heap is in use
+    pub fn init(size: usize) -> Self {
+        Self::init_with_trigger(size, size / 2)
+    }
+
+    pub fn init_with_trigger(size: usize, trigger: usize) -> Self {
+        Self {
+            shared: Arc::new(Shared::init(size)),
+            free_list: (0..size).rev().map(NodePointer::from).collect(),
+            trigger,
+            marker: None,
+            marking: false,
+            cycles: 0,
+            pauses: Pauses::default(),
+            large: None,
+        }
+    }
+
+    /// the same as `init`, along with a large object space of `large_size`
+    /// slots, for nodes taking up more than `threshold` slots
+    pub fn init_large(size: usize, threshold: usize, large_size: usize) -> Self {
+        Self {
+            shared: Arc::new(Shared::init(size + large_size)),
+            large: Some(LargeObjectSpace::init(threshold, size, large_size)),
+            ..Self::init(size)
+        }
+    }
+
+    /// where the rest of the heap ends, and the large object space starts
+    #[inline(always)]
+    pub fn end(&self) -> usize {
+        self.large
+            .as_ref()
+            .map_or(self.shared.committed_memory.len(), |large| large.start)
+    }
+
+    /// whether the marking thread has run out of work, so the remark pause
+    /// would be short
+    pub fn marking_finished(&self) -> bool {
+        self.marker
+            .as_ref()
+            .is_none_or(|marker| marker.is_finished())
+    }
+
+    /// the only way that the mutator writes to a node while the marking
+    /// thread could be running
+    #[inline(always)]
+    fn write<R>(&mut self, idx: usize, f: impl FnOnce(&mut Node) -> R) -> R {
+        let _lock = self.shared.locks[idx].lock().unwrap();
+        // we've got `&mut self`, so nothing from `get` is still around, and
+        // the marking thread can't read it while we hold the lock
+        f(unsafe { &mut *self.shared.committed_memory[idx].get() })
+    }
+
+    /// logs the reference that's about to disappear, so the marking thread
+    /// still gets to whatever it was pointing at
+    #[inline(always)]
+    fn log(&mut self, old: Option<NodePointer>) {
+        if let (true, Some(old)) = (self.marking, old) {
+            self.shared.satb.lock().unwrap().push(old);
+        }
+    }
+
+    /// takes the snapshot of the roots and starts the marking thread
+    fn start(&mut self, stack: &Stack) {
+        let roots: Vec<NodePointer> = stack
+            .roots
+            .iter()
+            .flat_map(|root| root.children.iter().copied())
+            .collect();
+        let shared = Arc::clone(&self.shared);
+        self.marking = true;
+        self.marker = Some(thread::spawn(move || shared.mark(roots)));
+    }
+
+    /// the remark pause. Waits for the marking thread, marks from whatever got
+    /// logged since it finished, then sweeps
+    fn finish(&mut self) {
+        if let Some(marker) = self.marker.take() {
+            marker.join().unwrap();
+            self.cycles += 1;
+        }
+        // there's nobody else reading the heap now
+        self.shared.mark(Vec::new());
+        self.marking = false;
+
+        self.free_list.clear();
+        for idx in (0..self.end()).rev() {
+            if self.shared.marks[idx].swap(false, Ordering::Relaxed) {
+                continue;
+            }
+            // reset the slot so that the garbage's children vec gets dropped
+            self.write(idx, |node| *node = Node::default());
+            self.free_list.push(NodePointer::from(idx));
+        }
+        if let Some(large) = &mut self.large {
+            let marked: Vec<usize> = large
+                .objects
+                .keys()
+                .copied()
+                .filter(|idx| self.shared.marks[*idx].swap(false, Ordering::Relaxed))
+                .collect();
+            for idx in marked {
+                large.mark(idx.into());
+            }
+            large.sweep();
+        }
+    }
+
+    /// what every allocation does before it allocates. Does the remark pause
+    /// if the marking thread is done, and starts marking if enough of the
+    /// heap is in use
+    fn step(&mut self, stack: &Stack) {
+        if self.marking && self.marking_finished() {
+            let instant = Instant::now();
+            self.finish();
+            self.pauses.record(instant.elapsed());
+        }
+        if !self.marking && self.free() >= self.trigger {
+            self.start(stack);
+        }
+    }
+
+    /// first fit in the large object space, collecting if nothing fits
+    fn alloc_large(&mut self, node: Node, size: usize, stack: &mut Stack) -> Result<NodePointer> {
+        self.step(stack);
+        let mut node_pointer = self.large.as_mut().unwrap().alloc(size);
+        if node_pointer.is_none() {
+            self.collect(stack)?;
+            node_pointer = self.large.as_mut().unwrap().alloc(size);
+        }
+        let node_pointer =
+            node_pointer.ok_or("gg collection didn't result in any amount of garbage collected")?;
+        let idx = usize::from(node_pointer);
+        // marked if we're in the middle of marking, the same as in `alloc`
+        self.write(idx, |slot| *slot = node);
+        if self.marking {
+            self.shared.marks[idx].store(true, Ordering::Relaxed);
+        }
+        Ok(node_pointer)
+    }
+
+    /// reads a node, even while the marking thread is running. This heap
+    /// isn't `Nodes`, since the mutator changes edges through `write` rather
+    /// than through these
+    #[inline(always)]
+    pub fn get(&self, node_pointer: NodePointer) -> Option<&Node> {
+        // the mutator is the only writer, and it can't write while this is
+        // borrowed
+        self.shared
+            .committed_memory
+            .get(usize::from(node_pointer))
+            .map(|cell| unsafe { &*cell.get() })
+    }
+
+    /// waits for marking to finish before handing out the node
+    pub fn get_mut(&mut self, node_pointer: NodePointer) -> Option<&mut Node> {
+        if self.marking {
+            self.finish();
+        }
+        // nobody else is reading the heap now
+        self.shared
+            .committed_memory
+            .get(usize::from(node_pointer))
+            .map(|cell| unsafe { &mut *cell.get() })
+    }
+}
+
+impl Clone for ConcurrentMarkSweepHeap {
+    /// copies the nodes and the free list, but not the cycle in progress, if
+    /// there is one. The copy starts off not marking
+    fn clone(&self) -> Self {
+        let shared = Shared::init(self.shared.committed_memory.len());
+        for (idx, cell) in shared.committed_memory.iter().enumerate() {
+            unsafe { *cell.get() = self.get(idx.into()).unwrap().clone() };
+        }
+        Self {
+            shared: Arc::new(shared),
+            free_list: self.free_list.clone(),
+            trigger: self.trigger,
+            marker: None,
+            marking: false,
+            cycles: self.cycles,
+            pauses: self.pauses,
+            large: self.large.clone(),
+        }
+    }
+}
+
+impl MemoryManager for ConcurrentMarkSweepHeap {
+    // allocates a new node into the first slot on the free list
+    fn alloc(&mut self, node: Node, stack: &mut Stack) -> Result<NodePointer> {
+        self.step(stack);
+        // if there's nothing left on the free list
+        if self.free_list.is_empty() {
+            // we need to run gc
+            self.collect(stack)?;
+        }
+        // take a slot off of the free list
+        let node_pointer = match self.free_list.pop() {
+            Some(node_pointer) => node_pointer,
+            None => {
+                return Err("gg collection didn't result in any amount of garbage collected".into())
+            }
+        };
+        let idx = usize::from(node_pointer);
+        // add it to the heap, marked if we're in the middle of marking, since
+        // it isn't in the snapshot
+        self.write(idx, |slot| *slot = node);
+        if self.marking {
+            self.shared.marks[idx].store(true, Ordering::Relaxed);
+        }
+
+        Ok(node_pointer)
+    }
+
+    // nodes are all the same size, unless they're big enough for the large
+    // object space
+    fn alloc_sized(&mut self, node: Node, size: usize, stack: &mut Stack) -> Result<NodePointer> {
+        match &self.large {
+            Some(large) if 1 + size > large.threshold => self.alloc_large(node, 1 + size, stack),
+            _ if size > 0 => Err("this heap's nodes are all the same size".into()),
+            _ => self.alloc(node, stack),
+        }
+    }
+
+    /// finishes the cycle in progress, if there is one, then runs a whole
+    /// cycle with the mutator stopped, since the snapshot could've been taken
+    /// before anything became garbage
+    fn collect(&mut self, stack: &mut Stack) -> Result<()> {
+        let instant = Instant::now();
+        if self.marking {
+            self.finish();
+        }
+        self.marking = true;
+        self.shared.mark(
+            stack
+                .roots
+                .iter()
+                .flat_map(|root| root.children.iter().copied())
+                .collect(),
+        );
+        self.finish();
+        self.pauses.record(instant.elapsed());
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn node_pointer_from_usize(&self, idx: usize) -> NodePointer {
+        NodePointer::from(idx)
+    }
+
+    #[inline(always)]
+    fn free(&self) -> usize {
+        // there's no free pointer, so report the number of slots in use
+        self.end() - self.free_list.len() + self.large.as_ref().map_or(0, |large| large.used())
+    }
+
+    fn heap_size(&self) -> usize {
+        self.end()
+    }
+}
+
+impl Barriers for ConcurrentMarkSweepHeap {
+    /// the snapshot-at-the-beginning barrier, which only cares about the
+    /// reference that got overwritten
+    #[inline(always)]
+    fn write_barrier(
+        &mut self,
+        _parent: NodePointer,
+        old: Option<NodePointer>,
+        _new: Option<NodePointer>,
+    ) {
+        self.log(old);
+    }
+
+    #[inline(always)]
+    fn root_barrier(&mut self, old: Option<NodePointer>, _new: Option<NodePointer>) {
+        self.log(old);
+    }
+}
+
+// the edge changing methods go through `write` rather than `get_mut`, so
+// that they don't have to wait for marking to finish
+impl Edges for ConcurrentMarkSweepHeap {
+    #[inline(always)]
+    fn value(&self, node_pointer: NodePointer) -> Option<u32> {
+        self.get(node_pointer).unwrap().value
+    }
+
+    #[inline(always)]
+    fn children(&self, node_pointer: NodePointer) -> impl Iterator<Item = NodePointer> + '_ {
+        self.get(node_pointer).unwrap().children.iter().copied()
+    }
+
+    #[inline(always)]
+    fn child(&self, parent: NodePointer, idx: usize) -> Result<&NodePointer> {
+        Ok(self
+            .get(parent)
+            .ok_or("parent isn't on the heap")?
+            .children
+            .get(idx)
+            .ok_or("parent doesn't have that many children")?)
+    }
+
+    fn add_child(&mut self, parent: NodePointer, child: NodePointer) -> Result<()> {
+        self.get(parent).ok_or("parent isn't on the heap")?;
+        self.write(usize::from(parent), |node| node.children.push(child));
+        self.write_barrier(parent, None, Some(child));
+        Ok(())
+    }
+
+    fn set_child(
+        &mut self,
+        parent: NodePointer,
+        idx: usize,
+        child: NodePointer,
+    ) -> Result<NodePointer> {
+        let old = *self.child(parent, idx)?;
+        self.write(usize::from(parent), |node| node.children[idx] = child);
+        self.write_barrier(parent, Some(old), Some(child));
+        Ok(old)
+    }
+
+    fn remove_child(&mut self, parent: NodePointer, idx: usize) -> Result<NodePointer> {
+        self.child(parent, idx)?;
+        let old = self.write(usize::from(parent), |node| node.children.remove(idx));
+        self.write_barrier(parent, Some(old), None);
+        Ok(old)
+    }
+
+    fn pop_child(&mut self, parent: NodePointer) -> Result<Option<NodePointer>> {
+        self.get(parent).ok_or("parent isn't on the heap")?;
+        let old = self.write(usize::from(parent), |node| node.children.pop());
+        self.write_barrier(parent, old, None);
+        Ok(old)
+    }
+}
@@ -0,0 +1,124 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::shared::{Children, NodePointer};
+
+/// how many children a node keeps inline by default. `link_heap` gives a node
+/// its two tree children plus two random links on average, so this leaves
+/// room for most of them
+pub const ARITY: usize = 8;
+
+/// Every `Node` keeps its children in a `Vec` by default, which lives
+/// wherever the system allocator put it, so compacting or copying nodes only
+/// ever moves a pointer to their children around. A heap with nodes that
+/// keep their children in `Inline<N>` instead has up to `N` of them right
+/// there in the node, in the same slot that gets compacted or copied. A node
+/// that's laid out next to its children really is next to its children.
+///
+/// Once a node has more than `N` children, they all spill out into a `Vec`,
+/// rather than `add_child` failing. Nothing about the collectors changes, so
+/// e.g. `MarkCompactHeap::<Option<u32>, Inline<ARITY>>` compacts flat nodes
+/// the same way it compacts any other
+#[derive(Debug, Clone)]
+pub enum Inline<const N: usize> {
+    Fields {
+        // how many of the fields are in use
+        len: usize,
+        fields: [NodePointer; N],
+    },
+    Spilled(Vec<NodePointer>),
+}
+
+// `Default` is only derived for arrays of up to 32 elements
+impl<const N: usize> Default for Inline<N> {
+    fn default() -> Self {
+        Inline::Fields {
+            len: 0,
+            fields: [NodePointer::default(); N],
+        }
+    }
+}
+
+impl<const N: usize> Inline<N> {
+    /// whether the children have spilled out of the node
+    pub fn spilled(&self) -> bool {
+        matches!(self, Inline::Spilled(_))
+    }
+}
+
+impl<const N: usize> From<Vec<NodePointer>> for Inline<N> {
+    fn from(children: Vec<NodePointer>) -> Self {
+        if children.len() > N {
+            return Inline::Spilled(children);
+        }
+        let mut fields = [NodePointer::default(); N];
+        fields[..children.len()].copy_from_slice(&children);
+        Inline::Fields {
+            len: children.len(),
+            fields,
+        }
+    }
+}
+
+impl<const N: usize> Deref for Inline<N> {
+    type Target = [NodePointer];
+
+    #[inline(always)]
+    fn deref(&self) -> &[NodePointer] {
+        match self {
+            Inline::Fields { len, fields } => &fields[..*len],
+            Inline::Spilled(children) => children,
+        }
+    }
+}
+
+impl<const N: usize> DerefMut for Inline<N> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut [NodePointer] {
+        match self {
+            Inline::Fields { len, fields } => &mut fields[..*len],
+            Inline::Spilled(children) => children,
+        }
+    }
+}
+
+impl<const N: usize> Children for Inline<N> {
+    fn push(&mut self, child: NodePointer) {
+        match self {
+            Inline::Fields { len, fields } if *len < N => {
+                fields[*len] = child;
+                *len += 1;
+            }
+            Inline::Fields { fields, .. } => {
+                let mut children = fields.to_vec();
+                children.push(child);
+                *self = Inline::Spilled(children);
+            }
+            Inline::Spilled(children) => children.push(child),
+        }
+    }
+
+    fn pop(&mut self) -> Option<NodePointer> {
+        match self {
+            Inline::Fields { len: 0, .. } => None,
+            Inline::Fields { len, fields } => {
+                *len -= 1;
+                Some(fields[*len])
+            }
+            Inline::Spilled(children) => children.pop(),
+        }
+    }
+
+    fn remove(&mut self, idx: usize) -> NodePointer {
+        match self {
+            Inline::Fields { len, fields } => {
+                assert!(idx < *len, "removal index is out of bounds");
+                let old = fields[idx];
+                // shift the rest of the fields down
+                fields.copy_within(idx + 1..*len, idx);
+                *len -= 1;
+                old
+            }
+            Inline::Spilled(children) => children.remove(idx),
+        }
+    }
+}
@@ -0,0 +1,572 @@
+use std::collections::{HashSet, VecDeque};
+use std::time::Instant;
+
+use crate::large::LargeObjectSpace;
+use crate::shared::{Barriers, MarkBitmap, MemoryManager, Node, NodePointer, Nodes, Pauses, Stack};
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// regions are 64KB, which is 1024 nodes
+pub const REGION_SIZE: usize = 1024;
+/// the most regions that a single collection evacuates
+pub const EVACUATION_BUDGET: usize = 8;
+
+/// a fixed-size slice of the heap, which gets bump allocated into and is
+/// evacuated as a whole
+#[derive(Debug, Clone, Default)]
+pub struct Region {
+    pub start: usize,
+    pub end: usize,
+    // the bump pointer, anything from `start` up to here has been allocated
+    pub top: usize,
+    // how many nodes in it were live as of the last marking
+    pub live: usize,
+    // every node outside of this region that might point into it. Entries
+    // can go stale, so whatever uses them has to check the pointers
+    // themselves
+    pub remembered_set: HashSet<NodePointer>,
+}
+
+impl Region {
+    #[inline(always)]
+    pub fn used(&self) -> usize {
+        self.top - self.start
+    }
+
+    #[inline(always)]
+    pub fn garbage(&self) -> usize {
+        self.used() - self.live
+    }
+}
+
+/// This is a region-based collector in the style of G1 (garbage first). The
+/// heap is split up into fixed-size regions, and `alloc` bump allocates into
+/// one region at a time, moving on to a free region once it's full.
+///
+/// A collection marks the whole heap, counting up how much is live in each
+/// region as it goes. Regions with nothing live in them are freed straight
+/// away, and then the regions with the most garbage are evacuated, copying
+/// their live nodes out into free regions, up to an evacuation budget. The
+/// write barrier keeps a remembered set of pointers into each region from
+/// other regions, so that fixing up pointers to evacuated nodes only looks
+/// at the nodes that could be pointing at them, rather than the whole heap.
+///
+/// Like G1, if a collection can't free up a single region, either because
+/// everything is fragmented or there's nowhere to evacuate to, it falls back
+/// to compacting the whole heap.
+///
+/// Large objects live outside of the regions, so they're never evacuated or
+/// compacted, and nothing needs remembering about what points at them. They
+/// still get remembered as pointing into regions, like anything else
+#[derive(Clone)]
+pub struct GarbageFirstHeap {
+    pub committed_memory: Vec<Node>,
+    pub region_size: usize,
+    pub regions: Vec<Region>,
+    // free regions, kept sorted highest first so that popping one off gives
+    // the lowest
+    pub free_regions: Vec<usize>,
+    // the region that we're bump allocating into
+    pub current: Option<usize>,
+    pub marks: MarkBitmap,
+    pub evacuation_budget: usize,
+    // how many nodes have been evacuated, and how many times the whole heap
+    // has been compacted, over every collection
+    pub evacuated: usize,
+    pub full_collections: usize,
+    pub pauses: Pauses,
+    // just the part of each pause spent evacuating. G1 marks concurrently, so
+    // this is the part of the pause that the budget is there to keep short
+    pub evacuation_pauses: Pauses,
+    // nodes bigger than its threshold go in here instead, which comes after
+    // the last region
+    pub large: Option<LargeObjectSpace>,
+}
+
+impl GarbageFirstHeap {
+    pub fn init(size: usize) -> Self {
+        Self::init_with_regions(size, REGION_SIZE, EVACUATION_BUDGET)
+    }
+
+    pub fn init_with_budget(size: usize, evacuation_budget: usize) -> Self {
+        Self::init_with_regions(size, REGION_SIZE, evacuation_budget)
+    }
+
+    pub fn init_with_regions(size: usize, region_size: usize, evacuation_budget: usize) -> Self {
+        let mut committed_memory: Vec<Node> = Vec::new();
+        for _ in 0..size {
+            committed_memory.push(Node::default());
+        }
+        let region_size = region_size.max(1);
+        let regions: Vec<Region> = (0..size.div_ceil(region_size))
+            .map(|region| {
+                let start = region * region_size;
+                Region {
+                    start,
+                    end: (start + region_size).min(size),
+                    top: start,
+                    ..Default::default()
+                }
+            })
+            .collect();
+        Self {
+            committed_memory,
+            region_size,
+            free_regions: (0..regions.len()).rev().collect(),
+            regions,
+            current: None,
+            marks: MarkBitmap::init(size),
+            evacuation_budget,
+            evacuated: 0,
+            full_collections: 0,
+            pauses: Pauses::default(),
+            evacuation_pauses: Pauses::default(),
+            large: None,
+        }
+    }
+
+    /// the same as `init`, along with a large object space of `large_size`
+    /// slots, for nodes taking up more than `threshold` slots
+    pub fn init_large(size: usize, threshold: usize, large_size: usize) -> Self {
+        let mut heap = Self::init(size);
+        heap.committed_memory
+            .resize_with(size + large_size, Node::default);
+        heap.large = Some(LargeObjectSpace::init(threshold, size, large_size));
+        heap
+    }
+
+    /// where the regions end, and the large object space starts
+    #[inline(always)]
+    pub fn end(&self) -> usize {
+        self.large
+            .as_ref()
+            .map_or(self.committed_memory.len(), |large| large.start)
+    }
+
+    #[inline(always)]
+    fn is_large(&self, node_pointer: NodePointer) -> bool {
+        usize::from(node_pointer) >= self.end()
+    }
+
+    /// whether a node is marked, wherever it lives
+    #[inline(always)]
+    fn is_marked(&self, node_pointer: NodePointer) -> bool {
+        match &self.large {
+            Some(large) if large.contains(node_pointer) => large.is_marked(node_pointer),
+            _ => self.marks.is_marked(usize::from(node_pointer)),
+        }
+    }
+
+    /// whether a node is in one of the regions being evacuated
+    #[inline(always)]
+    fn evacuating(&self, evacuating: &[bool], node_pointer: NodePointer) -> bool {
+        !self.is_large(node_pointer) && evacuating[self.region(node_pointer)]
+    }
+
+    #[inline(always)]
+    fn region(&self, node_pointer: NodePointer) -> usize {
+        usize::from(node_pointer) / self.region_size
+    }
+
+    /// adds `parent` to the remembered set of the region `child` is in, if
+    /// they're in different regions
+    #[inline(always)]
+    fn remember(&mut self, parent: NodePointer, child: NodePointer) {
+        if self.is_large(child) {
+            return;
+        }
+        let region = self.region(child);
+        if self.is_large(parent) || self.region(parent) != region {
+            self.regions[region].remembered_set.insert(parent);
+        }
+    }
+
+    /// takes the lowest free region
+    fn take_free_region(&mut self) -> Option<usize> {
+        self.free_regions.pop()
+    }
+
+    fn free_region(&mut self, region: usize) {
+        let region = &mut self.regions[region];
+        region.top = region.start;
+        region.live = 0;
+        region.remembered_set.clear();
+    }
+
+    /// marks everything reachable breadth-first, counting up what's live in
+    /// each region
+    fn mark(&mut self, stack: &Stack) {
+        self.regions.iter_mut().for_each(|region| region.live = 0);
+        let mut worklist: VecDeque<NodePointer> = VecDeque::new();
+        for root in &stack.roots {
+            for child in &root.children {
+                worklist.push_back(*child);
+            }
+        }
+        while let Some(node_pointer) = worklist.pop_front() {
+            let idx = usize::from(node_pointer);
+            if self.is_marked(node_pointer) {
+                continue;
+            }
+            match &mut self.large {
+                Some(large) if large.contains(node_pointer) => {
+                    large.mark(node_pointer);
+                }
+                _ => {
+                    self.marks.mark(idx);
+                    let region = self.region(node_pointer);
+                    self.regions[region].live += 1;
+                }
+            }
+            for child in &self.committed_memory[idx].children {
+                worklist.push_back(*child);
+            }
+        }
+    }
+
+    /// picks the regions with the most garbage, as long as they're within the
+    /// budget and what's live in them fits into the free regions
+    fn collection_set(&self) -> Vec<usize> {
+        let mut candidates: Vec<usize> = (0..self.regions.len())
+            .filter(|region| self.regions[*region].garbage() > 0)
+            .collect();
+        candidates.sort_by_key(|region| std::cmp::Reverse(self.regions[*region].garbage()));
+
+        let mut room: usize = self
+            .free_regions
+            .iter()
+            .map(|region| self.regions[*region].end - self.regions[*region].start)
+            .sum();
+        let mut collection_set = Vec::new();
+        for region in candidates {
+            if collection_set.len() >= self.evacuation_budget {
+                break;
+            }
+            let live = self.regions[region].live;
+            if live <= room {
+                room -= live;
+                collection_set.push(region);
+            }
+        }
+        collection_set
+    }
+
+    /// copies the live nodes out of every region in the collection set, then
+    /// frees those regions
+    fn evacuate(&mut self, stack: &mut Stack, collection_set: &[usize]) {
+        let mut evacuating = vec![false; self.regions.len()];
+        for region in collection_set {
+            evacuating[*region] = true;
+            if self.current == Some(*region) {
+                self.current = None;
+            }
+        }
+
+        // 1. copy everything live out, in address order, bump allocating into
+        // free regions
+        let mut target: Option<usize> = None;
+        let mut copies = Vec::new();
+        for region in collection_set {
+            for idx in self.regions[*region].start..self.regions[*region].top {
+                if !self.marks.is_marked(idx) {
+                    continue;
+                }
+                let to = match target {
+                    Some(to) if self.regions[to].top < self.regions[to].end => to,
+                    // there's always room, since the collection set was picked
+                    // to fit
+                    _ => self.take_free_region().unwrap(),
+                };
+                target = Some(to);
+                let new_idx = self.regions[to].top;
+                self.regions[to].top += 1;
+                self.regions[to].live += 1;
+                self.committed_memory.swap(idx, new_idx);
+                self.committed_memory[new_idx].forwarding_address = None;
+                self.committed_memory[idx].forwarding_address = Some(new_idx.into());
+                self.marks.mark(new_idx);
+                copies.push(NodePointer::from(new_idx));
+                self.evacuated += 1;
+            }
+        }
+        // the last region that got copied into still has room, so allocation
+        // carries on in it
+        if self.current.is_none() {
+            self.current = target;
+        }
+
+        let forwarded = |heap: &Self, node_pointer: NodePointer| {
+            if heap.evacuating(&evacuating, node_pointer) {
+                heap.committed_memory[usize::from(node_pointer)]
+                    .forwarding_address
+                    .unwrap()
+            } else {
+                node_pointer
+            }
+        };
+
+        // 2. fix up every pointer into the collection set. That's the roots,
+        // the copies themselves, and whatever the remembered sets say might be
+        // pointing in from elsewhere
+        for root in &mut stack.roots {
+            for child in &mut root.children {
+                *child = forwarded(self, *child);
+            }
+        }
+        let mut parents = copies;
+        for region in collection_set {
+            for parent in &self.regions[*region].remembered_set {
+                // anything in the collection set has already been copied, and
+                // anything that isn't marked is garbage, or a stale entry
+                if !self.evacuating(&evacuating, *parent) && self.is_marked(*parent) {
+                    parents.push(*parent);
+                }
+            }
+        }
+        for parent in parents {
+            let idx = usize::from(parent);
+            for i in 0..self.committed_memory[idx].children.len() {
+                let child = forwarded(self, self.committed_memory[idx].children[i]);
+                self.committed_memory[idx].children[i] = child;
+                // the copies are somewhere new, so the remembered sets need to
+                // know about them
+                self.remember(parent, child);
+            }
+        }
+
+        // 3. and now the collection set is free
+        for region in collection_set {
+            self.free_region(*region);
+            self.free_regions.push(*region);
+        }
+    }
+
+    /// slides everything that's marked down to the bottom of the heap, like
+    /// `MarkCompactHeap` does, then rebuilds the regions and remembered sets
+    /// around it. Large objects stay put, but the live ones get their
+    /// references updated along with everything else
+    fn compact(&mut self, stack: &mut Stack) {
+        let size = self.end();
+        let large = self
+            .large
+            .as_ref()
+            .map_or_else(Vec::new, |large| large.marked());
+        let forwarded = |heap: &Self, node_pointer: NodePointer| {
+            if heap.is_large(node_pointer) {
+                node_pointer
+            } else {
+                heap.committed_memory[usize::from(node_pointer)]
+                    .forwarding_address
+                    .unwrap()
+            }
+        };
+
+        // 1. calculate new locations
+        let mut free = 0;
+        for idx in 0..size {
+            if self.marks.is_marked(idx) {
+                self.committed_memory[idx].forwarding_address = Some(free.into());
+                free += 1;
+            }
+        }
+
+        // 2. update references
+        let marked = (0..size).filter(|idx| self.marks.is_marked(*idx));
+        for idx in marked.chain(large.iter().map(|node_pointer| usize::from(*node_pointer))) {
+            for i in 0..self.committed_memory[idx].children.len() {
+                let child = self.committed_memory[idx].children[i];
+                self.committed_memory[idx].children[i] = forwarded(self, child);
+            }
+        }
+        for root in &mut stack.roots {
+            for child in &mut root.children {
+                *child = forwarded(self, *child);
+            }
+        }
+
+        // 3. move the nodes
+        for idx in 0..size {
+            if self.marks.is_marked(idx) {
+                let forwarding_address = self.committed_memory[idx].forwarding_address.take();
+                self.committed_memory
+                    .swap(idx, usize::from(forwarding_address.unwrap()));
+            }
+        }
+
+        // every region below `free` is full, the one it's in is where
+        // allocation carries on, and every region above it is free
+        self.free_regions.clear();
+        self.current = None;
+        for region in 0..self.regions.len() {
+            self.free_region(region);
+            let (start, end) = (self.regions[region].start, self.regions[region].end);
+            let top = free.clamp(start, end);
+            self.regions[region].top = top;
+            self.regions[region].live = top - start;
+            if top == start {
+                self.free_regions.push(region);
+            } else if top < end {
+                self.current = Some(region);
+            }
+        }
+        self.free_regions.reverse();
+
+        // the remembered sets all have to be built again from scratch
+        for idx in (0..free).chain(large.iter().map(|node_pointer| usize::from(*node_pointer))) {
+            for i in 0..self.committed_memory[idx].children.len() {
+                let child = self.committed_memory[idx].children[i];
+                self.remember(idx.into(), child);
+            }
+        }
+        self.full_collections += 1;
+    }
+
+    /// first fit in the large object space, collecting if nothing fits
+    fn alloc_large(&mut self, node: Node, size: usize, stack: &mut Stack) -> Result<NodePointer> {
+        let mut node_pointer = self.large.as_mut().unwrap().alloc(size);
+        if node_pointer.is_none() {
+            self.collect(stack)?;
+            node_pointer = self.large.as_mut().unwrap().alloc(size);
+        }
+        let node_pointer =
+            node_pointer.ok_or("gg collection didn't result in any amount of garbage collected")?;
+        // it could be pointing into regions already
+        for child in &node.children {
+            self.remember(node_pointer, *child);
+        }
+        self.committed_memory[usize::from(node_pointer)] = node;
+        Ok(node_pointer)
+    }
+}
+
+impl MemoryManager for GarbageFirstHeap {
+    // bump allocates into the current region, moving on to the lowest free
+    // region once it's full
+    fn alloc(&mut self, node: Node, stack: &mut Stack) -> Result<NodePointer> {
+        let has_room = |heap: &Self| match heap.current {
+            Some(region) => heap.regions[region].top < heap.regions[region].end,
+            None => false,
+        };
+        if !has_room(self) {
+            self.current = self.take_free_region();
+        }
+        if !has_room(self) {
+            // we've run out of regions, so we need to run gc
+            self.collect(stack)?;
+            if !has_room(self) {
+                self.current = self.take_free_region();
+            }
+            if !has_room(self) {
+                return Err(
+                    "gg collection didn't result in any amount of garbage collected".into(),
+                );
+            }
+        }
+
+        let region = self.current.unwrap();
+        let idx = self.regions[region].top;
+        self.regions[region].top += 1;
+        self.committed_memory[idx] = node;
+        for i in 0..self.committed_memory[idx].children.len() {
+            let child = self.committed_memory[idx].children[i];
+            self.remember(idx.into(), child);
+        }
+
+        Ok(NodePointer::from(idx))
+    }
+
+    // nodes are all the same size, unless they're big enough for the large
+    // object space
+    fn alloc_sized(&mut self, node: Node, size: usize, stack: &mut Stack) -> Result<NodePointer> {
+        match &self.large {
+            Some(large) if 1 + size > large.threshold => self.alloc_large(node, 1 + size, stack),
+            _ if size > 0 => Err("this heap's nodes are all the same size".into()),
+            _ => self.alloc(node, stack),
+        }
+    }
+
+    // garbage-first algorithm
+    fn collect(&mut self, stack: &mut Stack) -> Result<()> {
+        let instant = Instant::now();
+        self.mark(stack);
+
+        // regions that nothing survived in can be freed without copying
+        // anything
+        let mut freed = 0;
+        for region in 0..self.regions.len() {
+            if self.regions[region].used() > 0 && self.regions[region].live == 0 {
+                self.free_region(region);
+                self.free_regions.push(region);
+                if self.current == Some(region) {
+                    self.current = None;
+                }
+                freed += 1;
+            }
+        }
+        self.free_regions.sort_by(|a, b| b.cmp(a));
+
+        let evacuation = Instant::now();
+        let collection_set = self.collection_set();
+        freed += collection_set.len();
+        self.evacuate(stack, &collection_set);
+        self.free_regions.sort_by(|a, b| b.cmp(a));
+        self.evacuation_pauses.record(evacuation.elapsed());
+
+        if freed == 0 {
+            self.compact(stack);
+        }
+        self.marks.clear();
+        if let Some(large) = &mut self.large {
+            large.sweep();
+        }
+        self.pauses.record(instant.elapsed());
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn node_pointer_from_usize(&self, idx: usize) -> NodePointer {
+        NodePointer::from(idx)
+    }
+
+    #[inline(always)]
+    fn free(&self) -> usize {
+        // there's no single free pointer, so report the number of slots in use
+        self.regions
+            .iter()
+            .map(|region| region.used())
+            .sum::<usize>()
+            + self.large.as_ref().map_or(0, |large| large.used())
+    }
+
+    fn heap_size(&self) -> usize {
+        self.end()
+    }
+}
+
+impl Barriers for GarbageFirstHeap {
+    /// keeps the remembered sets up to date
+    #[inline(always)]
+    fn write_barrier(
+        &mut self,
+        parent: NodePointer,
+        _old: Option<NodePointer>,
+        new: Option<NodePointer>,
+    ) {
+        if let Some(new) = new {
+            self.remember(parent, new);
+        }
+    }
+}
+
+impl Nodes for GarbageFirstHeap {
+    type Children = Vec<NodePointer>;
+
+    #[inline(always)]
+    fn get(&self, node_pointer: NodePointer) -> Option<&Node> {
+        self.committed_memory.get(usize::from(node_pointer))
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self, node_pointer: NodePointer) -> Option<&mut Node> {
+        self.committed_memory.get_mut(usize::from(node_pointer))
+    }
+}
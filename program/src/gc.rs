@@ -0,0 +1,289 @@
+use std::any::Any;
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::shared::{MemoryManager, Node, NodePointer, Nodes, Payload, Stack};
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Anything that can live on the heap behind a `Gc`. `trace` has to hand
+/// every reference that a value holds onto to `visit`, in the same order
+/// every time, since that order is how they line up with the node's children
+pub trait Trace: fmt::Debug + Clone + Send + Sync + 'static {
+    fn trace(&mut self, visit: &mut dyn FnMut(&mut NodePointer));
+}
+
+// values that don't hold onto any references
+macro_rules! trace_nothing {
+    ($($t:ty),*) => {
+        $(
+            impl Trace for $t {
+                #[inline(always)]
+                fn trace(&mut self, _visit: &mut dyn FnMut(&mut NodePointer)) {}
+            }
+        )*
+    };
+}
+
+trace_nothing!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    isize,
+    f32,
+    f64,
+    String
+);
+
+impl<T: 'static> Trace for Gc<T> {
+    #[inline(always)]
+    fn trace(&mut self, visit: &mut dyn FnMut(&mut NodePointer)) {
+        visit(&mut self.node_pointer);
+    }
+}
+
+impl<T: Trace> Trace for Option<T> {
+    fn trace(&mut self, visit: &mut dyn FnMut(&mut NodePointer)) {
+        if let Some(value) = self {
+            value.trace(visit);
+        }
+    }
+}
+
+impl<T: Trace> Trace for Vec<T> {
+    fn trace(&mut self, visit: &mut dyn FnMut(&mut NodePointer)) {
+        for value in self {
+            value.trace(visit);
+        }
+    }
+}
+
+impl<A: Trace, B: Trace> Trace for (A, B) {
+    fn trace(&mut self, visit: &mut dyn FnMut(&mut NodePointer)) {
+        self.0.trace(visit);
+        self.1.trace(visit);
+    }
+}
+
+/// a `Trace` value with its type erased, so that one heap can hold onto all
+/// kinds of them
+trait Traced: Any + fmt::Debug + Send + Sync {
+    fn clone_box(&self) -> Box<dyn Traced>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Trace> Traced for T {
+    fn clone_box(&self) -> Box<dyn Traced> {
+        Box::new(self.clone())
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl Clone for Box<dyn Traced> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// The payload of a heap that `Gc`s get allocated into, e.g.
+/// `MarkCompactHeap::<Object>::init_payload(size)`. Numbers are stored as
+/// `u32`s, so the workloads, dumps and sums still work on these heaps
+#[derive(Debug, Clone, Default)]
+pub struct Object(Option<Box<dyn Traced>>);
+
+impl Object {
+    fn downcast_ref<T: Trace>(&self) -> Option<&T> {
+        self.0.as_ref()?.as_any().downcast_ref()
+    }
+
+    fn downcast_mut<T: Trace>(&mut self) -> Option<&mut T> {
+        self.0.as_mut()?.as_any_mut().downcast_mut()
+    }
+}
+
+impl From<Option<u32>> for Object {
+    fn from(value: Option<u32>) -> Self {
+        Self(value.map(|value| Box::new(value) as Box<dyn Traced>))
+    }
+}
+
+impl Payload for Object {
+    #[inline(always)]
+    fn value(&self) -> Option<u32> {
+        self.downcast_ref().copied()
+    }
+}
+
+/// A typed reference to a `T` on the heap. Allocating, dereferencing and
+/// mutating all go through the heap, which keeps the node's children in
+/// sync with whatever `T::trace` reports. Just like a `NodePointer`, a `Gc`
+/// that's held onto outside of the heap goes stale as soon as a moving
+/// collection happens, unless it's been rooted on the stack
+pub struct Gc<T> {
+    pub node_pointer: NodePointer,
+    marker: PhantomData<fn() -> T>,
+}
+
+// deriving these would need `T` to implement them, when it's only the
+// pointer that gets copied or compared
+impl<T> Clone for Gc<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Gc<T> {}
+
+impl<T> PartialEq for Gc<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.node_pointer == other.node_pointer
+    }
+}
+
+impl<T> Eq for Gc<T> {}
+
+impl<T> fmt::Debug for Gc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Gc({})", usize::from(self.node_pointer))
+    }
+}
+
+/// whether a node actually holds onto a `T` gets checked every time that
+/// it's dereferenced, so any node pointer can be turned into a `Gc`
+impl<T> From<NodePointer> for Gc<T> {
+    fn from(node_pointer: NodePointer) -> Self {
+        Self {
+            node_pointer,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> From<Gc<T>> for NodePointer {
+    fn from(gc: Gc<T>) -> Self {
+        gc.node_pointer
+    }
+}
+
+impl<T: Trace> Gc<T> {
+    /// allocates `value` on the heap. Whatever `value` points to gets rooted
+    /// until it's on the heap, in case allocating it has to collect
+    pub fn new<H: MemoryManager<Object>>(
+        value: T,
+        heap: &mut H,
+        stack: &mut Stack,
+    ) -> Result<Self> {
+        let mut value = value;
+        let mut references = Vec::new();
+        value.trace(&mut |node_pointer| references.push(*node_pointer));
+
+        let root = stack.roots.len();
+        stack.roots.push(Node::default());
+        for node_pointer in references {
+            stack.add_root(heap, root, node_pointer);
+        }
+        let node = Node {
+            value: Object(Some(Box::new(value))),
+            ..Default::default()
+        };
+        let node_pointer = heap.alloc(node, stack);
+
+        // the references might have moved, so they come from the root rather
+        // than from `value`. Dereferencing patches `value` up to match
+        let mut references = Vec::new();
+        while !stack.roots[root].children.is_empty() {
+            references.push(stack.remove_root(heap, root, 0));
+        }
+        stack.roots.pop();
+        let node_pointer = node_pointer?;
+        for child in references {
+            heap.add_child(node_pointer, child)?;
+        }
+        Ok(Self::from(node_pointer))
+    }
+
+    /// dereferences the handle, returning the `T` that it points to
+    pub fn get<'a, H: MemoryManager<Object> + Nodes<Object>>(
+        &self,
+        heap: &'a mut H,
+    ) -> Result<&'a T> {
+        Ok(self.object(heap)?)
+    }
+
+    /// mutates the `T` that the handle points to with `f`, and then writes
+    /// whatever it points to afterwards back into the heap's edges
+    pub fn update<H: MemoryManager<Object> + Nodes<Object>, R>(
+        &self,
+        heap: &mut H,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Result<R> {
+        let object = self.object(heap)?;
+        let result = f(object);
+        let mut references = Vec::new();
+        object.trace(&mut |node_pointer| references.push(*node_pointer));
+
+        // through the heap, so that the write barrier sees every change
+        let node_pointer = heap.read_barrier(self.node_pointer);
+        let len = heap.children(node_pointer).count();
+        for (idx, child) in references.iter().enumerate() {
+            if idx >= len {
+                heap.add_child(node_pointer, *child)?;
+            } else if *heap.child(node_pointer, idx)? != *child {
+                heap.set_child(node_pointer, idx, *child)?;
+            }
+        }
+        for _ in references.len()..len {
+            heap.pop_child(node_pointer)?;
+        }
+        Ok(result)
+    }
+
+    /// replaces the `T` that the handle points to
+    pub fn set<H: MemoryManager<Object> + Nodes<Object>>(
+        &self,
+        heap: &mut H,
+        value: T,
+    ) -> Result<()> {
+        self.update(heap, |object| *object = value)
+    }
+
+    /// finds the `T` on the heap, with its references patched up to wherever
+    /// the collector has moved the node's children since
+    fn object<'a, H: MemoryManager<Object> + Nodes<Object>>(
+        &self,
+        heap: &'a mut H,
+    ) -> Result<&'a mut T> {
+        let node_pointer = heap.read_barrier(self.node_pointer);
+        let children: Vec<NodePointer> = heap
+            .children(node_pointer)
+            .map(|child| heap.read_barrier(child))
+            .collect();
+        let object = heap
+            .get_mut(node_pointer)
+            .ok_or("node isn't on the heap")?
+            .value
+            .downcast_mut::<T>()
+            .ok_or("node doesn't hold onto that type")?;
+        let mut children = children.into_iter();
+        object.trace(&mut |node_pointer| {
+            if let Some(child) = children.next() {
+                *node_pointer = child;
+            }
+        });
+        Ok(object)
+    }
+}
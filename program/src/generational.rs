@@ -0,0 +1,253 @@
+use std::collections::VecDeque;
+
+use crate::mark_compact::MarkCompactHeap;
+use crate::shared::{Barriers, MemoryManager, Node, NodePointer, Nodes, Stack};
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// This generational algorithm bump allocates into a nursery that sits right
+/// on top of the old generation, like Appel's collector. The layout of the
+/// memory looks like
+///
+/// ```text
+/// [ old generation | nursery | unused ]
+/// 0             boundary    free     heap_size
+/// ```
+///
+/// A minor collection only traces the nursery (using the remembered set to
+/// find old objects that point into it), then evacuates the survivors in
+/// address order to the old generation's bump pointer, which is `boundary`.
+/// Since survivors only ever move downwards, evacuating in address order
+/// never clobbers a survivor that hasn't been evacuated yet, so unlike
+/// stop-and-copy we don't need a copy reserve. A major collection is only run
+/// when the old generation fills the whole heap, and it's just the LISP-2
+/// collection from `mark_compact.rs`.
+///
+/// With a large object space, large objects are allocated straight into the
+/// old generation, so the write barrier remembers them just like any other
+/// old object. Only a major collection sweeps them
+#[derive(Clone)]
+pub struct GenerationalHeap {
+    // the whole memory, old generation and nursery, is managed by a mark
+    // compact heap, whose free pointer doubles as the nursery's bump pointer
+    pub heap: MarkCompactHeap,
+    // where the old generation ends and the nursery begins
+    pub boundary: usize,
+    // the maximum number of objects allocated between minor collections
+    pub nursery_size: usize,
+    // old objects that might point into the nursery
+    pub remembered_set: Vec<NodePointer>,
+    // whether or not an old object is already in the remembered set, so it
+    // doesn't get added twice
+    pub remembered: Vec<bool>,
+    pub minor_collections: usize,
+    pub major_collections: usize,
+}
+
+impl GenerationalHeap {
+    /// uses an eighth of the heap as the nursery
+    pub fn init(size: usize) -> Self {
+        Self::init_with_nursery(size, size / 8)
+    }
+
+    pub fn init_with_nursery(size: usize, nursery_size: usize) -> Self {
+        Self::init_with_heap(MarkCompactHeap::init(size), nursery_size)
+    }
+
+    /// the same as `init`, along with a large object space of `large_size`
+    /// slots, for nodes taking up more than `threshold` slots
+    pub fn init_large(size: usize, threshold: usize, large_size: usize) -> Self {
+        Self::init_with_heap(
+            MarkCompactHeap::init_large(size, threshold, large_size),
+            size / 8,
+        )
+    }
+
+    fn init_with_heap(heap: MarkCompactHeap, nursery_size: usize) -> Self {
+        let size = heap.committed_memory.len();
+        Self {
+            heap,
+            boundary: 0,
+            // a nursery without any room in it would just collect forever
+            nursery_size: nursery_size.max(1),
+            remembered_set: Vec::new(),
+            remembered: vec![false; size],
+            minor_collections: 0,
+            major_collections: 0,
+        }
+    }
+
+    /// the nursery ends `nursery_size` after the boundary, or at the end of the
+    /// heap, whichever comes first
+    #[inline(always)]
+    fn nursery_top(&self) -> usize {
+        (self.boundary + self.nursery_size).min(self.heap.end())
+    }
+
+    /// adds an old object to the remembered set, if it isn't already in it.
+    /// Large objects are always old
+    #[inline(always)]
+    fn remember(&mut self, node_pointer: NodePointer) {
+        let idx = usize::from(node_pointer);
+        if (idx < self.boundary || idx >= self.heap.end()) && !self.remembered[idx] {
+            self.remembered[idx] = true;
+            self.remembered_set.push(node_pointer);
+        }
+    }
+
+    /// collects only the nursery, promoting everything that survives into the
+    /// old generation
+    pub fn minor_collect(&mut self, stack: &mut Stack) -> Result<()> {
+        // the roots are the stack, plus every old object that we remember
+        // pointing into the nursery
+        let mut worklist: VecDeque<NodePointer> = VecDeque::new();
+        for root in &stack.roots {
+            for child in &root.children {
+                worklist.push_back(*child);
+            }
+        }
+        for node_pointer in &self.remembered_set {
+            for child in &self.heap.get(*node_pointer).unwrap().children {
+                worklist.push_back(*child);
+            }
+        }
+
+        // mark the nursery without tracing into the old generation, then
+        // evacuate the survivors down to the boundary
+        self.heap.mark_from(worklist, self.boundary);
+        self.heap
+            .compact(stack, self.boundary, &self.remembered_set);
+
+        // everything that survived is old now, so the nursery is empty and
+        // nothing old can point into it anymore
+        self.boundary = self.heap.free;
+        self.forget();
+        self.minor_collections += 1;
+        Ok(())
+    }
+
+    /// collects the whole heap, which also promotes anything left in the
+    /// nursery
+    pub fn major_collect(&mut self, stack: &mut Stack) -> Result<()> {
+        self.heap.collect(stack)?;
+        self.boundary = self.heap.free;
+        self.forget();
+        self.major_collections += 1;
+        Ok(())
+    }
+
+    /// first fit in the large object space, which is part of the old
+    /// generation, running a major collection if nothing fits, since that's
+    /// the only thing that sweeps it
+    fn alloc_large(&mut self, node: Node, size: usize, stack: &mut Stack) -> Result<NodePointer> {
+        let mut node_pointer = self.heap.large.as_mut().unwrap().alloc(size);
+        if node_pointer.is_none() {
+            self.major_collect(stack)?;
+            node_pointer = self.heap.large.as_mut().unwrap().alloc(size);
+        }
+        let node_pointer =
+            node_pointer.ok_or("gg collection didn't result in any amount of garbage collected")?;
+        // it's old already, so anything in the nursery that it starts out
+        // pointing at needs remembering
+        for child in &node.children {
+            self.write_barrier(node_pointer, None, Some(*child));
+        }
+        self.heap.committed_memory[usize::from(node_pointer)] = node;
+        Ok(node_pointer)
+    }
+
+    /// empties the remembered set, once there's no nursery left to point into
+    fn forget(&mut self) {
+        for node_pointer in self.remembered_set.drain(..) {
+            self.remembered[usize::from(node_pointer)] = false;
+        }
+    }
+}
+
+impl MemoryManager for GenerationalHeap {
+    // allocates a new node into the nursery
+    fn alloc(&mut self, node: Node, stack: &mut Stack) -> Result<NodePointer> {
+        // if the nursery is full
+        if self.heap.free >= self.nursery_top() {
+            // promote whatever is alive in it
+            self.minor_collect(stack)?;
+            // and if that leaves the old generation taking up the whole heap,
+            // it's time to collect the old generation too
+            if self.heap.free >= self.heap.end() {
+                self.major_collect(stack)?;
+            }
+        }
+        if self.heap.free >= self.nursery_top() {
+            return Err("gg collection didn't result in any amount of garbage collected".into());
+        }
+
+        // set the node id to where the top of the nursery is
+        let node_pointer = NodePointer::from(self.heap.free);
+        // add it to the heap
+        self.heap.committed_memory[usize::from(node_pointer)] = node;
+        // bump the free pointer
+        self.heap.free += 1;
+
+        Ok(node_pointer)
+    }
+
+    // nodes are all the same size, unless they're big enough for the large
+    // object space
+    fn alloc_sized(&mut self, node: Node, size: usize, stack: &mut Stack) -> Result<NodePointer> {
+        match &self.heap.large {
+            Some(large) if 1 + size > large.threshold => self.alloc_large(node, 1 + size, stack),
+            _ if size > 0 => Err("this heap's nodes are all the same size".into()),
+            _ => self.alloc(node, stack),
+        }
+    }
+
+    /// a full collection, which is just a major collection
+    fn collect(&mut self, stack: &mut Stack) -> Result<()> {
+        self.major_collect(stack)
+    }
+
+    #[inline(always)]
+    fn node_pointer_from_usize(&self, idx: usize) -> NodePointer {
+        NodePointer::from(idx)
+    }
+
+    #[inline(always)]
+    fn free(&self) -> usize {
+        self.heap.free()
+    }
+
+    fn heap_size(&self) -> usize {
+        self.heap.end()
+    }
+}
+
+impl Barriers for GenerationalHeap {
+    /// remembers old objects that get a reference into the nursery written
+    /// into them
+    #[inline(always)]
+    fn write_barrier(
+        &mut self,
+        parent: NodePointer,
+        _old: Option<NodePointer>,
+        new: Option<NodePointer>,
+    ) {
+        if let Some(new) = new {
+            if (self.boundary..self.heap.end()).contains(&usize::from(new)) {
+                self.remember(parent);
+            }
+        }
+    }
+}
+
+impl Nodes for GenerationalHeap {
+    type Children = Vec<NodePointer>;
+
+    #[inline(always)]
+    fn get(&self, node_pointer: NodePointer) -> Option<&Node> {
+        self.heap.get(node_pointer)
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self, node_pointer: NodePointer) -> Option<&mut Node> {
+        self.heap.get_mut(node_pointer)
+    }
+}
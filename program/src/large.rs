@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+
+use crate::shared::{MarkBitmap, NodePointer};
+
+/// The non-moving space that objects bigger than `threshold` slots get
+/// allocated into, so that they never get compacted or copied. The slots
+/// themselves live in the heap's own memory, from `start` to `end`, right
+/// after the rest of the heap, and this only keeps track of which of them are
+/// in use. The heap's collector marks objects in here along with the rest of
+/// the heap, and then they get swept into a free list of chunks.
+///
+/// Every tracing heap can have one. `RefCountHeap` can't, since it frees
+/// nodes as soon as their count drops, rather than sweeping anything
+#[derive(Debug, Clone)]
+pub struct LargeObjectSpace {
+    // objects taking up more than this many slots go in here
+    pub threshold: usize,
+    // where the space starts and ends in the heap's memory
+    pub start: usize,
+    pub end: usize,
+    // chunks that are free to allocate into, as `(start, size)`, lowest
+    // address first
+    pub free_list: Vec<(usize, usize)>,
+    // every object that's in use, and how many slots it takes up
+    pub objects: BTreeMap<usize, usize>,
+    // the mark bits for the objects in here, from `start` on
+    pub marks: MarkBitmap,
+    // objects that have been marked, but haven't had their children scanned
+    // yet, for collectors that don't keep their gray nodes anywhere else
+    pub gray: Vec<NodePointer>,
+}
+
+impl LargeObjectSpace {
+    /// a space of `size` slots, starting at `start`
+    pub fn init(threshold: usize, start: usize, size: usize) -> Self {
+        Self {
+            threshold,
+            start,
+            end: start + size,
+            free_list: if size > 0 {
+                vec![(start, size)]
+            } else {
+                Vec::new()
+            },
+            objects: BTreeMap::new(),
+            marks: MarkBitmap::init(size),
+            gray: Vec::new(),
+        }
+    }
+
+    /// how many slots the objects in here take up
+    pub fn used(&self) -> usize {
+        self.objects.values().sum()
+    }
+
+    /// whether an object lives in here
+    #[inline(always)]
+    pub fn contains(&self, node_pointer: NodePointer) -> bool {
+        (self.start..self.end).contains(&usize::from(node_pointer))
+    }
+
+    /// first fit, splitting whatever's left of the chunk off into a smaller
+    /// one. Returns where the object goes, if it fits anywhere
+    pub fn alloc(&mut self, size: usize) -> Option<NodePointer> {
+        let position = self
+            .free_list
+            .iter()
+            .position(|(_, chunk)| *chunk >= size)?;
+        let (start, chunk) = self.free_list.remove(position);
+        if chunk > size {
+            self.free_list
+                .insert(position, (start + size, chunk - size));
+        }
+        self.objects.insert(start, size);
+        Some(start.into())
+    }
+
+    #[inline(always)]
+    pub fn is_marked(&self, node_pointer: NodePointer) -> bool {
+        self.marks.is_marked(usize::from(node_pointer) - self.start)
+    }
+
+    /// marks an object, returning false if it was already marked
+    #[inline(always)]
+    pub fn mark(&mut self, node_pointer: NodePointer) -> bool {
+        if self.is_marked(node_pointer) {
+            return false;
+        }
+        self.marks.mark(usize::from(node_pointer) - self.start);
+        true
+    }
+
+    /// every object that's been marked, lowest address first
+    pub fn marked(&self) -> Vec<NodePointer> {
+        self.objects
+            .keys()
+            .map(|idx| NodePointer::from(*idx))
+            .filter(|node_pointer| self.is_marked(*node_pointer))
+            .collect()
+    }
+
+    /// frees every unmarked object, and unmarks the rest. Whatever's between
+    /// the objects that are left makes up the new free list, so free chunks
+    /// next to each other get merged
+    pub fn sweep(&mut self) {
+        let (marks, start) = (&self.marks, self.start);
+        self.objects.retain(|idx, _| marks.is_marked(idx - start));
+        self.marks.clear();
+        self.gray.clear();
+
+        self.free_list.clear();
+        let mut free = self.start;
+        for (idx, size) in &self.objects {
+            if *idx > free {
+                self.free_list.push((free, idx - free));
+            }
+            free = idx + size;
+        }
+        if self.end > free {
+            self.free_list.push((free, self.end - free));
+        }
+    }
+}
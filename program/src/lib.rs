@@ -3,13 +3,25 @@ use std::collections::VecDeque;
 use rand::prelude::*;
 use rand_pcg::Pcg64;
 
-use shared::{MemoryManager, Node, NodePointer, Stack};
+use shared::{MemoryManager, Node, NodePointer, Payload, Stack};
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+pub mod gc;
+pub mod large;
 pub mod shared;
 
+pub mod adaptive;
+pub mod concurrent;
+pub mod flat;
+pub mod garbage_first;
+pub mod generational;
 pub mod mark_compact;
+pub mod mark_region;
+pub mod mark_sweep;
+pub mod parallel;
+pub mod ref_count;
 pub mod stop_copy;
+pub mod treadmill;
 
 // testing stuff below
 
@@ -20,7 +32,7 @@ pub fn init_log() {
     let _ = env_logger::builder().is_test(true).try_init();
 }
 
-pub fn recursively_add_children<T: MemoryManager>(
+pub fn recursively_add_children<P: Payload, T: MemoryManager<P>>(
     parent_node_pointer: NodePointer,
     max_objects: usize,
     stack: &mut Stack,
@@ -43,15 +55,12 @@ pub fn recursively_add_children<T: MemoryManager>(
             if current_objects < max_objects {
                 // create a new node on the heap
                 let node = Node {
-                    value: Some(current_objects as u32),
+                    value: Some(current_objects as u32).into(),
                     ..Default::default()
                 };
                 let child_node_pointer = heap.alloc(node, stack).unwrap();
                 // add the node as a child of node_pointer
-                heap.get_mut(parent_node_pointer)
-                    .unwrap()
-                    .children
-                    .push(child_node_pointer);
+                heap.add_child(parent_node_pointer, child_node_pointer)?;
                 // push the child into the worklist
                 worklist.push_back(child_node_pointer);
                 current_objects += 1;
@@ -61,13 +70,16 @@ pub fn recursively_add_children<T: MemoryManager>(
     Ok(())
 }
 
-pub fn seed_root<T: MemoryManager>(stack: &mut Stack, heap: &mut T) -> Result<NodePointer> {
+pub fn seed_root<P: Payload, T: MemoryManager<P>>(
+    stack: &mut Stack,
+    heap: &mut T,
+) -> Result<NodePointer> {
     let temp = Node {
-        value: Some(1),
+        value: Some(1).into(),
         ..Default::default()
     };
     let node_pointer = heap.alloc(temp, stack).unwrap();
-    stack.roots[0].children.push(node_pointer);
+    stack.add_root(heap, 0, node_pointer);
     Ok(node_pointer)
 }
 
@@ -118,7 +130,7 @@ pub fn seed_root<T: MemoryManager>(stack: &mut Stack, heap: &mut T) -> Result<No
 
 /// takes in a reference to stack and heap, and returns a clone deleted with
 /// specification to the ratio included
-pub fn make_garbage<T: MemoryManager + Clone>(
+pub fn make_garbage<P: Payload, T: MemoryManager<P> + Clone>(
     _stack: &mut Stack,
     heap: &mut T,
     garbage_ratio: f32,
@@ -154,10 +166,7 @@ pub fn make_garbage<T: MemoryManager + Clone>(
             // generate two random numbers
             let num = rng.gen_range(lowest_layer..highest_layer);
             // link child before point of removal to parent
-            heap.get_mut(heap.node_pointer_from_usize(num))
-                .unwrap()
-                .children
-                .pop();
+            heap.pop_child(heap.node_pointer_from_usize(num))?;
         }
     }
     Ok(())
@@ -165,7 +174,11 @@ pub fn make_garbage<T: MemoryManager + Clone>(
 
 /// takes in a mutable reference to stack and heap, linking them to a ratio of
 /// around 2 edges to : 1 node?
-pub fn link_heap<T: MemoryManager>(stack: &mut Stack, heap: &mut T, rng: &mut Pcg64) -> Result<()> {
+pub fn link_heap<P: Payload, T: MemoryManager<P>>(
+    stack: &mut Stack,
+    heap: &mut T,
+    rng: &mut Pcg64,
+) -> Result<()> {
     // get number of powers of two (so we can know how many layers of binary
     // tree there are)
 
@@ -187,7 +200,7 @@ pub fn link_heap<T: MemoryManager>(stack: &mut Stack, heap: &mut T, rng: &mut Pc
                 heap.node_pointer_from_usize(first),
                 heap.node_pointer_from_usize(second),
             );
-            heap.get_mut(first).unwrap().children.push(second);
+            heap.add_child(first, second)?;
         }
     }
     // run gc
@@ -195,3 +208,114 @@ pub fn link_heap<T: MemoryManager>(stack: &mut Stack, heap: &mut T, rng: &mut Pc
     // stack.dump_all(heap).unwrap();
     Ok(())
 }
+
+/// how much payload the nodes that the sized workloads allocate carry, in
+/// slots
+#[derive(Debug, Clone, Copy)]
+pub enum SizeDistribution {
+    Fixed(usize),
+    // anywhere from `min` to `max`, inclusive
+    Uniform {
+        min: usize,
+        max: usize,
+    },
+    // mostly `small` nodes, with a `ratio` of them being `large`
+    Bimodal {
+        small: usize,
+        large: usize,
+        ratio: f32,
+    },
+}
+
+impl SizeDistribution {
+    pub fn sample(&self, rng: &mut Pcg64) -> usize {
+        match *self {
+            Self::Fixed(size) => size,
+            Self::Uniform { min, max } => rng.gen_range(min..=max),
+            Self::Bimodal {
+                small,
+                large,
+                ratio,
+            } => {
+                if rng.gen::<f32>() < ratio {
+                    large
+                } else {
+                    small
+                }
+            }
+        }
+    }
+
+    /// how much payload a node carries on average
+    pub fn mean(&self) -> f32 {
+        match *self {
+            Self::Fixed(size) => size as f32,
+            Self::Uniform { min, max } => (min + max) as f32 / 2.,
+            Self::Bimodal {
+                small,
+                large,
+                ratio,
+            } => small as f32 * (1. - ratio) + large as f32 * ratio,
+        }
+    }
+}
+
+/// the same as `link_heap`, but for `num_nodes` nodes, each with a payload
+/// sized by `sizes`. Once nodes aren't all the same size,
+/// `node_pointer_from_usize` can't find them anymore, so every node gets
+/// returned in the order that it was allocated in instead
+pub fn link_heap_sized<P: Payload, T: MemoryManager<P>>(
+    stack: &mut Stack,
+    heap: &mut T,
+    num_nodes: usize,
+    sizes: SizeDistribution,
+    rng: &mut Pcg64,
+) -> Result<Vec<NodePointer>> {
+    // the same binary tree as `recursively_add_children`
+    let mut nodes = vec![seed_root(stack, heap)?];
+    let mut parent = 0;
+    while nodes.len() < num_nodes {
+        for _ in 0..2 {
+            if nodes.len() < num_nodes {
+                let node = Node {
+                    value: Some(nodes.len() as u32 - 1).into(),
+                    ..Default::default()
+                };
+                let child_node_pointer = heap.alloc_sized(node, sizes.sample(rng), stack)?;
+                heap.add_child(nodes[parent], child_node_pointer)?;
+                nodes.push(child_node_pointer);
+            }
+        }
+        parent += 1;
+    }
+
+    // create number of links equal to number of nodes, randomly from anywhere to anywhere
+    for _ in 0..num_nodes {
+        let (first, second) = (
+            rng.gen_range(0..(num_nodes / 2)),
+            rng.gen_range(num_nodes / 2..num_nodes),
+        );
+        heap.add_child(nodes[first], nodes[second])?;
+    }
+    Ok(nodes)
+}
+
+/// the same as `make_garbage`, but for the nodes that `link_heap_sized`
+/// returned
+pub fn make_garbage_sized<P: Payload, T: MemoryManager<P>>(
+    heap: &mut T,
+    nodes: &[NodePointer],
+    garbage_ratio: f32,
+    rng: &mut Pcg64,
+) -> Result<()> {
+    let layers = (1. + nodes.len() as f32).log2().floor() as u32;
+
+    let lowest_layer = 2_usize.pow(layers - 7);
+    let highest_layer = 2_usize.pow(layers - 5);
+
+    for _ in 0..(((highest_layer - lowest_layer) * 4) as f32 * garbage_ratio) as usize {
+        let num = rng.gen_range(lowest_layer..highest_layer);
+        heap.pop_child(nodes[num])?;
+    }
+    Ok(())
+}
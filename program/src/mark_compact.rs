@@ -1,27 +1,173 @@
 // use bitvec::prelude::*;
 use std::collections::VecDeque;
+use std::time::Instant;
 
+use crate::large::LargeObjectSpace;
+use crate::parallel;
 use crate::shared::*;
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// how an incremental mark-compact heap spreads out its marking
+#[derive(Debug, Clone, Copy)]
+pub struct Incremental {
+    // how many gray nodes get scanned every time we allocate
+    pub quantum: usize,
+    // marking starts once this many slots are in use. Marking has to finish
+    // before the heap fills up, otherwise whatever's left gets done in one
+    // big pause, so this should leave at least (live nodes / quantum) slots
+    pub trigger: usize,
+}
+
+/// how marking traverses the graph. Only one of these can be picked, so
+/// there's no way to ask for e.g. a bounded mark stack on several threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkOrder {
+    // the worklist is a queue
+    Bfs,
+    // the worklist is a mark stack
+    Dfs,
+    // Deutsch-Schorr-Waite, which doesn't have a worklist at all. It's
+    // depth-first too
+    PointerReversal,
+    // depth-first, with a mark stack that never holds more than `capacity`
+    // nodes. Whatever doesn't fit gets found again by rescanning the heap
+    Bounded { capacity: usize },
+    // split up between `threads` threads, which steal work off each other,
+    // so there's no telling what order nodes get marked in
+    Parallel { threads: usize },
+}
+
+/// how marked nodes get compacted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compaction {
+    // sliding, in three passes over the heap
+    Lisp2,
+    // Edwards' two-finger algorithm, which moves the highest live nodes into
+    // the lowest holes. It only takes two passes, but doesn't keep nodes in
+    // the order they were allocated in
+    TwoFinger,
+    // Jonkers' threaded compaction, which slides like LISP-2, but never
+    // touches the forwarding address
+    Threaded,
+    // the Compressor's compaction, which works out forwarding addresses from
+    // the mark bitmap, so it can update references and slide nodes in one
+    // pass
+    Compressor,
+}
+
+impl Compaction {
+    /// how many bytes every node has to set aside just for compaction, on top
+    /// of a node without a forwarding address. Side tables get spread out over
+    /// the nodes that they cover, so this can be a fraction of a byte
+    pub fn node_overhead(self) -> f64 {
+        let word = std::mem::size_of::<Option<NodePointer>>() as f64;
+        match self {
+            // the forwarding address
+            Compaction::Lisp2 | Compaction::TwoFinger => word,
+            // nothing, the threads go through the parent pointer, which a
+            // node has all the same
+            Compaction::Threaded => 0.0,
+            // a bit in the mark bitmap, which has to stick around until the
+            // nodes have moved, plus an offset table entry for every 64 nodes
+            Compaction::Compressor => (1.0 + std::mem::size_of::<usize>() as f64 / 8.0) / 8.0,
+        }
+    }
+
+    /// how big a node would be without the forwarding address, plus whatever
+    /// this compaction needs. A `Node` has a forwarding address already, so
+    /// this is `size_of::<Node>()` for LISP-2, and anything less is a saving
+    pub fn node_size(self) -> f64 {
+        (std::mem::size_of::<Node>() - std::mem::size_of::<Option<NodePointer>>()) as f64
+            + self.node_overhead()
+    }
+}
+
+// while a node's references are threaded, its parent pointer points at the
+// first child slot in its thread, which holds the next slot in the thread, and
+// so on. The last slot holds whatever the parent pointer was to begin with.
+// A slot gets packed into a node pointer, with the top bit set so that it can't
+// be mistaken for a real one
+const THREAD: usize = 1 << 63;
+// the slot is in a root on the stack instead of in the heap
+const ROOT_SLOT: usize = 1 << 62;
+const SLOT_BITS: usize = 31;
+// the end of a thread, if the parent pointer was none
+const NO_PARENT: usize = THREAD - 1;
+
+#[inline(always)]
+fn thread_slot(root: bool, idx: usize, i: usize) -> usize {
+    debug_assert!(idx < 1 << SLOT_BITS && i < 1 << SLOT_BITS);
+    THREAD | if root { ROOT_SLOT } else { 0 } | idx << SLOT_BITS | i
+}
+
+/// the child slot that a packed slot refers to
+#[inline(always)]
+fn threaded<'a, P, C: Children>(
+    committed_memory: &'a mut [Node<P, C>],
+    stack: &'a mut Stack,
+    slot: usize,
+) -> &'a mut NodePointer {
+    let idx = (slot & !(THREAD | ROOT_SLOT)) >> SLOT_BITS;
+    let i = slot & ((1 << SLOT_BITS) - 1);
+    if slot & ROOT_SLOT != 0 {
+        &mut stack.roots[idx].children[i]
+    } else {
+        &mut committed_memory[idx].children[i]
+    }
+}
+
 /// This mark-compact algorithm uses the LISP-2 style sliding algorithm Heap
 /// includes the graph data structure, and acts pretty much like an arena
 #[derive(Clone)]
-pub struct MarkCompactHeap {
+pub struct MarkCompactHeap<P = Option<u32>, C = Vec<NodePointer>> {
     // the `top` of the memory != strip.len() because we don't want to have to
     // zero them out if we don't need to, and don't want to push / pop the vec
     // especially when we're compacting
-    pub committed_memory: Vec<Node>,
+    pub committed_memory: Vec<Node<P, C>>,
     // pub marked_node_pointers: Vec<NodePointer>,
     // // when the length of vector len reaches the max pub max_size: usize, //
     // the size of the top, where the last piece of recognizable memory is. 1
     // less than strip.len() pub top: usize, pub max: usize,
     pub free: usize,
+    // the mark bits, which used to be stored by setting `forwarding_address`
+    // to anything at all. Now `forwarding_address` is only ever set during
+    // compaction
+    pub marks: MarkBitmap,
+    pub mark_order: MarkOrder,
+    // how many times a node didn't fit on the bounded mark stack
+    pub mark_stack_overflows: usize,
+    pub compaction: Compaction,
+    // if this is set, marking happens a little bit at a time on every
+    // allocation instead of all at once in `collect`. Compaction is still
+    // stop-the-world
+    pub incremental: Option<Incremental>,
+    // whether we're in the middle of an incremental mark
+    pub marking: bool,
+    // marked nodes whose children haven't been scanned yet. Unmarked nodes
+    // are white, marked nodes in here are gray, and every other marked node
+    // is black
+    pub gray: VecDeque<NodePointer>,
+    // a node with a payload of `size` slots takes up `1 + size` of them, and
+    // only the first one holds the node. Every compaction but two-finger
+    // moves them all together
+    pub sizes: SizeTable,
+    // nodes bigger than its threshold go in here instead, which comes after
+    // the rest of `committed_memory`
+    pub large: Option<LargeObjectSpace>,
+    // how many slots compaction has had to move
+    pub copied: usize,
+    pub pauses: Pauses,
+    // how long just the mark phase of every `collect` took
+    pub mark_phases: Pauses,
 }
 
-impl MarkCompactHeap {
-    pub fn init(size: usize) -> Self {
-        let mut committed_memory: Vec<Node> = Vec::new();
+impl<P: Payload, C: Children> MarkCompactHeap<P, C> {
+    /// the same as `init`, but for nodes carrying any kind of payload, with
+    /// their children laid out any way. The rest of the constructors work for
+    /// any of them too, e.g.
+    /// `MarkCompactHeap::<Object, Inline<ARITY>>::init_compacting(size, compaction)`
+    pub fn init_payload(size: usize) -> Self {
+        let mut committed_memory: Vec<Node<P, C>> = Vec::new();
         for _ in 0..size {
             committed_memory.push(Node::default());
         }
@@ -30,90 +176,555 @@ impl MarkCompactHeap {
             committed_memory,
             // marked_node_pointers,
             free: 0,
+            marks: MarkBitmap::init(size),
+            mark_order: MarkOrder::Bfs,
+            mark_stack_overflows: 0,
+            compaction: Compaction::Lisp2,
+            incremental: None,
+            marking: false,
+            gray: VecDeque::new(),
+            sizes: SizeTable::default(),
+            large: None,
+            copied: 0,
+            pauses: Pauses::default(),
+            mark_phases: Pauses::default(),
+        }
+    }
+
+    pub fn init_ordered(size: usize, mark_order: MarkOrder) -> Self {
+        Self {
+            mark_order,
+            ..Self::init_payload(size)
+        }
+    }
+
+    pub fn init_compacting(size: usize, compaction: Compaction) -> Self {
+        Self {
+            compaction,
+            ..Self::init_payload(size)
+        }
+    }
+
+    pub fn init_bounded(size: usize, mark_stack_capacity: usize) -> Self {
+        // an empty mark stack can't mark anything
+        let capacity = mark_stack_capacity.max(1);
+        Self::init_ordered(size, MarkOrder::Bounded { capacity })
+    }
+
+    pub fn init_parallel(size: usize, mark_threads: usize) -> Self {
+        let threads = mark_threads.max(1);
+        Self::init_ordered(size, MarkOrder::Parallel { threads })
+    }
+
+    pub fn init_incremental(size: usize, incremental: Incremental) -> Self {
+        Self {
+            incremental: Some(incremental),
+            ..Self::init_payload(size)
         }
     }
+
+    /// the same as `init_payload`, along with a large object space of
+    /// `large_size` slots, for nodes taking up more than `threshold` slots
+    pub fn init_large(size: usize, threshold: usize, large_size: usize) -> Self {
+        let mut heap = Self::init_payload(size);
+        heap.committed_memory
+            .resize_with(size + large_size, Node::default);
+        heap.large = Some(LargeObjectSpace::init(threshold, size, large_size));
+        heap
+    }
+
+    /// where the rest of the heap ends, and the large object space starts
+    #[inline(always)]
+    pub fn end(&self) -> usize {
+        self.large
+            .as_ref()
+            .map_or(self.committed_memory.len(), |large| large.start)
+    }
 }
 
-impl MemoryManager for MarkCompactHeap {
+impl MarkCompactHeap {
+    pub fn init(size: usize) -> Self {
+        Self::init_payload(size)
+    }
+}
+
+impl<P: Payload, C: Children> MemoryManager<P> for MarkCompactHeap<P, C> {
     // allocates a new node
     // we can just add a new node and return its id
-    fn alloc(&mut self, node: Node, stack: &mut Stack) -> Result<NodePointer> {
+    fn alloc(&mut self, node: Node<P>, stack: &mut Stack) -> Result<NodePointer> {
+        self.bump(node, 0, stack)
+    }
+
+    // the node goes in the next `1 + size` slots, unless it's big enough for
+    // the large object space
+    fn alloc_sized(
+        &mut self,
+        node: Node<P>,
+        size: usize,
+        stack: &mut Stack,
+    ) -> Result<NodePointer> {
+        match &self.large {
+            Some(large) if 1 + size > large.threshold => self.alloc_large(node, 1 + size, stack),
+            _ if size > 0 && self.compaction == Compaction::TwoFinger => {
+                Err("two-finger compaction can't move a node that has a payload".into())
+            }
+            _ => self.bump(node, size, stack),
+        }
+    }
+
+    // mark-compact algorithm
+    fn collect(&mut self, stack: &mut Stack) -> Result<()> {
+        // log::debug!("exceeded heap size! now calling collect function for mark_compact");
+        let instant = Instant::now();
+
+        if self.marking {
+            // we were in the middle of an incremental mark. The barrier has
+            // kept everything it marked (and everything allocated black)
+            // right, so all that's left is to finish it off
+            self.shade_roots(stack);
+            self.mark_slice(usize::MAX);
+        } else {
+            match self.mark_order {
+                MarkOrder::Parallel { threads } => {
+                    // the threads keep their own mark bits, which get copied
+                    // over afterwards. The large object space is at the end,
+                    // so that's the whole thing
+                    let end = if self.large.is_some() {
+                        self.committed_memory.len()
+                    } else {
+                        self.free
+                    };
+                    let marks = parallel::mark(&self.committed_memory[..end], stack, threads);
+                    for (idx, mark) in marks.into_iter().enumerate() {
+                        if mark.into_inner() {
+                            self.mark(idx.into());
+                        }
+                    }
+                }
+                MarkOrder::Bounded { capacity } => self.mark_bounded(stack, capacity),
+                MarkOrder::PointerReversal => {
+                    for root in &stack.roots {
+                        for child in &root.children {
+                            self.mark_reversing(*child);
+                        }
+                    }
+                }
+                MarkOrder::Bfs | MarkOrder::Dfs => {
+                    // first create a worklist, which is a queue if we're doing
+                    // breadth-first traversal, or a stack if we're going
+                    // depth-first
+                    let mut worklist: VecDeque<NodePointer> = VecDeque::new();
+
+                    // populate the worklist with children reachable from the
+                    // roots
+                    for root in &stack.roots {
+                        for child in &root.children {
+                            worklist.push_back(*child);
+                        }
+                    }
+
+                    // mark everything
+                    self.mark_from(worklist, 0);
+                }
+            }
+        }
+        self.mark_phases.record(instant.elapsed());
+
+        // then slide everything
+        self.compact_and_sweep(stack);
+        self.marking = false;
+
+        self.pauses.record(instant.elapsed());
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn node_pointer_from_usize(&self, idx: usize) -> NodePointer {
+        NodePointer::from(idx)
+    }
+
+    #[inline(always)]
+    fn free(&self) -> usize {
+        self.free + self.large.as_ref().map_or(0, |large| large.used())
+    }
+
+    fn heap_size(&self) -> usize {
+        self.end()
+    }
+}
+
+impl<P: Payload, C: Children> Barriers for MarkCompactHeap<P, C> {
+    /// Dijkstra's insertion barrier. A black node could be getting a white
+    /// child written into it, which would never be scanned, so we shade the
+    /// child. The roots don't go through the barrier, so they get scanned
+    /// again once the gray nodes run out
+    #[inline(always)]
+    fn write_barrier(
+        &mut self,
+        _parent: NodePointer,
+        _old: Option<NodePointer>,
+        new: Option<NodePointer>,
+    ) {
+        if self.marking {
+            if let Some(new) = new {
+                self.shade(new);
+            }
+        }
+    }
+}
+
+impl<P: Payload, C: Children> Nodes<P> for MarkCompactHeap<P, C> {
+    type Children = C;
+
+    #[inline(always)]
+    fn get(&self, node_pointer: NodePointer) -> Option<&Node<P, C>> {
+        self.committed_memory.get(usize::from(node_pointer))
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self, node_pointer: NodePointer) -> Option<&mut Node<P, C>> {
+        self.committed_memory.get_mut(usize::from(node_pointer))
+    }
+}
+
+impl<P: Payload, C: Children> MarkCompactHeap<P, C> {
+    /// does one pause worth of incremental collection. The first slice shades
+    /// the roots, and the slice that runs out of gray nodes finishes the
+    /// collection
+    fn increment(&mut self, stack: &mut Stack, quantum: usize) {
+        let instant = Instant::now();
+        if !self.marking {
+            self.marking = true;
+            self.shade_roots(stack);
+        }
+        if self.mark_slice(quantum) {
+            // rescanning the roots might find more white nodes, which is fine,
+            // the rest of them get marked in this pause
+            self.shade_roots(stack);
+            self.mark_slice(usize::MAX);
+            self.compact_and_sweep(stack);
+            self.marking = false;
+        }
+        self.pauses.record(instant.elapsed());
+    }
+
+    /// bumps the free pointer past a node and `size` slots of payload
+    fn bump(&mut self, node: Node<P>, size: usize, stack: &mut Stack) -> Result<NodePointer> {
+        // do a slice of marking work before handing out memory
+        if let Some(incremental) = self.incremental {
+            if self.marking || self.free >= incremental.trigger {
+                self.increment(stack, incremental.quantum);
+            }
+        }
         // if our free pointer is over the committed memory length
-        if self.free >= self.committed_memory.len() {
+        if self.free + size >= self.end() {
             // we need to run gc
+            let finishing = self.marking;
             self.collect(stack)?;
+            // finishing off an incremental mark keeps everything that was
+            // allocated black alive, which might be what filled the heap in
+            // the first place, so try again from scratch
+            if finishing && self.free + size >= self.end() {
+                self.collect(stack)?;
+            }
         }
-        if self.free >= self.committed_memory.len() {
+        if self.free + size >= self.end() {
             return Err("gg collection didn't result in any amount of garbage collected".into());
         }
 
         // set the node id to where the top of the heap is
         let node_pointer = NodePointer::from(self.free);
-        // add it to the heap
-        self.committed_memory[usize::from(node_pointer)] = node;
+        // add it to the heap, and clear out whatever was where its payload
+        // goes
+        self.committed_memory[usize::from(node_pointer)] = node.with_children();
+        for idx in self.free + 1..=self.free + size {
+            self.committed_memory[idx] = Node::default();
+        }
+        self.sizes.set(self.free, size, self.committed_memory.len());
         // bump the free pointer
-        self.free += 1;
+        self.free += 1 + size;
+        self.blacken(node_pointer);
 
         Ok(node_pointer)
     }
 
-    // mark-compact algorithm
-    fn collect(&mut self, stack: &mut Stack) -> Result<()> {
-        // log::debug!("exceeded heap size! now calling collect function for mark_compact");
+    /// nodes allocated in the middle of marking are allocated black, so
+    /// whatever they already point to has to be shaded
+    fn blacken(&mut self, node_pointer: NodePointer) {
+        if self.marking {
+            self.mark(node_pointer);
+            for i in 0..self.get(node_pointer).unwrap().children.len() {
+                self.shade(self.get(node_pointer).unwrap().children[i]);
+            }
+        }
+    }
 
-        // this block contains the code to mark all reachable objects
-        {
-            // first create a worklist, which is going to be a queue, since
-            // we're doing breadth-first traversal
-            let mut worklist: VecDeque<NodePointer> = VecDeque::new();
+    /// first fit in the large object space, collecting if nothing fits
+    fn alloc_large(
+        &mut self,
+        node: Node<P>,
+        size: usize,
+        stack: &mut Stack,
+    ) -> Result<NodePointer> {
+        let mut node_pointer = self.large.as_mut().unwrap().alloc(size);
+        if node_pointer.is_none() {
+            // we need to run gc, twice if the first one was finishing off an
+            // incremental mark, the same as in `alloc`
+            let finishing = self.marking;
+            self.collect(stack)?;
+            node_pointer = self.large.as_mut().unwrap().alloc(size);
+            if finishing && node_pointer.is_none() {
+                self.collect(stack)?;
+                node_pointer = self.large.as_mut().unwrap().alloc(size);
+            }
+        }
+        let node_pointer =
+            node_pointer.ok_or("gg collection didn't result in any amount of garbage collected")?;
+        self.committed_memory[usize::from(node_pointer)] = node.with_children();
+        self.blacken(node_pointer);
+        Ok(node_pointer)
+    }
 
-            // populate the worklist with children reachable from the roots
+    /// compacts everything once it's been marked. Large objects don't move,
+    /// but the ones that are live can point at nodes that do, so they get
+    /// their references updated along with the rest, before the large object
+    /// space gets swept
+    fn compact_and_sweep(&mut self, stack: &mut Stack) {
+        let live = self
+            .large
+            .as_ref()
+            .map_or_else(Vec::new, |large| large.marked());
+        self.compact(stack, 0, &live);
+        if let Some(large) = &mut self.large {
+            large.sweep();
+        }
+    }
+
+    fn shade_roots(&mut self, stack: &Stack) {
+        for root in &stack.roots {
+            for child in &root.children {
+                self.shade(*child);
+            }
+        }
+    }
+
+    /// turns a white node gray
+    #[inline]
+    fn shade(&mut self, node_pointer: NodePointer) {
+        if !self.is_marked(node_pointer) {
+            self.mark(node_pointer);
+            self.gray.push_back(node_pointer);
+        }
+    }
+
+    /// scans up to `quantum` gray nodes, turning them black. Returns true once
+    /// there's nothing gray left
+    fn mark_slice(&mut self, quantum: usize) -> bool {
+        for _ in 0..quantum {
+            match self.gray.pop_front() {
+                Some(node_pointer) => {
+                    for i in 0..self.get(node_pointer).unwrap().children.len() {
+                        self.shade(self.get(node_pointer).unwrap().children[i]);
+                    }
+                }
+                None => return true,
+            }
+        }
+        self.gray.is_empty()
+    }
+
+    /// marks every node reachable from the worklist, without tracing through
+    /// nodes below `start`, or through large objects, which are as old as it
+    /// gets. A `start` of 0 marks the whole heap
+    pub(crate) fn mark_from(&mut self, mut worklist: VecDeque<NodePointer>, start: usize) {
+        let end = if start > 0 { self.end() } else { usize::MAX };
+        // then we just keep on taking from the worklist until it's empty
+        while let Some(node) = match self.mark_order {
+            // any partial mark is done on one thread, breadth-first
+            MarkOrder::Bfs | MarkOrder::Parallel { .. } => worklist.pop_front(),
+            // pointer reversal and the bounded mark stack only ever mark the
+            // whole heap, so a partial mark just goes depth-first instead
+            MarkOrder::Dfs | MarkOrder::PointerReversal | MarkOrder::Bounded { .. } => {
+                worklist.pop_back()
+            }
+        } {
+            // if the node isn't marked (already), and we care about it
+            if (start..end).contains(&usize::from(node)) && !self.is_marked(node) {
+                // we mark it because it means it's accessible
+                self.mark(node);
+                // then add the rest of its children to the back of the queue.
+                // On a stack they go on in reverse, so the first child gets
+                // marked first
+                match self.mark_order {
+                    MarkOrder::Bfs | MarkOrder::Parallel { .. } => {
+                        worklist.extend(self.get(node).unwrap().children.iter())
+                    }
+                    MarkOrder::Dfs | MarkOrder::PointerReversal | MarkOrder::Bounded { .. } => {
+                        worklist.extend(self.get(node).unwrap().children.iter().rev())
+                    }
+                }
+            }
+        }
+    }
+
+    /// depth-first marking with a mark stack that holds at most `capacity`
+    /// nodes. A node only gets marked once it's made it onto the stack, so
+    /// when the stack overflows, the node that didn't fit is left unmarked
+    /// with a marked parent. Once the stack empties out, we rescan the heap
+    /// for marked nodes like that and push their unmarked children, and keep
+    /// going until nothing overflows
+    fn mark_bounded(&mut self, stack: &Stack, capacity: usize) {
+        let mut mark_stack: Vec<NodePointer> = Vec::with_capacity(capacity);
+        let mut overflowed = false;
+        // the roots can overflow too, so they get rescanned along with the
+        // heap
+        for root in &stack.roots {
+            for child in &root.children {
+                overflowed |= !self.push_bounded(&mut mark_stack, capacity, *child);
+            }
+        }
+        loop {
+            while let Some(node_pointer) = mark_stack.pop() {
+                for i in 0..self.get(node_pointer).unwrap().children.len() {
+                    let child = self.get(node_pointer).unwrap().children[i];
+                    overflowed |= !self.push_bounded(&mut mark_stack, capacity, child);
+                }
+            }
+            if !overflowed {
+                break;
+            }
+            overflowed = false;
             for root in &stack.roots {
                 for child in &root.children {
-                    worklist.push_back(*child);
+                    overflowed |= !self.push_bounded(&mut mark_stack, capacity, *child);
                 }
             }
-
-            // then we just keep on taking from the worklist until it's empty
-            while let Some(node) = worklist.pop_front() {
-                // if the node isn't marked (already)
-                if !self.is_marked(node) {
-                    // we mark it because it means it's accessible
-                    self.mark(node);
-                    // then add the rest of its children to the back of the queue
-                    for child_node_pointer in &self.get(node).unwrap().children {
-                        worklist.push_back(*child_node_pointer);
+            let large = self
+                .large
+                .as_ref()
+                .map_or_else(Vec::new, |large| large.marked());
+            for idx in (0..self.free).chain(large.into_iter().map(usize::from)) {
+                if self.is_marked(idx.into()) {
+                    for i in 0..self.committed_memory[idx].children.len() {
+                        let child = self.committed_memory[idx].children[i];
+                        overflowed |= !self.push_bounded(&mut mark_stack, capacity, child);
                     }
                 }
             }
         }
+    }
+
+    /// marks a node and pushes it onto the mark stack, if it isn't marked
+    /// already. Returns false if it needed pushing but there wasn't any room
+    #[inline]
+    fn push_bounded(
+        &mut self,
+        mark_stack: &mut Vec<NodePointer>,
+        capacity: usize,
+        node_pointer: NodePointer,
+    ) -> bool {
+        if self.is_marked(node_pointer) {
+            return true;
+        }
+        if mark_stack.len() >= capacity {
+            self.mark_stack_overflows += 1;
+            return false;
+        }
+        self.mark(node_pointer);
+        mark_stack.push(node_pointer);
+        true
+    }
+
+    /// Deutsch-Schorr-Waite marking, which marks everything reachable from
+    /// `node_pointer` without a worklist. Instead of pushing the node we came
+    /// from, we store it in the child slot that we're descending through, so
+    /// the path back up to the start lives in the reversed edges. On the way
+    /// back up, each slot gets its original child back.
+    ///
+    /// Every node on the path also needs to remember which child we descended
+    /// through. Nothing's in the forwarding address until compaction, so we
+    /// borrow it for that
+    fn mark_reversing(&mut self, node_pointer: NodePointer) {
+        if self.is_marked(node_pointer) {
+            return;
+        }
+        self.mark(node_pointer);
+        self.set_forwarding_address(node_pointer, 0.into());
+
+        // the node above `current` on the path, or none if we're at the start
+        let mut previous: Option<NodePointer> = None;
+        let mut current = node_pointer;
+        loop {
+            let i = usize::from(self.get(current).unwrap().forwarding_address.unwrap());
+            if i < self.get(current).unwrap().children.len() {
+                let child = self.get(current).unwrap().children[i];
+                if self.is_marked(child) {
+                    // nothing to do here, move on to the next child
+                    self.set_forwarding_address(current, (i + 1).into());
+                } else {
+                    // descend, pointing the slot back up the path. The start
+                    // has nothing above it, so its slot just points at itself
+                    self.mark(child);
+                    self.set_forwarding_address(child, 0.into());
+                    self.get_mut(current).unwrap().children[i] = previous.unwrap_or(current);
+                    previous = Some(current);
+                    current = child;
+                }
+            } else {
+                // every child has been visited, so go back up a level
+                self.get_mut(current).unwrap().forwarding_address = None;
+                let parent = match previous {
+                    Some(parent) => parent,
+                    None => break,
+                };
+                let i = usize::from(self.get(parent).unwrap().forwarding_address.unwrap());
+                // the slot we came down through holds whatever's above the
+                // parent, so swap the child back in
+                let above =
+                    std::mem::replace(&mut self.get_mut(parent).unwrap().children[i], current);
+                previous = if parent == node_pointer {
+                    None
+                } else {
+                    Some(above)
+                };
+                self.set_forwarding_address(parent, (i + 1).into());
+                current = parent;
+            }
+        }
+    }
+
+    /// compacts every marked node between `start` and `free` down to `start`.
+    /// Nodes below `start` are left where they are, so any of them that might
+    /// point above `start` have to be passed in through `remembered` for their
+    /// references to get updated
+    pub(crate) fn compact(&mut self, stack: &mut Stack, start: usize, remembered: &[NodePointer]) {
+        match self.compaction {
+            Compaction::Lisp2 => self.slide(stack, start, remembered),
+            Compaction::TwoFinger => self.two_finger(stack, start, remembered),
+            Compaction::Threaded => self.jonkers(stack, start, remembered),
+            Compaction::Compressor => self.compress(stack, start, remembered),
+        }
+    }
+
+    /// LISP-2 style sliding
+    fn slide(&mut self, stack: &mut Stack, start: usize, remembered: &[NodePointer]) {
         // now all our reachable objects should be marked, everything that isn't
         // is considered garbo we only care about the marked objects from now on
 
-        // log::trace!(
-        //     "marked_node_pointers is this long: {}",
-        //     marked_node_pointers.len()
-        // );
-        // let iterator = marked_node_pointers.iter().enumerate().filter(|(_, v)| **v);
-        // log::trace!(
-        //     "we have this many marked nodes: {}",
-        //     iterator.clone().count()
-        // );
-
         // the next three blocks contain the compact code
-        // free starts at 0, the beginning of the point which we wish to compact to
-        let mut free = 0;
+        // free starts at `start`, the beginning of the point which we wish to compact to
+        let mut free = start;
 
         // 1. the first step is to calculate new locations of all objects
         {
             // we iterate over all objects in the heap
-            for idx in 0..self.free {
+            for idx in start..self.free {
                 // if it is marked,
                 if self.is_marked(idx.into()) {
                     // set its forwarding address equal to free
                     self.set_forwarding_address(idx.into(), free.into());
                     // then bump free by the object's size
-                    free += 1;
+                    free += self.sizes.size(idx);
                 }
             }
         }
@@ -122,24 +733,19 @@ impl MemoryManager for MarkCompactHeap {
         {
             // for every marked parent, set the parent's references to the
             // child's forwarding address
-            for idx in 0..self.free {
+            for idx in start..self.free {
                 if self.is_marked(idx.into()) {
-                    let node = NodePointer::from(idx);
-
-                    //  for every child that the marked parent node holds
-                    for i in 0..self.get_mut(node).unwrap().children.len() {
-                        let child_node_pointer = self.get(node).unwrap().children[i];
-
-                        // get the child node's forwarding address
-                        let forwarding_address = self
-                            .get(child_node_pointer)
-                            .unwrap()
-                            .forwarding_address
-                            .unwrap();
-
-                        // then update the parent's reference to the child's forwarding address
-                        self.get_mut(node).unwrap().children[i] = forwarding_address;
-                    }
+                    self.update_references(idx.into(), start);
+                }
+            }
+            // the same goes for anything below `start` that points up into it
+            for node in remembered {
+                self.update_references(*node, start);
+            }
+            // don't forget that the roots on the stack point into the heap too
+            for root in &mut stack.roots {
+                for child in &mut root.children {
+                    *child = self.forwarded(*child, start);
                 }
             }
         }
@@ -147,65 +753,317 @@ impl MemoryManager for MarkCompactHeap {
         // 3. actually move the objects
         {
             //  for every marked node
-            for idx in 0..self.free {
+            for idx in start..self.free {
                 if self.is_marked(idx.into()) {
                     let node = NodePointer::from(idx);
 
                     // unset the forwarding address of the object that's about
-                    // to be moved, consequently unmarking it for the next
-                    // collection cycle
+                    // to be moved, and unmark it for the next collection cycle
                     let forwarding_address = self.get(node).unwrap().forwarding_address.unwrap();
                     self.get_mut(node).unwrap().forwarding_address = None;
+                    self.marks.unmark(idx);
 
                     // swap node's current position with node's forwarding
                     // position...  but only if they're not already in the right
                     // place! This is an advantage of mark-compact over stop
                     // copy
                     if usize::from(forwarding_address) != usize::from(node) {
-                        self.committed_memory
-                            .swap(usize::from(node), usize::from(forwarding_address));
+                        self.move_node(idx, usize::from(forwarding_address));
                     }
                 }
             }
         }
         // set our new free pointer to the compacted point
         self.free = free;
-        Ok(())
     }
 
-    #[inline(always)]
-    fn node_pointer_from_usize(&self, idx: usize) -> NodePointer {
-        NodePointer::from(idx)
+    /// moves a node down from `from` to `to`, along with its payload. A slot
+    /// at a time, lowest first, so that it still works when the two overlap
+    #[inline]
+    fn move_node(&mut self, from: usize, to: usize) {
+        let size = self.sizes.size(from);
+        for i in 0..size {
+            self.committed_memory.swap(from + i, to + i);
+        }
+        self.sizes.set(to, size - 1, self.committed_memory.len());
+        self.copied += size;
     }
 
-    #[inline(always)]
-    fn get(&self, node_pointer: NodePointer) -> Option<&Node> {
-        self.committed_memory.get(usize::from(node_pointer))
+    /// Edwards' two-finger compaction. One finger starts at the bottom and
+    /// looks for holes, the other starts at the top and looks for marked
+    /// nodes, and every marked node the top finger finds gets moved into the
+    /// hole the bottom finger found, leaving its forwarding address behind.
+    /// Once the fingers meet, everything below them is live, and anything
+    /// pointing above them points at a forwarding address
+    fn two_finger(&mut self, stack: &mut Stack, start: usize, remembered: &[NodePointer]) {
+        // 1. move the nodes
+        let end = self.free;
+        let mut free = start;
+        let mut top = self.free;
+        loop {
+            // find the lowest hole
+            while free < top && self.is_marked(free.into()) {
+                free += 1;
+            }
+            // and the highest marked node above it
+            while top > free && !self.is_marked((top - 1).into()) {
+                top -= 1;
+            }
+            if top <= free {
+                break;
+            }
+            let from = top - 1;
+            self.committed_memory.swap(from, free);
+            self.copied += 1;
+            self.marks.unmark(from);
+            self.set_forwarding_address(from.into(), free.into());
+            free += 1;
+            top -= 1;
+        }
+
+        // 2. update references to anything that moved
+        let forwarded = |heap: &Self, node_pointer: NodePointer| {
+            if (free..end).contains(&usize::from(node_pointer)) {
+                heap.get(node_pointer).unwrap().forwarding_address.unwrap()
+            } else {
+                node_pointer
+            }
+        };
+        for idx in start..free {
+            self.marks.unmark(idx);
+            for i in 0..self.committed_memory[idx].children.len() {
+                self.committed_memory[idx].children[i] =
+                    forwarded(self, self.committed_memory[idx].children[i]);
+            }
+        }
+        for node in remembered {
+            for i in 0..self.get(*node).unwrap().children.len() {
+                self.get_mut(*node).unwrap().children[i] =
+                    forwarded(self, self.get(*node).unwrap().children[i]);
+            }
+        }
+        for root in &mut stack.roots {
+            for child in &mut root.children {
+                *child = forwarded(self, *child);
+            }
+        }
+        // set our new free pointer to where the fingers met
+        self.free = free;
     }
 
-    #[inline(always)]
-    fn get_mut(&mut self, node_pointer: NodePointer) -> Option<&mut Node> {
-        self.committed_memory.get_mut(usize::from(node_pointer))
+    /// Jonkers' threaded compaction. Instead of storing a forwarding address
+    /// in every node, every reference to a node gets chained together into a
+    /// "thread" that starts at the node, so that once we know where the node
+    /// is going, we can walk the thread and point every reference there.
+    ///
+    /// The first pass threads the roots, then goes up the heap. By the time it
+    /// gets to a node, every reference to it from below has been threaded, so
+    /// it can update them all, before threading the node's own references.
+    /// Whatever's left are references from above, which have been threaded by
+    /// the end of the pass, so the second pass updates those and slides every
+    /// node down, in the same order as LISP-2
+    fn jonkers(&mut self, stack: &mut Stack, start: usize, remembered: &[NodePointer]) {
+        // 1. update forward references
+        for idx in 0..stack.roots.len() {
+            for i in 0..stack.roots[idx].children.len() {
+                self.thread(stack, start, thread_slot(true, idx, i));
+            }
+        }
+        for node in remembered {
+            for i in 0..self.get(*node).unwrap().children.len() {
+                self.thread(stack, start, thread_slot(false, usize::from(*node), i));
+            }
+        }
+        let mut free = start;
+        for idx in start..self.free {
+            if self.is_marked(idx.into()) {
+                self.unthread(stack, idx, free.into());
+                for i in 0..self.committed_memory[idx].children.len() {
+                    self.thread(stack, start, thread_slot(false, idx, i));
+                }
+                free += self.sizes.size(idx);
+            }
+        }
+
+        // 2. update backward references, and move the nodes
+        let mut free = start;
+        for idx in start..self.free {
+            if self.is_marked(idx.into()) {
+                self.unthread(stack, idx, free.into());
+                self.marks.unmark(idx);
+                let size = self.sizes.size(idx);
+                if idx != free {
+                    self.move_node(idx, free);
+                }
+                free += size;
+            }
+        }
+        self.free = free;
     }
 
+    /// the Compressor's compaction. A node's forwarding address is `start`
+    /// plus the number of marked nodes between `start` and it, which we can
+    /// count quickly with an offset table holding the number of marked nodes
+    /// before every word of the mark bitmap, plus a popcount of the word the
+    /// node's in. The table takes one pass over the bitmap instead of the
+    /// heap, so the heap only gets walked once, updating references and
+    /// sliding nodes down as we go
+    fn compress(&mut self, stack: &mut Stack, start: usize, remembered: &[NodePointer]) {
+        // 0. the offsets count slots rather than nodes, so a node with a
+        // payload gets the rest of its slots marked too
+        if !self.sizes.is_empty() {
+            let mut idx = start;
+            while idx < self.free {
+                let size = self.sizes.size(idx);
+                if self.marks.is_marked(idx) {
+                    for payload in idx + 1..idx + size {
+                        self.marks.mark(payload);
+                    }
+                }
+                idx += size;
+            }
+        }
+
+        // 1. build the offset table. Anything marked below `start` isn't
+        // moving, so it gets masked out of the first word
+        let first_word = start / 64;
+        let mut offsets = Vec::with_capacity(self.free.div_ceil(64).saturating_sub(first_word));
+        let mut marked = 0;
+        for word in first_word..self.free.div_ceil(64) {
+            offsets.push(marked);
+            marked += self.mark_word(word, start).count_ones() as usize;
+        }
+        let forwarded = |heap: &Self, node_pointer: NodePointer| {
+            let idx = usize::from(node_pointer);
+            if idx < start || idx >= heap.free {
+                return node_pointer;
+            }
+            let below = heap.mark_word(idx / 64, start) & ((1 << (idx % 64)) - 1);
+            NodePointer::from(start + offsets[idx / 64 - first_word] + below.count_ones() as usize)
+        };
+
+        // 2. update references and move the nodes
+        for node in remembered {
+            for i in 0..self.get(*node).unwrap().children.len() {
+                self.get_mut(*node).unwrap().children[i] =
+                    forwarded(self, self.get(*node).unwrap().children[i]);
+            }
+        }
+        for root in &mut stack.roots {
+            for child in &mut root.children {
+                *child = forwarded(self, *child);
+            }
+        }
+        let mut free = start;
+        for idx in start..self.free {
+            if self.is_marked(idx.into()) {
+                for i in 0..self.committed_memory[idx].children.len() {
+                    self.committed_memory[idx].children[i] =
+                        forwarded(self, self.committed_memory[idx].children[i]);
+                }
+                // a slot at a time, payload included
+                if idx != free {
+                    self.committed_memory.swap(idx, free);
+                    if !self.sizes.is_empty() {
+                        self.sizes.payloads[free] = self.sizes.payloads[idx];
+                    }
+                    self.copied += 1;
+                }
+                free += 1;
+            }
+        }
+
+        // the mark bits were needed right up until the end, so they only get
+        // cleared now, a word at a time
+        for word in first_word..self.free.div_ceil(64) {
+            self.marks.words[word] ^= self.mark_word(word, start);
+        }
+        self.free = free;
+    }
+
+    /// a word of the mark bitmap, without anything below `start`
     #[inline(always)]
-    fn free(&self) -> usize {
-        self.free
+    fn mark_word(&self, word: usize, start: usize) -> u64 {
+        let word_bits = self.marks.words[word];
+        if word == start / 64 {
+            word_bits & (u64::MAX << (start % 64))
+        } else {
+            word_bits
+        }
     }
 
-    fn heap_size(&self) -> usize {
-        self.committed_memory.len()
+    /// adds a child slot to the thread of the node it points at, if that node
+    /// is going to move
+    #[inline]
+    fn thread(&mut self, stack: &mut Stack, start: usize, slot: usize) {
+        let idx = usize::from(*threaded(&mut self.committed_memory, stack, slot));
+        if idx < start || idx >= self.free {
+            return;
+        }
+        let head = self.committed_memory[idx]
+            .parent
+            .unwrap_or_else(|| NO_PARENT.into());
+        *threaded(&mut self.committed_memory, stack, slot) = head;
+        self.committed_memory[idx].parent = Some(slot.into());
+    }
+
+    /// points every slot in a node's thread at `node_pointer`, and gives the
+    /// node its parent pointer back
+    #[inline]
+    fn unthread(&mut self, stack: &mut Stack, idx: usize, node_pointer: NodePointer) {
+        let mut head = self.committed_memory[idx].parent;
+        while let Some(slot) = head.map(usize::from).filter(|slot| slot & THREAD != 0) {
+            let next = std::mem::replace(
+                threaded(&mut self.committed_memory, stack, slot),
+                node_pointer,
+            );
+            head = if usize::from(next) == NO_PARENT {
+                None
+            } else {
+                Some(next)
+            };
+        }
+        self.committed_memory[idx].parent = head;
+    }
+
+    /// points every child of `node` at where that child is going to end up
+    #[inline]
+    fn update_references(&mut self, node: NodePointer, start: usize) {
+        //  for every child that the marked parent node holds
+        for i in 0..self.get(node).unwrap().children.len() {
+            let child_node_pointer = self.get(node).unwrap().children[i];
+            // then update the parent's reference to the child's forwarding address
+            self.get_mut(node).unwrap().children[i] = self.forwarded(child_node_pointer, start);
+        }
+    }
+
+    /// where a child is going to end up after compaction
+    #[inline]
+    fn forwarded(&self, node_pointer: NodePointer, start: usize) -> NodePointer {
+        // nodes below start don't move, and neither do large objects
+        if usize::from(node_pointer) < start || usize::from(node_pointer) >= self.free {
+            node_pointer
+        } else {
+            // get the child node's forwarding address
+            self.get(node_pointer).unwrap().forwarding_address.unwrap()
+        }
     }
-}
 
-impl MarkCompactHeap {
     #[inline]
     fn is_marked(&self, node_pointer: NodePointer) -> bool {
-        self.get(node_pointer).unwrap().forwarding_address.is_some()
+        match &self.large {
+            Some(large) if large.contains(node_pointer) => large.is_marked(node_pointer),
+            _ => self.marks.is_marked(usize::from(node_pointer)),
+        }
     }
     #[inline]
     fn mark(&mut self, node_pointer: NodePointer) {
-        self.get_mut(node_pointer).unwrap().forwarding_address = Some(NodePointer::default());
+        match &mut self.large {
+            Some(large) if large.contains(node_pointer) => {
+                large.mark(node_pointer);
+            }
+            _ => self.marks.mark(usize::from(node_pointer)),
+        }
     }
     #[inline]
     fn set_forwarding_address(
@@ -0,0 +1,354 @@
+use std::collections::VecDeque;
+
+use crate::large::LargeObjectSpace;
+use crate::shared::{Barriers, MarkBitmap, MemoryManager, Node, NodePointer, Nodes, Stack};
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Immix uses 128 byte lines, which is two nodes
+pub const LINE_SIZE: usize = 2;
+/// and 32KB blocks, which is 512 nodes
+pub const BLOCK_SIZE: usize = 512;
+/// a block needs at least this many holes in it to be worth evacuating
+pub const FRAGMENTED_HOLES: usize = 2;
+
+/// This mark-region algorithm is based on Immix. The heap is split up into
+/// blocks, which are split up into lines, and `alloc` bump allocates into runs
+/// of free lines (holes), skipping over any line that has something live in
+/// it. Marking marks lines as well as nodes, and a line is free again as soon
+/// as nothing in it gets marked, so there's no sweep and nothing gets moved
+/// just to free up memory.
+///
+/// Nodes only move to fight fragmentation. Blocks that the last collection
+/// left with lots of holes get picked as evacuation candidates, and while
+/// marking, any live node in one of them is copied out into a hole in another
+/// block, as long as there's room for it. Otherwise it just gets marked where
+/// it is
+#[derive(Clone)]
+pub struct MarkRegionHeap {
+    pub committed_memory: Vec<Node>,
+    // in nodes
+    pub line_size: usize,
+    // in nodes, and always a multiple of the line size
+    pub block_size: usize,
+    // whether each line is in use, either because something in it survived
+    // the last collection, or because something's been allocated into it
+    // since
+    pub lines: Vec<bool>,
+    pub marks: MarkBitmap,
+    // the hole that we're bump allocating into
+    pub cursor: usize,
+    pub limit: usize,
+    // how many holes each block had after the last collection
+    pub holes: Vec<usize>,
+    // how many nodes are live or have been allocated since the last collection
+    pub in_use: usize,
+    // how many nodes have been evacuated, over every collection
+    pub evacuated: usize,
+    // nodes bigger than its threshold go in here instead, which comes after
+    // the last block
+    pub large: Option<LargeObjectSpace>,
+}
+
+impl MarkRegionHeap {
+    pub fn init(size: usize) -> Self {
+        Self::init_with_lines(size, LINE_SIZE, BLOCK_SIZE)
+    }
+
+    pub fn init_with_lines(size: usize, line_size: usize, block_size: usize) -> Self {
+        let mut committed_memory: Vec<Node> = Vec::new();
+        for _ in 0..size {
+            committed_memory.push(Node::default());
+        }
+        let line_size = line_size.max(1);
+        // round the block size up to a whole number of lines
+        let block_size = block_size.max(1).div_ceil(line_size) * line_size;
+        Self {
+            committed_memory,
+            line_size,
+            block_size,
+            lines: vec![false; size.div_ceil(line_size)],
+            marks: MarkBitmap::init(size),
+            cursor: 0,
+            limit: 0,
+            holes: vec![0; size.div_ceil(block_size)],
+            in_use: 0,
+            evacuated: 0,
+            large: None,
+        }
+    }
+
+    /// the same as `init`, along with a large object space of `large_size`
+    /// slots, for nodes taking up more than `threshold` slots
+    pub fn init_large(size: usize, threshold: usize, large_size: usize) -> Self {
+        let mut heap = Self::init(size);
+        heap.committed_memory
+            .resize_with(size + large_size, Node::default);
+        heap.large = Some(LargeObjectSpace::init(threshold, size, large_size));
+        heap
+    }
+
+    /// where the blocks end, and the large object space starts
+    #[inline(always)]
+    pub fn end(&self) -> usize {
+        self.large
+            .as_ref()
+            .map_or(self.committed_memory.len(), |large| large.start)
+    }
+
+    #[inline(always)]
+    fn line(&self, idx: usize) -> usize {
+        idx / self.line_size
+    }
+
+    #[inline(always)]
+    fn block(&self, idx: usize) -> usize {
+        idx / self.block_size
+    }
+
+    /// finds the next run of lines after `limit` that `free` says are free,
+    /// and returns where it starts and ends
+    fn next_hole(&self, limit: usize, free: impl Fn(usize) -> bool) -> Option<(usize, usize)> {
+        let mut line = limit.div_ceil(self.line_size);
+        while line < self.lines.len() && !free(line) {
+            line += 1;
+        }
+        if line >= self.lines.len() {
+            return None;
+        }
+        let start = line;
+        while line < self.lines.len() && free(line) {
+            line += 1;
+        }
+        Some((
+            start * self.line_size,
+            (line * self.line_size).min(self.end()),
+        ))
+    }
+
+    /// moves the bump allocator on to the next hole. Returns false if there
+    /// aren't any left
+    fn next_bump_hole(&mut self) -> bool {
+        match self.next_hole(self.limit, |line| !self.lines[line]) {
+            Some((cursor, limit)) => {
+                self.cursor = cursor;
+                self.limit = limit;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// picks the blocks with the most holes to evacuate, as long as everything
+    /// in them is going to fit into the holes in every other block
+    fn evacuation_candidates(&self) -> Vec<bool> {
+        let blocks = self.holes.len();
+        let lines_per_block = self.block_size / self.line_size;
+        let free_lines = |block: usize| {
+            let lines =
+                block * lines_per_block..((block + 1) * lines_per_block).min(self.lines.len());
+            lines.filter(|line| !self.lines[*line]).count()
+        };
+        let mut evacuating = vec![false; blocks];
+        let mut fragmented: Vec<usize> = (0..blocks)
+            .filter(|block| self.holes[*block] >= FRAGMENTED_HOLES)
+            .collect();
+        fragmented.sort_by_key(|block| std::cmp::Reverse(self.holes[*block]));
+
+        // room in the blocks that aren't being evacuated, and how much of it
+        // has already been promised to candidates
+        let mut room: usize = (0..blocks).map(free_lines).sum::<usize>() * self.line_size;
+        let mut reserved = 0;
+        for block in fragmented {
+            let free = free_lines(block) * self.line_size;
+            let used = self
+                .block_size
+                .min(self.end() - block * self.block_size)
+                .saturating_sub(free);
+            if reserved + used <= room - free {
+                room -= free;
+                reserved += used;
+                evacuating[block] = true;
+            }
+        }
+        evacuating
+    }
+
+    /// first fit in the large object space, collecting if nothing fits
+    fn alloc_large(&mut self, node: Node, size: usize, stack: &mut Stack) -> Result<NodePointer> {
+        let mut node_pointer = self.large.as_mut().unwrap().alloc(size);
+        if node_pointer.is_none() {
+            self.collect(stack)?;
+            node_pointer = self.large.as_mut().unwrap().alloc(size);
+        }
+        let node_pointer =
+            node_pointer.ok_or("gg collection didn't result in any amount of garbage collected")?;
+        self.committed_memory[usize::from(node_pointer)] = node;
+        Ok(node_pointer)
+    }
+}
+
+impl MemoryManager for MarkRegionHeap {
+    // bump allocates into the current hole, moving on to the next one if it's
+    // full
+    fn alloc(&mut self, node: Node, stack: &mut Stack) -> Result<NodePointer> {
+        if self.cursor >= self.limit && !self.next_bump_hole() {
+            // we've run out of holes, so we need to run gc
+            self.collect(stack)?;
+            if !self.next_bump_hole() {
+                return Err(
+                    "gg collection didn't result in any amount of garbage collected".into(),
+                );
+            }
+        }
+
+        let node_pointer = NodePointer::from(self.cursor);
+        // add it to the heap
+        self.committed_memory[self.cursor] = node;
+        let line = self.line(self.cursor);
+        self.lines[line] = true;
+        // bump the cursor
+        self.cursor += 1;
+        self.in_use += 1;
+
+        Ok(node_pointer)
+    }
+
+    // nodes are all the same size, unless they're big enough for the large
+    // object space
+    fn alloc_sized(&mut self, node: Node, size: usize, stack: &mut Stack) -> Result<NodePointer> {
+        match &self.large {
+            Some(large) if 1 + size > large.threshold => self.alloc_large(node, 1 + size, stack),
+            _ if size > 0 => Err("this heap's nodes are all the same size".into()),
+            _ => self.alloc(node, stack),
+        }
+    }
+
+    // mark-region algorithm
+    fn collect(&mut self, stack: &mut Stack) -> Result<()> {
+        // only the holes that are free right now, and aren't in a block that's
+        // being evacuated, can be evacuated into
+        let evacuating = self.evacuation_candidates();
+        let targets: Vec<bool> = (0..self.lines.len())
+            .map(|line| !self.lines[line] && !evacuating[line * self.line_size / self.block_size])
+            .collect();
+        let (mut cursor, mut limit) = (0, 0);
+
+        // every line starts out free, and marking decides which ones aren't
+        self.lines.iter_mut().for_each(|line| *line = false);
+        self.in_use = 0;
+
+        // where a node ends up after marking, copying it out first if it's in
+        // a block that's being evacuated
+        let mut visit =
+            |heap: &mut Self, worklist: &mut VecDeque<NodePointer>, node_pointer: NodePointer| {
+                // large objects aren't in any block, so they never move, and
+                // get marked in the large object space instead
+                if let Some(large) = &mut heap.large {
+                    if large.contains(node_pointer) {
+                        if large.mark(node_pointer) {
+                            worklist.push_back(node_pointer);
+                        }
+                        return node_pointer;
+                    }
+                }
+                let idx = usize::from(node_pointer);
+                // it's already been evacuated
+                if let Some(forwarding_address) = heap.committed_memory[idx].forwarding_address {
+                    return forwarding_address;
+                }
+                if heap.marks.is_marked(idx) {
+                    return node_pointer;
+                }
+                let mut new_idx = idx;
+                if evacuating[heap.block(idx)] {
+                    if cursor >= limit {
+                        if let Some(hole) = heap.next_hole(limit, |line| targets[line]) {
+                            (cursor, limit) = hole;
+                        }
+                    }
+                    // if there's no room left, it just stays where it is
+                    if cursor < limit {
+                        new_idx = cursor;
+                        cursor += 1;
+                        heap.committed_memory.swap(idx, new_idx);
+                        heap.committed_memory[new_idx].forwarding_address = None;
+                        heap.committed_memory[idx].forwarding_address = Some(new_idx.into());
+                        heap.evacuated += 1;
+                    }
+                }
+                heap.marks.mark(new_idx);
+                let line = heap.line(new_idx);
+                heap.lines[line] = true;
+                heap.in_use += 1;
+                worklist.push_back(new_idx.into());
+                new_idx.into()
+            };
+
+        // mark breadth-first from the roots
+        let mut worklist: VecDeque<NodePointer> = VecDeque::new();
+        for root in &mut stack.roots {
+            for child in &mut root.children {
+                *child = visit(self, &mut worklist, *child);
+            }
+        }
+        while let Some(node_pointer) = worklist.pop_front() {
+            let idx = usize::from(node_pointer);
+            for i in 0..self.committed_memory[idx].children.len() {
+                let child = self.committed_memory[idx].children[i];
+                self.committed_memory[idx].children[i] = visit(self, &mut worklist, child);
+            }
+        }
+
+        // there's nothing to sweep, but we count up the holes in every block
+        // so the next collection knows which ones are fragmented
+        let lines_per_block = self.block_size / self.line_size;
+        for (block, holes) in self.holes.iter_mut().enumerate() {
+            let lines = &self.lines
+                [block * lines_per_block..((block + 1) * lines_per_block).min(self.lines.len())];
+            *holes = lines
+                .iter()
+                .enumerate()
+                .filter(|(i, used)| !**used && (*i == 0 || lines[i - 1]))
+                .count();
+        }
+        self.marks.clear();
+        if let Some(large) = &mut self.large {
+            large.sweep();
+        }
+        // and start allocating from the bottom of the heap again
+        self.cursor = 0;
+        self.limit = 0;
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn node_pointer_from_usize(&self, idx: usize) -> NodePointer {
+        NodePointer::from(idx)
+    }
+
+    #[inline(always)]
+    fn free(&self) -> usize {
+        // there's no single free pointer, so report the number of slots in use
+        self.in_use + self.large.as_ref().map_or(0, |large| large.used())
+    }
+
+    fn heap_size(&self) -> usize {
+        self.end()
+    }
+}
+
+impl Barriers for MarkRegionHeap {}
+
+impl Nodes for MarkRegionHeap {
+    type Children = Vec<NodePointer>;
+
+    #[inline(always)]
+    fn get(&self, node_pointer: NodePointer) -> Option<&Node> {
+        self.committed_memory.get(usize::from(node_pointer))
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self, node_pointer: NodePointer) -> Option<&mut Node> {
+        self.committed_memory.get_mut(usize::from(node_pointer))
+    }
+}
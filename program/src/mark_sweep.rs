@@ -0,0 +1,191 @@
+use std::collections::VecDeque;
+
+use crate::large::LargeObjectSpace;
+use crate::shared::{Barriers, MemoryManager, Node, NodePointer, Nodes, Stack};
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// This mark-sweep algorithm never moves objects. Dead slots are swept into a
+/// free list, and `alloc` hands those slots back out instead of bumping a
+/// pointer
+#[derive(Clone)]
+pub struct MarkSweepHeap {
+    pub committed_memory: Vec<Node>,
+    // one mark bit per slot, kept off to the side so we don't have to abuse
+    // the forwarding address like the mark-compact heap does
+    pub marked: Vec<bool>,
+    // slots that are free to be allocated into. It's used as a stack, so the
+    // lowest address is always at the end of the vec
+    pub free_list: Vec<NodePointer>,
+    // nodes bigger than its threshold go in here instead, which comes after
+    // the rest of the heap
+    pub large: Option<LargeObjectSpace>,
+}
+
+impl MarkSweepHeap {
+    pub fn init(size: usize) -> Self {
+        let mut committed_memory: Vec<Node> = Vec::new();
+        for _ in 0..size {
+            committed_memory.push(Node::default());
+        }
+        // every slot starts out free. We push them in reverse so that the
+        // first allocation lands on slot 0, just like the bump allocators
+        let free_list = (0..size).rev().map(NodePointer::from).collect();
+        Self {
+            committed_memory,
+            marked: vec![false; size],
+            free_list,
+            large: None,
+        }
+    }
+
+    /// the same as `init`, along with a large object space of `large_size`
+    /// slots, for nodes taking up more than `threshold` slots
+    pub fn init_large(size: usize, threshold: usize, large_size: usize) -> Self {
+        let mut heap = Self::init(size);
+        heap.committed_memory
+            .resize_with(size + large_size, Node::default);
+        heap.large = Some(LargeObjectSpace::init(threshold, size, large_size));
+        heap
+    }
+
+    /// where the rest of the heap ends, and the large object space starts
+    #[inline(always)]
+    pub fn end(&self) -> usize {
+        self.large
+            .as_ref()
+            .map_or(self.committed_memory.len(), |large| large.start)
+    }
+
+    /// first fit in the large object space, collecting if nothing fits
+    fn alloc_large(&mut self, node: Node, size: usize, stack: &mut Stack) -> Result<NodePointer> {
+        let mut node_pointer = self.large.as_mut().unwrap().alloc(size);
+        if node_pointer.is_none() {
+            self.collect(stack)?;
+            node_pointer = self.large.as_mut().unwrap().alloc(size);
+        }
+        let node_pointer =
+            node_pointer.ok_or("gg collection didn't result in any amount of garbage collected")?;
+        self.committed_memory[usize::from(node_pointer)] = node;
+        Ok(node_pointer)
+    }
+}
+
+impl MemoryManager for MarkSweepHeap {
+    // allocates a new node into the first slot on the free list
+    fn alloc(&mut self, node: Node, stack: &mut Stack) -> Result<NodePointer> {
+        // if there's nothing left on the free list
+        if self.free_list.is_empty() {
+            // we need to run gc
+            self.collect(stack)?;
+        }
+        // take a slot off of the free list
+        let node_pointer = match self.free_list.pop() {
+            Some(node_pointer) => node_pointer,
+            None => {
+                return Err("gg collection didn't result in any amount of garbage collected".into())
+            }
+        };
+        // add it to the heap
+        self.committed_memory[usize::from(node_pointer)] = node;
+
+        Ok(node_pointer)
+    }
+
+    // nodes are all the same size, unless they're big enough for the large
+    // object space
+    fn alloc_sized(&mut self, node: Node, size: usize, stack: &mut Stack) -> Result<NodePointer> {
+        match &self.large {
+            Some(large) if 1 + size > large.threshold => self.alloc_large(node, 1 + size, stack),
+            _ if size > 0 => Err("this heap's nodes are all the same size".into()),
+            _ => self.alloc(node, stack),
+        }
+    }
+
+    // mark-sweep algorithm
+    fn collect(&mut self, stack: &mut Stack) -> Result<()> {
+        // marking is exactly the same as mark-compact, breadth-first from the
+        // roots
+        {
+            let mut worklist: VecDeque<NodePointer> = VecDeque::new();
+
+            for root in &stack.roots {
+                for child in &root.children {
+                    worklist.push_back(*child);
+                }
+            }
+
+            while let Some(node_pointer) = worklist.pop_front() {
+                let idx = usize::from(node_pointer);
+                // large objects have their own mark bits
+                let newly_marked = match &mut self.large {
+                    Some(large) if large.contains(node_pointer) => large.mark(node_pointer),
+                    _ if self.marked[idx] => false,
+                    _ => {
+                        self.marked[idx] = true;
+                        true
+                    }
+                };
+                if newly_marked {
+                    for child_node_pointer in &self.committed_memory[idx].children {
+                        worklist.push_back(*child_node_pointer);
+                    }
+                }
+            }
+        }
+
+        // then we sweep. Rather than appending to the old free list, we build
+        // it again from scratch, since every slot that isn't marked is free
+        // (whether it was garbage or never allocated in the first place)
+        {
+            self.free_list.clear();
+            // walk from the top down so that the lowest address ends up at the
+            // top of the free list
+            for idx in (0..self.end()).rev() {
+                if self.marked[idx] {
+                    // unmark it for the next collection cycle
+                    self.marked[idx] = false;
+                } else {
+                    // reset the slot so that the garbage's children vec gets
+                    // dropped
+                    self.committed_memory[idx] = Node::default();
+                    self.free_list.push(NodePointer::from(idx));
+                }
+            }
+            if let Some(large) = &mut self.large {
+                large.sweep();
+            }
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn node_pointer_from_usize(&self, idx: usize) -> NodePointer {
+        NodePointer::from(idx)
+    }
+
+    #[inline(always)]
+    fn free(&self) -> usize {
+        // there's no free pointer, so report the number of slots in use
+        self.end() - self.free_list.len() + self.large.as_ref().map_or(0, |large| large.used())
+    }
+
+    fn heap_size(&self) -> usize {
+        self.end()
+    }
+}
+
+impl Barriers for MarkSweepHeap {}
+
+impl Nodes for MarkSweepHeap {
+    type Children = Vec<NodePointer>;
+
+    #[inline(always)]
+    fn get(&self, node_pointer: NodePointer) -> Option<&Node> {
+        self.committed_memory.get(usize::from(node_pointer))
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self, node_pointer: NodePointer) -> Option<&mut Node> {
+        self.committed_memory.get_mut(usize::from(node_pointer))
+    }
+}
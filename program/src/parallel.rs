@@ -0,0 +1,349 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+
+use crossbeam_deque::{Injector, Steal, Stealer, Worker};
+
+use crate::large::LargeObjectSpace;
+use crate::shared::{Children, Node, NodePointer, Payload, Stack};
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// how many to-space slots a copying thread claims at a time
+pub const LAB_SIZE: usize = 64;
+// the forwarding address of a node that hasn't been copied yet
+const NOT_FORWARDED: usize = usize::MAX;
+
+/// the number of threads that there are cores on this machine
+pub fn available_threads() -> usize {
+    thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+/// Marks everything in `committed_memory` that's reachable from the stack,
+/// using `threads` worker threads. The roots go into a shared injector queue,
+/// and every worker pushes the children it finds onto its own deque, which the
+/// other workers steal from once they run out of work. A node is only pushed
+/// by whichever worker flips its mark bit first, so every node is scanned
+/// exactly once
+pub fn mark<P: Payload, C: Children>(
+    committed_memory: &[Node<P, C>],
+    stack: &Stack,
+    threads: usize,
+) -> Vec<AtomicBool> {
+    let marks: Vec<AtomicBool> = committed_memory
+        .iter()
+        .map(|_| AtomicBool::new(false))
+        .collect();
+    // the number of nodes that have been pushed but not scanned yet. A
+    // worker only decrements this after it's pushed all the children of a
+    // node, so it can only hit zero once there's nothing left to mark
+    let pending = AtomicUsize::new(0);
+
+    let injector = Injector::new();
+    for root in &stack.roots {
+        for child in &root.children {
+            if !marks[usize::from(*child)].swap(true, Ordering::Relaxed) {
+                pending.fetch_add(1, Ordering::SeqCst);
+                injector.push(*child);
+            }
+        }
+    }
+
+    let workers: Vec<Worker<NodePointer>> =
+        (0..threads.max(1)).map(|_| Worker::new_lifo()).collect();
+    let stealers: Vec<Stealer<NodePointer>> = workers.iter().map(|w| w.stealer()).collect();
+
+    thread::scope(|scope| {
+        for worker in workers {
+            let (marks, pending, injector, stealers) = (&marks, &pending, &injector, &stealers);
+            scope.spawn(move || loop {
+                match worker.pop().or_else(|| steal(&worker, injector, stealers)) {
+                    Some(node_pointer) => {
+                        for child in committed_memory[usize::from(node_pointer)].children.iter() {
+                            if !marks[usize::from(*child)].swap(true, Ordering::Relaxed) {
+                                pending.fetch_add(1, Ordering::SeqCst);
+                                worker.push(*child);
+                            }
+                        }
+                        pending.fetch_sub(1, Ordering::SeqCst);
+                    }
+                    None => {
+                        if pending.load(Ordering::SeqCst) == 0 {
+                            break;
+                        }
+                        // somebody else is still scanning, and might push
+                        // something we can steal
+                        thread::yield_now();
+                    }
+                }
+            });
+        }
+    });
+
+    marks
+}
+
+/// takes a batch of roots from the injector, or failing that, a node from one
+/// of the other workers
+fn steal(
+    worker: &Worker<NodePointer>,
+    injector: &Injector<NodePointer>,
+    stealers: &[Stealer<NodePointer>],
+) -> Option<NodePointer> {
+    std::iter::repeat_with(|| {
+        injector
+            .steal_batch_and_pop(worker)
+            .or_else(|| stealers.iter().map(|s| s.steal()).collect())
+    })
+    .find(|s| !s.is_retry())
+    .and_then(Steal::success)
+}
+
+/// Copies everything reachable from the stack out of from-space and into
+/// to-space, using `threads` worker threads, and returns the new free pointer.
+///
+/// Every thread copies into its own local allocation block (LAB) of to-space,
+/// so the threads only contend on the to-space free pointer once every
+/// `LAB_SIZE` copies. Forwarding addresses live in a table off to the side,
+/// and a thread only copies a node if it manages to compare-and-swap its
+/// forwarding address in first. Everybody else just uses the address that won,
+/// so every node is copied exactly once. Copied nodes are pushed onto the
+/// copying thread's deque to be scanned, and the deques get work-stolen just
+/// like in `mark`.
+///
+/// Whatever is left over in each thread's last LAB is a hole in to-space, so
+/// the new free pointer can be up to `threads * LAB_SIZE` higher than the
+/// serial collector's.
+///
+/// Objects in the large object space don't get copied. Whoever marks one
+/// first scans it instead, and the marks get copied over into `large` at the
+/// end, ready for it to be swept
+pub fn copy<P: Payload, C: Children>(
+    committed_memory: &mut [Node<P, C>],
+    stack: &mut Stack,
+    from_space: usize,
+    to_space: usize,
+    extent: usize,
+    threads: usize,
+    large: Option<&mut LargeObjectSpace>,
+) -> Result<usize> {
+    let top = to_space + extent;
+    let (large_start, large_end) = large
+        .as_ref()
+        .map_or((0, 0), |large| (large.start, large.end));
+    let copier = Copier {
+        slots: Slots(committed_memory.as_mut_ptr()),
+        forwarding: (0..extent)
+            .map(|_| AtomicUsize::new(NOT_FORWARDED))
+            .collect(),
+        free: AtomicUsize::new(to_space),
+        from_space,
+        top,
+        large_start,
+        large_marks: (large_start..large_end)
+            .map(|_| AtomicBool::new(false))
+            .collect(),
+    };
+    let pending = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+
+    // the roots get copied up front, one slot at a time so they don't leave
+    // any holes
+    let injector = Injector::new();
+    let mut lab = Lab::new(1);
+    for root in &mut stack.roots {
+        for child in &mut root.children {
+            let (forwarded, copied) = copier
+                .forward(*child, &mut lab)
+                .ok_or("ran out of to-space copying the roots")?;
+            if copied {
+                pending.fetch_add(1, Ordering::SeqCst);
+                injector.push(forwarded);
+            }
+            *child = forwarded;
+        }
+    }
+
+    let workers: Vec<Worker<NodePointer>> =
+        (0..threads.max(1)).map(|_| Worker::new_lifo()).collect();
+    let stealers: Vec<Stealer<NodePointer>> = workers.iter().map(|w| w.stealer()).collect();
+
+    let labs: Vec<Lab> = thread::scope(|scope| {
+        let handles: Vec<_> = workers
+            .into_iter()
+            .map(|worker| {
+                let (copier, pending, failed, injector, stealers) =
+                    (&copier, &pending, &failed, &injector, &stealers);
+                scope.spawn(move || {
+                    let mut lab = Lab::new(LAB_SIZE);
+                    while !failed.load(Ordering::Relaxed) {
+                        match worker.pop().or_else(|| steal(&worker, injector, stealers)) {
+                            Some(node_pointer) => {
+                                // whoever pops a node off a deque is the only
+                                // one that ever scans it
+                                let node = unsafe { &mut *copier.slots.slot(node_pointer) };
+                                for child in node.children.iter_mut() {
+                                    match copier.forward(*child, &mut lab) {
+                                        Some((forwarded, copied)) => {
+                                            if copied {
+                                                pending.fetch_add(1, Ordering::SeqCst);
+                                                worker.push(forwarded);
+                                            }
+                                            *child = forwarded;
+                                        }
+                                        None => failed.store(true, Ordering::Relaxed),
+                                    }
+                                }
+                                pending.fetch_sub(1, Ordering::SeqCst);
+                            }
+                            None => {
+                                if pending.load(Ordering::SeqCst) == 0 {
+                                    break;
+                                }
+                                thread::yield_now();
+                            }
+                        }
+                    }
+                    lab
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    if failed.into_inner() {
+        return Err("ran out of to-space copying in parallel".into());
+    }
+
+    // if the last LAB that got handed out wasn't used up, we can give its
+    // leftovers back
+    let mut free = copier.free.into_inner().min(top);
+    for lab in labs {
+        if lab.end == free {
+            free = lab.cursor;
+        }
+    }
+    if let Some(large) = large {
+        for (i, mark) in copier.large_marks.into_iter().enumerate() {
+            if mark.into_inner() {
+                large.mark((large_start + i).into());
+            }
+        }
+    }
+    Ok(free)
+}
+
+/// a pointer to the start of the heap that can be shared between threads.
+/// A from-space slot is only ever touched by the thread that won the race to
+/// forward it, and a to-space slot is only touched by the thread that copied
+/// into it, and then by the thread that scans it
+struct Slots<P, C>(*mut Node<P, C>);
+
+// deriving these would need `P: Copy` and `C: Copy`, when it's only the
+// pointer that gets copied
+impl<P, C> Clone for Slots<P, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<P, C> Copy for Slots<P, C> {}
+
+unsafe impl<P: Send, C: Send> Send for Slots<P, C> {}
+unsafe impl<P: Send, C: Send> Sync for Slots<P, C> {}
+
+impl<P, C> Slots<P, C> {
+    #[inline(always)]
+    fn slot(self, node_pointer: NodePointer) -> *mut Node<P, C> {
+        unsafe { self.0.add(usize::from(node_pointer)) }
+    }
+}
+
+/// a local allocation block, the part of to-space a single thread copies into
+struct Lab {
+    cursor: usize,
+    end: usize,
+    size: usize,
+}
+
+impl Lab {
+    fn new(size: usize) -> Self {
+        Self {
+            cursor: 0,
+            end: 0,
+            size,
+        }
+    }
+
+    /// hands out the next slot, claiming a new block from `free` if this one's
+    /// used up
+    #[inline(always)]
+    fn bump(&mut self, free: &AtomicUsize, top: usize) -> Option<usize> {
+        if self.cursor == self.end {
+            let start = free.fetch_add(self.size, Ordering::SeqCst);
+            if start >= top {
+                return None;
+            }
+            self.cursor = start;
+            self.end = (start + self.size).min(top);
+        }
+        self.cursor += 1;
+        Some(self.cursor - 1)
+    }
+}
+
+struct Copier<P, C> {
+    slots: Slots<P, C>,
+    // indexed by offset into from-space
+    forwarding: Vec<AtomicUsize>,
+    // the to-space free pointer that LABs get claimed from
+    free: AtomicUsize,
+    from_space: usize,
+    top: usize,
+    // the mark bits for the large object space, from `large_start` on
+    large_start: usize,
+    large_marks: Vec<AtomicBool>,
+}
+
+impl<P: Payload, C: Children> Copier<P, C> {
+    /// returns the to-space address of a from-space node, and whether it was
+    /// this call that copied it there. Returns nothing if to-space is full
+    #[inline(always)]
+    fn forward(&self, node_pointer: NodePointer, lab: &mut Lab) -> Option<(NodePointer, bool)> {
+        // a large object stays where it is, so "copying" it is just marking
+        // it, and whoever does gets to scan it
+        if let Some(mark) = usize::from(node_pointer)
+            .checked_sub(self.large_start)
+            .and_then(|i| self.large_marks.get(i))
+        {
+            return Some((node_pointer, !mark.swap(true, Ordering::Relaxed)));
+        }
+        let forwarding = &self.forwarding[usize::from(node_pointer) - self.from_space];
+        let forwarding_address = forwarding.load(Ordering::Acquire);
+        if forwarding_address != NOT_FORWARDED {
+            return Some((forwarding_address.into(), false));
+        }
+        // claim a slot first, so that the address we race to install is
+        // final. If we lose, nobody else ever saw the slot, so we hand it back
+        let new_node_pointer = lab.bump(&self.free, self.top)?;
+        match forwarding.compare_exchange(
+            NOT_FORWARDED,
+            new_node_pointer,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => {
+                // we won, so we're the only one who'll ever touch the old slot
+                let mut node = std::mem::take(unsafe { &mut *self.slots.slot(node_pointer) });
+                node.forwarding_address = None;
+                unsafe { *self.slots.slot(new_node_pointer.into()) = node };
+                Some((new_node_pointer.into(), true))
+            }
+            Err(forwarding_address) => {
+                lab.cursor -= 1;
+                Some((forwarding_address.into(), false))
+            }
+        }
+    }
+}
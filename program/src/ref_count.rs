@@ -0,0 +1,333 @@
+use crate::shared::{Barriers, MemoryManager, Node, NodePointer, Nodes, Stack};
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// the colors from Bacon and Rajan's synchronous cycle collector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    // in use, or free
+    Black,
+    // possible member of a cycle
+    Gray,
+    // member of a garbage cycle
+    White,
+    // possible root of a cycle
+    Purple,
+}
+
+/// This reference counting algorithm keeps a count of every reference to a
+/// node, from both the heap and the stack, and frees the node the moment its
+/// count drops to zero. Counting alone can't free cycles, so every node whose
+/// count gets decremented without hitting zero is buffered as a possible root
+/// of a garbage cycle, and `collect` runs Bacon and Rajan's trial deletion over
+/// the buffer.
+///
+/// The counts are kept up to date through the write and root barriers, so
+/// edges changed directly through `get_mut(..).children` aren't seen at all.
+/// Like mark-sweep, nodes never move and freed slots go onto a free list
+#[derive(Clone)]
+pub struct RefCountHeap {
+    pub committed_memory: Vec<Node>,
+    pub counts: Vec<usize>,
+    pub colors: Vec<Color>,
+    // whether a node is in `candidates`
+    pub buffered: Vec<bool>,
+    // possible roots of garbage cycles
+    pub candidates: Vec<NodePointer>,
+    // nodes start out with a count of zero, but they can't be freed until the
+    // mutator's had a chance to link them to something. So every new node goes
+    // in here, and whatever still has a count of zero at the next collection
+    // gets freed
+    pub zero_count_table: Vec<NodePointer>,
+    pub allocated: Vec<bool>,
+    // slots that are free to be allocated into, lowest address at the end
+    pub free_list: Vec<NodePointer>,
+}
+
+impl RefCountHeap {
+    pub fn init(size: usize) -> Self {
+        let mut committed_memory: Vec<Node> = Vec::new();
+        for _ in 0..size {
+            committed_memory.push(Node::default());
+        }
+        let free_list = (0..size).rev().map(NodePointer::from).collect();
+        Self {
+            committed_memory,
+            counts: vec![0; size],
+            colors: vec![Color::Black; size],
+            buffered: vec![false; size],
+            candidates: Vec::new(),
+            zero_count_table: Vec::new(),
+            allocated: vec![false; size],
+            free_list,
+        }
+    }
+
+    #[inline(always)]
+    fn increment(&mut self, node_pointer: NodePointer) {
+        let idx = usize::from(node_pointer);
+        self.counts[idx] += 1;
+        self.colors[idx] = Color::Black;
+    }
+
+    /// decrements a node's count, freeing it (and anything only it pointed to)
+    /// if the count hits zero
+    fn decrement(&mut self, node_pointer: NodePointer) {
+        // freeing a node decrements all of its children, which could free
+        // them too, so we use a worklist instead of recursing
+        let mut worklist = vec![node_pointer];
+        while let Some(node_pointer) = worklist.pop() {
+            let idx = usize::from(node_pointer);
+            self.counts[idx] -= 1;
+            if self.counts[idx] == 0 {
+                // release the node
+                worklist.extend(self.committed_memory[idx].children.iter().copied());
+                self.colors[idx] = Color::Black;
+                // if it's buffered, the cycle collector still has a pointer to
+                // it, so let the cycle collector free it
+                if !self.buffered[idx] {
+                    self.free_slot(node_pointer);
+                }
+            } else {
+                self.possible_root(node_pointer);
+            }
+        }
+    }
+
+    /// a node that got decremented to something other than zero might be
+    /// part of a garbage cycle now
+    #[inline(always)]
+    fn possible_root(&mut self, node_pointer: NodePointer) {
+        let idx = usize::from(node_pointer);
+        if self.colors[idx] != Color::Purple {
+            self.colors[idx] = Color::Purple;
+            if !self.buffered[idx] {
+                self.buffered[idx] = true;
+                self.candidates.push(node_pointer);
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn free_slot(&mut self, node_pointer: NodePointer) {
+        let idx = usize::from(node_pointer);
+        // drop the children vec
+        self.committed_memory[idx] = Node::default();
+        self.allocated[idx] = false;
+        self.free_list.push(node_pointer);
+    }
+
+    /// frees every new node that never got linked to anything
+    pub fn collect_zero_count_table(&mut self) {
+        for node_pointer in std::mem::take(&mut self.zero_count_table) {
+            let idx = usize::from(node_pointer);
+            // the node might've already been linked and freed, and the slot
+            // might even belong to another node by now, so we only free it if
+            // nothing has a reference to it
+            if self.allocated[idx] && self.counts[idx] == 0 && !self.buffered[idx] {
+                // it doesn't own any references to its children anymore
+                for child in std::mem::take(&mut self.committed_memory[idx].children) {
+                    self.decrement(child);
+                }
+                self.free_slot(node_pointer);
+            }
+        }
+    }
+
+    /// Bacon and Rajan's synchronous cycle collection. For every candidate we
+    /// subtract the references coming from inside the subgraph below it. Any
+    /// node whose count is still above zero is referenced from outside and
+    /// gets its counts restored, and everything else is garbage
+    pub fn collect_cycles(&mut self) {
+        self.mark_roots();
+        for node_pointer in self.candidates.clone() {
+            self.scan(node_pointer);
+        }
+        for node_pointer in std::mem::take(&mut self.candidates) {
+            self.buffered[usize::from(node_pointer)] = false;
+            self.collect_white(node_pointer);
+        }
+    }
+
+    fn mark_roots(&mut self) {
+        let mut candidates = Vec::new();
+        for node_pointer in std::mem::take(&mut self.candidates) {
+            let idx = usize::from(node_pointer);
+            if self.colors[idx] == Color::Purple && self.counts[idx] > 0 {
+                self.mark_gray(node_pointer);
+                candidates.push(node_pointer);
+            } else {
+                self.buffered[idx] = false;
+                // the node got released while it was buffered
+                if self.colors[idx] == Color::Black && self.counts[idx] == 0 {
+                    self.free_slot(node_pointer);
+                }
+            }
+        }
+        self.candidates = candidates;
+    }
+
+    /// trial deletion of every internal reference in the subgraph
+    fn mark_gray(&mut self, node_pointer: NodePointer) {
+        if self.colors[usize::from(node_pointer)] == Color::Gray {
+            return;
+        }
+        self.colors[usize::from(node_pointer)] = Color::Gray;
+        let mut worklist = vec![node_pointer];
+        while let Some(node_pointer) = worklist.pop() {
+            for i in 0..self.committed_memory[usize::from(node_pointer)]
+                .children
+                .len()
+            {
+                let child = self.committed_memory[usize::from(node_pointer)].children[i];
+                let idx = usize::from(child);
+                self.counts[idx] -= 1;
+                if self.colors[idx] != Color::Gray {
+                    self.colors[idx] = Color::Gray;
+                    worklist.push(child);
+                }
+            }
+        }
+    }
+
+    /// whitens everything gray that only had internal references, and
+    /// restores everything that's reachable from outside the subgraph
+    fn scan(&mut self, node_pointer: NodePointer) {
+        let mut worklist = vec![node_pointer];
+        while let Some(node_pointer) = worklist.pop() {
+            let idx = usize::from(node_pointer);
+            if self.colors[idx] == Color::Gray {
+                if self.counts[idx] > 0 {
+                    self.scan_black(node_pointer);
+                } else {
+                    self.colors[idx] = Color::White;
+                    worklist.extend(self.committed_memory[idx].children.iter().copied());
+                }
+            }
+        }
+    }
+
+    /// undoes the trial deletion below a node that turned out to be live
+    fn scan_black(&mut self, node_pointer: NodePointer) {
+        self.colors[usize::from(node_pointer)] = Color::Black;
+        let mut worklist = vec![node_pointer];
+        while let Some(node_pointer) = worklist.pop() {
+            for i in 0..self.committed_memory[usize::from(node_pointer)]
+                .children
+                .len()
+            {
+                let child = self.committed_memory[usize::from(node_pointer)].children[i];
+                let idx = usize::from(child);
+                self.counts[idx] += 1;
+                if self.colors[idx] != Color::Black {
+                    self.colors[idx] = Color::Black;
+                    worklist.push(child);
+                }
+            }
+        }
+    }
+
+    /// frees a garbage cycle
+    fn collect_white(&mut self, node_pointer: NodePointer) {
+        let mut worklist = vec![node_pointer];
+        while let Some(node_pointer) = worklist.pop() {
+            let idx = usize::from(node_pointer);
+            if self.colors[idx] == Color::White && !self.buffered[idx] {
+                self.colors[idx] = Color::Black;
+                worklist.extend(self.committed_memory[idx].children.iter().copied());
+                self.free_slot(node_pointer);
+            }
+        }
+    }
+}
+
+impl MemoryManager for RefCountHeap {
+    // allocates a new node into the first slot on the free list
+    fn alloc(&mut self, node: Node, stack: &mut Stack) -> Result<NodePointer> {
+        // if there's nothing left on the free list
+        if self.free_list.is_empty() {
+            // we need to run gc
+            self.collect(stack)?;
+        }
+        // take a slot off of the free list
+        let node_pointer = match self.free_list.pop() {
+            Some(node_pointer) => node_pointer,
+            None => {
+                return Err("gg collection didn't result in any amount of garbage collected".into())
+            }
+        };
+        let idx = usize::from(node_pointer);
+        // the new node holds a reference to everything it was created with
+        for child in &node.children {
+            self.counts[usize::from(*child)] += 1;
+        }
+        // add it to the heap
+        self.committed_memory[idx] = node;
+        self.counts[idx] = 0;
+        self.colors[idx] = Color::Black;
+        self.allocated[idx] = true;
+        self.zero_count_table.push(node_pointer);
+
+        Ok(node_pointer)
+    }
+
+    /// counting frees most garbage the moment it becomes garbage, so all
+    /// that's left to collect are new nodes that never got used and cycles
+    fn collect(&mut self, _stack: &mut Stack) -> Result<()> {
+        self.collect_zero_count_table();
+        self.collect_cycles();
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn node_pointer_from_usize(&self, idx: usize) -> NodePointer {
+        NodePointer::from(idx)
+    }
+
+    #[inline(always)]
+    fn free(&self) -> usize {
+        // there's no free pointer, so report the number of slots in use
+        self.committed_memory.len() - self.free_list.len()
+    }
+
+    fn heap_size(&self) -> usize {
+        self.committed_memory.len()
+    }
+}
+
+impl Barriers for RefCountHeap {
+    /// increments whatever got written before decrementing whatever got
+    /// overwritten, so that overwriting a reference with itself can't free it
+    #[inline(always)]
+    fn write_barrier(
+        &mut self,
+        _parent: NodePointer,
+        old: Option<NodePointer>,
+        new: Option<NodePointer>,
+    ) {
+        self.root_barrier(old, new);
+    }
+
+    #[inline(always)]
+    fn root_barrier(&mut self, old: Option<NodePointer>, new: Option<NodePointer>) {
+        if let Some(new) = new {
+            self.increment(new);
+        }
+        if let Some(old) = old {
+            self.decrement(old);
+        }
+    }
+}
+
+impl Nodes for RefCountHeap {
+    type Children = Vec<NodePointer>;
+
+    #[inline(always)]
+    fn get(&self, node_pointer: NodePointer) -> Option<&Node> {
+        self.committed_memory.get(usize::from(node_pointer))
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self, node_pointer: NodePointer) -> Option<&mut Node> {
+        self.committed_memory.get_mut(usize::from(node_pointer))
+    }
+}
@@ -25,7 +25,7 @@ impl Stack {
     }
     /// Provides a breadth-first ordered print of all the reachable values on the stack
     /// keep in mind the stack pooints into the heap
-    pub fn dump_all<T: MemoryManager>(&self, heap: &T) -> Result<String> {
+    pub fn dump_all<P: Payload, T: MemoryManager<P>>(&self, heap: &T) -> Result<String> {
         // for each root
         let mut root_list = Vec::new();
         for root in &self.roots {
@@ -48,7 +48,7 @@ impl Stack {
     // heap we would traverse over it again. However, we only have 1 root on the
     // heap so it doesn't currently matter.
     #[inline]
-    pub fn sum_bfs<T: MemoryManager>(&self, heap: &T) -> Result<u64> {
+    pub fn sum_bfs<P: Payload, T: MemoryManager<P>>(&self, heap: &T) -> Result<u64> {
         // for each root
         let mut sum = 0;
         for root in &self.roots {
@@ -60,7 +60,7 @@ impl Stack {
         Ok(sum)
     }
     #[inline]
-    pub fn sum_dfs<T: MemoryManager>(&self, heap: &T) -> Result<u64> {
+    pub fn sum_dfs<P: Payload, T: MemoryManager<P>>(&self, heap: &T) -> Result<u64> {
         // for each root
         let mut sum = 0;
         for root in &self.roots {
@@ -71,7 +71,29 @@ impl Stack {
         }
         Ok(sum)
     }
-    pub fn count<T: MemoryManager>(&self, heap: &T) -> Result<(u64, u64)> {
+    /// makes `child` a child of the `root`th root. Like the edges on the heap,
+    /// roots should be changed through here so that the heap gets to see it
+    pub fn add_root<P: Payload, T: MemoryManager<P>>(
+        &mut self,
+        heap: &mut T,
+        root: usize,
+        child: NodePointer,
+    ) {
+        self.roots[root].children.push(child);
+        heap.root_barrier(None, Some(child));
+    }
+    /// removes the `idx`th child of the `root`th root
+    pub fn remove_root<P: Payload, T: MemoryManager<P>>(
+        &mut self,
+        heap: &mut T,
+        root: usize,
+        idx: usize,
+    ) -> NodePointer {
+        let old = self.roots[root].children.remove(idx);
+        heap.root_barrier(Some(old), None);
+        old
+    }
+    pub fn count<P: Payload, T: MemoryManager<P>>(&self, heap: &T) -> Result<(u64, u64)> {
         let mut node_count = 0;
         let mut connection_count = 0;
 
@@ -88,14 +110,75 @@ impl Stack {
     }
 }
 
-pub trait MemoryManager {
-    fn alloc(&mut self, node: Node, stack: &mut Stack) -> Result<NodePointer>;
+/// What a node carries around. The workloads only ever put a number in it,
+/// but a payload can be as big as it likes
+pub trait Payload:
+    'static + std::fmt::Debug + Clone + Default + Send + Sync + From<Option<u32>>
+{
+    /// the number that dumps and sums go by
+    fn value(&self) -> Option<u32>;
+}
+
+impl Payload for Option<u32> {
+    #[inline(always)]
+    fn value(&self) -> Option<u32> {
+        *self
+    }
+}
+
+/// a value padded out with `N` bytes, so that nodes can be made bigger
+/// without changing anything else about them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Padded<const N: usize> {
+    pub value: Option<u32>,
+    pub padding: [u8; N],
+}
+
+// `Default` is only derived for arrays of up to 32 elements
+impl<const N: usize> Default for Padded<N> {
+    fn default() -> Self {
+        Self {
+            value: None,
+            padding: [0; N],
+        }
+    }
+}
+
+impl<const N: usize> From<Option<u32>> for Padded<N> {
+    fn from(value: Option<u32>) -> Self {
+        Self {
+            value,
+            ..Default::default()
+        }
+    }
+}
+
+impl<const N: usize> Payload for Padded<N> {
+    #[inline(always)]
+    fn value(&self) -> Option<u32> {
+        self.value
+    }
+}
+
+pub trait MemoryManager<P: Payload = Option<u32>>: Edges<P> + Barriers {
+    fn alloc(&mut self, node: Node<P>, stack: &mut Stack) -> Result<NodePointer>;
+    /// allocates a node with `size` slots worth of payload after it. Only
+    /// `MarkCompactHeap` and `StopAndCopyHeap` lay out objects of
+    /// different sizes. Every other heap's nodes take up the same amount of
+    /// space, so asking them for any payload is an error, rather than getting
+    /// quietly ignored
+    fn alloc_sized(
+        &mut self,
+        node: Node<P>,
+        size: usize,
+        stack: &mut Stack,
+    ) -> Result<NodePointer> {
+        if size > 0 {
+            return Err("this heap's nodes are all the same size".into());
+        }
+        self.alloc(node, stack)
+    }
     fn collect(&mut self, stack: &mut Stack) -> Result<()>;
-    // lifetime is elided here: by one of the lifetime ellision rules: given
-    // &self or &mut self, we apply the lifetime of &self to all output
-    // lifetimes
-    fn get(&self, node_pointer: NodePointer) -> Option<&Node>;
-    fn get_mut(&mut self, node_pointer: NodePointer) -> Option<&mut Node>;
     fn node_pointer_from_usize(&self, idx: usize) -> NodePointer;
     fn free(&self) -> usize;
     fn heap_size(&self) -> usize;
@@ -108,16 +191,14 @@ pub trait MemoryManager {
         worklist.push_back(node_pointer);
 
         while let Some(node_pointer) = worklist.pop_front() {
+            let node_pointer = self.read_barrier(node_pointer);
             if !visited.contains(&node_pointer) {
                 visited.insert(node_pointer);
 
-                let node = self.get(node_pointer).unwrap();
-                if let Some(value) = node.value {
+                if let Some(value) = self.value(node_pointer) {
                     elements.push(value.to_string());
                 }
-                for child in &node.children {
-                    worklist.push_back(*child);
-                }
+                worklist.extend(self.children(node_pointer));
             }
         }
         Ok(elements.join(", "))
@@ -132,16 +213,14 @@ pub trait MemoryManager {
         worklist.push_back(node_pointer);
 
         while let Some(node_pointer) = worklist.pop_front() {
+            let node_pointer = self.read_barrier(node_pointer);
             if !visited.contains(&node_pointer) {
                 visited.insert(node_pointer);
 
-                let node = self.get(node_pointer).unwrap();
-                if let Some(value) = node.value {
+                if let Some(value) = self.value(node_pointer) {
                     sum += value as u64;
                 }
-                for child in &node.children {
-                    worklist.push_back(*child);
-                }
+                worklist.extend(self.children(node_pointer));
             }
         }
         Ok(sum)
@@ -155,16 +234,14 @@ pub trait MemoryManager {
         let mut worklist: Vec<NodePointer> = vec![node_pointer];
 
         while let Some(node_pointer) = worklist.pop() {
+            let node_pointer = self.read_barrier(node_pointer);
             if !visited.contains(&node_pointer) {
                 visited.insert(node_pointer);
 
-                let node = self.get(node_pointer).unwrap();
-                if let Some(value) = node.value {
+                if let Some(value) = self.value(node_pointer) {
                     sum += value as u64;
                 }
-                for child in &node.children {
-                    worklist.push(*child);
-                }
+                worklist.extend(self.children(node_pointer));
             }
         }
         Ok(sum)
@@ -178,21 +255,247 @@ pub trait MemoryManager {
         let mut worklist = VecDeque::new();
         worklist.push_back(node_pointer);
         while let Some(node) = worklist.pop_front() {
+            let node = self.read_barrier(node);
             connection_count += 1;
             if !visited.contains(&node) {
                 visited.insert(node);
                 node_count += 1;
 
-                for child in &self.get(node).unwrap().children {
-                    worklist.push_back(*child);
-                }
+                worklist.extend(self.children(node));
             }
         }
         Ok((node_count, connection_count))
     }
 }
 
+/// How a collector gets to observe the mutator. The defaults don't do
+/// anything, which is all that a collector that only runs while the mutator
+/// is stopped needs
+pub trait Barriers {
+    /// gets called on every pointer store that goes through `add_child`,
+    /// `set_child`, `remove_child` and `pop_child`, right after the store
+    /// happens. `old` is the reference that got overwritten and `new` is the
+    /// reference that got written, if there is one. Collectors that need to
+    /// observe the mutator (generational, incremental, etc.) override this.
+    ///
+    /// The barrier runs after the store so that it sees `parent` the way the
+    /// mutator left it: a barrier that rescans `parent` has to find `new` in
+    /// there, and a barrier that frees `old` can end up freeing `parent` too,
+    /// after which there's nothing left to store into. Barriers that only
+    /// look at `old` and `new` themselves (remembering `parent`, shading `new`
+    /// or logging `old`) get the same references either way
+    #[inline(always)]
+    fn write_barrier(
+        &mut self,
+        _parent: NodePointer,
+        _old: Option<NodePointer>,
+        _new: Option<NodePointer>,
+    ) {
+    }
+    /// the same as `write_barrier`, but for the roots on the stack, which get
+    /// changed through `Stack::add_root` and `Stack::remove_root`
+    #[inline(always)]
+    fn root_barrier(&mut self, _old: Option<NodePointer>, _new: Option<NodePointer>) {}
+    /// the address that a pointer the mutator is holding onto should be read
+    /// from. This only matters for collectors that copy while the mutator is
+    /// running, where the pointer might be to an old copy of a node
+    #[inline(always)]
+    fn read_barrier(&self, node_pointer: NodePointer) -> NodePointer {
+        node_pointer
+    }
+}
+
+/// How the mutator reads and changes the graph on the heap. Heaps that keep
+/// their nodes around as `Node`s get all of this by implementing `Nodes`, and
+/// heaps that lay their objects out some other way implement it themselves
+pub trait Edges<P: Payload = Option<u32>> {
+    /// the value that a node is holding onto
+    fn value(&self, node_pointer: NodePointer) -> Option<u32>;
+    /// every node that a node points to, in order
+    fn children(&self, node_pointer: NodePointer) -> impl Iterator<Item = NodePointer> + '_;
+    fn child(&self, parent: NodePointer, idx: usize) -> Result<&NodePointer>;
+    /// the mutator should change edges through these, otherwise the write
+    /// barrier never sees it
+    fn add_child(&mut self, parent: NodePointer, child: NodePointer) -> Result<()>;
+    /// overwrites the `idx`th child of `parent`, returning the old child
+    fn set_child(
+        &mut self,
+        parent: NodePointer,
+        idx: usize,
+        child: NodePointer,
+    ) -> Result<NodePointer>;
+    /// removes the `idx`th child of `parent`, shifting the rest of the
+    /// children down
+    fn remove_child(&mut self, parent: NodePointer, idx: usize) -> Result<NodePointer>;
+    /// removes the last child of `parent`, if it has any
+    fn pop_child(&mut self, parent: NodePointer) -> Result<Option<NodePointer>>;
+}
+
+/// Heaps that keep every node around as a `Node`, which can be handed out to
+/// the mutator directly. This only asks for `Barriers` rather than all of
+/// `MemoryManager`, since `MemoryManager` asks for the `Edges` that this
+/// provides
+pub trait Nodes<P: Payload = Option<u32>>: Barriers {
+    /// where the heap's nodes keep their children, which is a `Vec` unless
+    /// the heap lays them out some other way
+    type Children: Children;
+    // lifetime is elided here: by one of the lifetime ellision rules: given
+    // &self or &mut self, we apply the lifetime of &self to all output
+    // lifetimes
+    fn get(&self, node_pointer: NodePointer) -> Option<&Node<P, Self::Children>>;
+    fn get_mut(&mut self, node_pointer: NodePointer) -> Option<&mut Node<P, Self::Children>>;
+}
+
+// every edge change goes through `get_mut`, so the mutator shouldn't reach
+// into `get_mut(..).children` itself, otherwise the write barrier never sees it
+impl<P: Payload, T: Nodes<P>> Edges<P> for T {
+    fn add_child(&mut self, parent: NodePointer, child: NodePointer) -> Result<()> {
+        self.get_mut(parent)
+            .ok_or("parent isn't on the heap")?
+            .children
+            .push(child);
+        self.write_barrier(parent, None, Some(child));
+        Ok(())
+    }
+    fn set_child(
+        &mut self,
+        parent: NodePointer,
+        idx: usize,
+        child: NodePointer,
+    ) -> Result<NodePointer> {
+        let old = *self.child(parent, idx)?;
+        self.get_mut(parent).unwrap().children[idx] = child;
+        self.write_barrier(parent, Some(old), Some(child));
+        Ok(old)
+    }
+    fn remove_child(&mut self, parent: NodePointer, idx: usize) -> Result<NodePointer> {
+        self.child(parent, idx)?;
+        let old = self.get_mut(parent).unwrap().children.remove(idx);
+        self.write_barrier(parent, Some(old), None);
+        Ok(old)
+    }
+    fn pop_child(&mut self, parent: NodePointer) -> Result<Option<NodePointer>> {
+        let old = self
+            .get_mut(parent)
+            .ok_or("parent isn't on the heap")?
+            .children
+            .pop();
+        if old.is_some() {
+            self.write_barrier(parent, old, None);
+        }
+        Ok(old)
+    }
+    #[inline(always)]
+    fn value(&self, node_pointer: NodePointer) -> Option<u32> {
+        self.get(node_pointer).unwrap().value.value()
+    }
+    #[inline(always)]
+    fn children(&self, node_pointer: NodePointer) -> impl Iterator<Item = NodePointer> + '_ {
+        self.get(node_pointer).unwrap().children.iter().copied()
+    }
+    #[inline(always)]
+    fn child(&self, parent: NodePointer, idx: usize) -> Result<&NodePointer> {
+        Ok(self
+            .get(parent)
+            .ok_or("parent isn't on the heap")?
+            .children
+            .get(idx)
+            .ok_or("parent doesn't have that many children")?)
+    }
+}
+
 use std::collections::{HashSet, VecDeque};
+use std::ops::{Deref, DerefMut};
+use std::time::Duration;
+
+/// keeps track of how long a collector stopped the mutator for
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Pauses {
+    pub count: usize,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl Pauses {
+    pub fn record(&mut self, pause: Duration) {
+        self.count += 1;
+        self.total += pause;
+        self.max = self.max.max(pause);
+    }
+}
+
+/// one mark bit per slot, kept off to the side instead of in the nodes
+/// themselves
+#[derive(Debug, Default, Clone)]
+pub struct MarkBitmap {
+    pub words: Vec<u64>,
+}
+
+impl MarkBitmap {
+    pub fn init(size: usize) -> Self {
+        Self {
+            words: vec![0; size.div_ceil(64)],
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_marked(&self, idx: usize) -> bool {
+        self.words[idx / 64] & (1 << (idx % 64)) != 0
+    }
+
+    #[inline(always)]
+    pub fn mark(&mut self, idx: usize) {
+        self.words[idx / 64] |= 1 << (idx % 64);
+    }
+
+    #[inline(always)]
+    pub fn unmark(&mut self, idx: usize) {
+        self.words[idx / 64] &= !(1 << (idx % 64));
+    }
+
+    /// unmarks everything
+    pub fn clear(&mut self) {
+        self.words.iter_mut().for_each(|word| *word = 0);
+    }
+}
+
+/// how many slots of payload come after the node in every slot, for heaps
+/// that lay out nodes of different sizes. A node with `payload` slots of it
+/// takes up `1 + payload` slots, and the ones after the first are left empty.
+/// This stays empty until something gets allocated with a payload, so heaps
+/// that never do don't pay for it
+#[derive(Debug, Default, Clone)]
+pub struct SizeTable {
+    pub payloads: Vec<usize>,
+}
+
+impl SizeTable {
+    /// how many slots the node at `idx` takes up
+    #[inline(always)]
+    pub fn size(&self, idx: usize) -> usize {
+        1 + self.payloads.get(idx).copied().unwrap_or(0)
+    }
+
+    /// records a node at `idx` with `payload` slots after it, on a heap of
+    /// `len` slots
+    #[inline(always)]
+    pub fn set(&mut self, idx: usize, payload: usize, len: usize) {
+        if self.payloads.is_empty() {
+            if payload == 0 {
+                return;
+            }
+            self.payloads.resize(len, 0);
+        }
+        self.payloads[idx] = payload;
+        self.payloads[idx + 1..=idx + payload].fill(0);
+    }
+
+    /// whether every node is still just the one slot
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.payloads.is_empty()
+    }
+}
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct NodePointer {
@@ -215,9 +518,60 @@ impl From<NodePointer> for usize {
 /// A node doesn't technically need a parent pointer, it's literally just there for eye candy
 #[derive(Debug, Default, Clone)]
 #[repr(align(8))]
-pub struct Node {
+pub struct Node<P = Option<u32>, C = Vec<NodePointer>> {
     pub forwarding_address: Option<NodePointer>,
     pub parent: Option<NodePointer>,
-    pub children: Vec<NodePointer>,
-    pub value: Option<u32>,
+    pub children: C,
+    pub value: P,
+}
+
+impl<P> Node<P> {
+    /// the same node, keeping its children in `C` instead. The mutator
+    /// always hands over nodes with a `Vec` of children, which heaps with
+    /// some other layout turn into theirs as they allocate
+    #[inline(always)]
+    pub fn with_children<C: Children>(self) -> Node<P, C> {
+        Node {
+            forwarding_address: self.forwarding_address,
+            parent: self.parent,
+            children: self.children.into(),
+            value: self.value,
+        }
+    }
+}
+
+/// Where a node keeps its children. Everything that only reads or overwrites
+/// them goes through the slice they deref to, and the rest is here
+pub trait Children:
+    'static
+    + std::fmt::Debug
+    + Clone
+    + Default
+    + Send
+    + Sync
+    + From<Vec<NodePointer>>
+    + Deref<Target = [NodePointer]>
+    + DerefMut
+{
+    fn push(&mut self, child: NodePointer);
+    fn pop(&mut self) -> Option<NodePointer>;
+    /// removes the `idx`th child, shifting the rest of them down
+    fn remove(&mut self, idx: usize) -> NodePointer;
+}
+
+/// A `Vec` lives wherever the system allocator put it, so compacting or
+/// copying a node only ever moves a pointer to its children around
+impl Children for Vec<NodePointer> {
+    #[inline(always)]
+    fn push(&mut self, child: NodePointer) {
+        Vec::push(self, child)
+    }
+    #[inline(always)]
+    fn pop(&mut self) -> Option<NodePointer> {
+        Vec::pop(self)
+    }
+    #[inline(always)]
+    fn remove(&mut self, idx: usize) -> NodePointer {
+        Vec::remove(self, idx)
+    }
 }
@@ -1,10 +1,32 @@
-use crate::shared::{MemoryManager, Node, NodePointer, Stack};
+use std::time::Instant;
+
+use crate::large::LargeObjectSpace;
+use crate::mark_compact::Incremental;
+use crate::parallel;
+use crate::shared::{
+    Barriers, Children, MemoryManager, Node, NodePointer, Nodes, Pauses, Payload, SizeTable, Stack,
+};
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// the order that `collect` copies objects into to-space in, which decides
+/// how they're laid out afterwards
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyOrder {
+    // cheney's algorithm
+    Bfs,
+    // using an explicit stack
+    Dfs,
+    // breadth-first, but within blocks of `block_size` nodes at a time
+    Hierarchical { block_size: usize },
+    // split up between `threads` threads, each copying into its own chunks of
+    // to-space, so there's no telling how things end up laid out
+    Parallel { threads: usize },
+}
+
 /// This mark-compact algorithm uses the LISP-2 style sliding algorithm
 /// Heap includes the graph data structure, and acts pretty much like an arena
 #[derive(Clone)]
-pub struct StopAndCopyHeap {
+pub struct StopAndCopyHeap<P = Option<u32>, C = Vec<NodePointer>> {
     // should be at the start of the heap
     pub from_space: usize,
     // should be at the middle of the heap
@@ -14,13 +36,38 @@ pub struct StopAndCopyHeap {
     // where we allocate from
     pub free: usize,
     pub top: usize,
-    pub committed_memory: Vec<Node>,
+    pub committed_memory: Vec<Node<P, C>>,
+    pub copy_order: CopyOrder,
+    // if this is set, copying happens a little bit at a time on every
+    // allocation, Baker style, instead of all at once in `collect`
+    pub incremental: Option<Incremental>,
+    // whether we're in the middle of an incremental copy. While we are,
+    // to-space looks like
+    //
+    // [ scanned | copied | unused | allocated ]
+    // to_space  scan     free     top       to_space + extent
+    //
+    // and everything scanned or allocated only points into to-space
+    pub collecting: bool,
+    pub scan: usize,
+    // a node with a payload of `size` slots takes up `1 + size` of them, and
+    // only the first one holds the node. They all get copied together
+    pub sizes: SizeTable,
+    // nodes bigger than its threshold go in here instead, which comes after
+    // both of the semispaces
+    pub large: Option<LargeObjectSpace>,
+    // how many slots have been copied
+    pub copied: usize,
+    pub pauses: Pauses,
 }
 
-impl StopAndCopyHeap {
-    /// make sure you allocate double the amount you actually need
-    pub fn init(size: usize) -> Self {
-        let mut committed_memory: Vec<Node> = Vec::new();
+impl<P: Payload, C: Children> StopAndCopyHeap<P, C> {
+    /// the same as `init`, but for nodes carrying any kind of payload, with
+    /// their children laid out any way. The rest of the constructors work for
+    /// any of them too, e.g.
+    /// `StopAndCopyHeap::<Object, Inline<ARITY>>::init_ordered(size, copy_order)`
+    pub fn init_payload(size: usize) -> Self {
+        let mut committed_memory: Vec<Node<P, C>> = Vec::new();
         for _ in 0..size {
             committed_memory.push(Node::default());
         }
@@ -43,36 +90,94 @@ impl StopAndCopyHeap {
             free,
             extent,
             committed_memory,
+            copy_order: CopyOrder::Bfs,
+            incremental: None,
+            collecting: false,
+            scan: to_space,
+            sizes: SizeTable::default(),
+            large: None,
+            copied: 0,
+            pauses: Pauses::default(),
         }
     }
-}
 
-impl MemoryManager for StopAndCopyHeap {
-    // allocates a new node
-    // we can just add a new node and return its id
-    fn alloc(&mut self, node: Node, stack: &mut Stack) -> Result<NodePointer> {
-        // check if free is going over fromspace + tospace
-        if self.free >= self.top {
-            log::trace!("exceeded from space, must run garbage collector");
-            // we need to run gc
-            self.collect(stack)?;
+    pub fn init_incremental(size: usize, incremental: Incremental) -> Self {
+        Self {
+            incremental: Some(incremental),
+            ..Self::init_payload(size)
         }
-        if self.free >= self.top {
-            return Err("gg collection didn't result in any amount of garbage collected".into());
+    }
+
+    pub fn init_ordered(size: usize, copy_order: CopyOrder) -> Self {
+        Self {
+            copy_order,
+            ..Self::init_payload(size)
         }
+    }
 
-        // set the node id to where the top of the heap is
-        let node_pointer = NodePointer::from(self.free);
-        // add it to the heap
-        self.committed_memory[usize::from(node_pointer)] = node;
-        // bump the free pointer
-        self.free += 1;
+    pub fn init_parallel(size: usize, copy_threads: usize) -> Self {
+        let threads = copy_threads.max(1);
+        Self::init_ordered(size, CopyOrder::Parallel { threads })
+    }
 
-        Ok(node_pointer)
+    /// the same as `init_payload`, along with a large object space of
+    /// `large_size` slots, for nodes taking up more than `threshold` slots
+    pub fn init_large(size: usize, threshold: usize, large_size: usize) -> Self {
+        let mut heap = Self::init_payload(size);
+        heap.committed_memory
+            .resize_with(size + large_size, Node::default);
+        heap.large = Some(LargeObjectSpace::init(threshold, size, large_size));
+        heap
+    }
+}
+
+impl StopAndCopyHeap {
+    /// make sure you allocate double the amount you actually need
+    pub fn init(size: usize) -> Self {
+        Self::init_payload(size)
+    }
+}
+
+impl<P: Payload, C: Children> MemoryManager<P> for StopAndCopyHeap<P, C> {
+    // allocates a new node
+    // we can just add a new node and return its id
+    fn alloc(&mut self, node: Node<P>, stack: &mut Stack) -> Result<NodePointer> {
+        self.bump(node, 0, stack)
+    }
+
+    // the node goes in the next `1 + size` slots, unless it's big enough for
+    // the large object space
+    fn alloc_sized(
+        &mut self,
+        node: Node<P>,
+        size: usize,
+        stack: &mut Stack,
+    ) -> Result<NodePointer> {
+        match &self.large {
+            Some(large) if 1 + size > large.threshold => self.alloc_large(node, 1 + size, stack),
+            _ if size > 0 && matches!(self.copy_order, CopyOrder::Parallel { .. }) => {
+                Err("copying in parallel can't move a node that has a payload".into())
+            }
+            _ => self.bump(node, size, stack),
+        }
     }
 
     /// stop-and-copy algorithm
     fn collect(&mut self, stack: &mut Stack) -> Result<()> {
+        if self.incremental.is_some() {
+            // whatever's in the middle of being copied was reachable when the
+            // collection started, so some of it might be garbage by now. We
+            // finish copying it, then start over from scratch
+            let instant = Instant::now();
+            if self.collecting {
+                self.finish(stack)?;
+            }
+            self.flip(stack)?;
+            self.finish(stack)?;
+            self.pauses.record(instant.elapsed());
+            return Ok(());
+        }
+        let instant = Instant::now();
         // first we swap from space with tospace
         {
             // literally std::mem swap them. They're both locations, neither is size
@@ -90,6 +195,24 @@ impl MemoryManager for StopAndCopyHeap {
             // self.free = self.to_space;
             // trace!("after swapping the heap, self.top (maxiumum of whatever is fromspace) is {}, self.from_space is {}, self.to_space is {}, and self.free is {}", self.top, self.from_space, self.to_space, self.free);
         }
+        if let CopyOrder::Parallel { threads } = self.copy_order {
+            self.free = parallel::copy(
+                &mut self.committed_memory,
+                stack,
+                self.from_space,
+                self.to_space,
+                self.extent,
+                threads,
+                self.large.as_mut(),
+            )?;
+            self.copied += self.free - self.to_space;
+            if let Some(large) = &mut self.large {
+                large.sweep();
+            }
+            self.pauses.record(instant.elapsed());
+            return Ok(());
+        }
+
         // the scan also starts from the beginning of the to_space
         let mut scan = self.free;
 
@@ -106,46 +229,35 @@ impl MemoryManager for StopAndCopyHeap {
             }
         }
 
-        // now we process all the references of the nodes in the worklist as well
-        {
-            // you might be wondering...
-            // how do we do `for each node in worklist`?
-            //
-            // well, so long as the scan does not catch up to free
-            // that is, so as long as we have not processed every single "copied" oject on the heap, keep on going
-            while scan < self.free {
-                let scan_node_pointer = NodePointer::from(scan);
-                // get all references, or children of the object that was recently copied to tospace
-                //
-                //
-                //  ... to copy the references over,
-                for i in 0..self.get(scan_node_pointer).unwrap().children.len() {
-                    // set the reference to whatever the forwarding address stored inside the reference is, or copy it
-                    //
-                    // TL;DR the reference should now be pointing to copied objects in the tospace no matter what
-                    self.get_mut(scan_node_pointer).unwrap().children[i] =
-                        self.copy(self.get(scan_node_pointer).unwrap().children[i])?;
-                    // the references get added to the worklist automatically
+        loop {
+            match self.copy_order {
+                CopyOrder::Bfs => self.scan_breadth_first(scan)?,
+                CopyOrder::Dfs => self.scan_depth_first(scan)?,
+                CopyOrder::Hierarchical { block_size } => {
+                    self.scan_hierarchical(scan, block_size)?
                 }
-                // don't forget to bump the scan pointer
-                scan += 1;
+                CopyOrder::Parallel { .. } => unreachable!("copied in parallel above"),
+            }
+            // large objects never end up between scan and free, so they get
+            // scanned once everything else has been, and whatever they point
+            // to gets scanned in the next go around
+            scan = self.free;
+            while let Some(node_pointer) = self.next_gray() {
+                self.scan(node_pointer)?;
             }
+            if scan == self.free {
+                break;
+            }
+        }
+        if let Some(large) = &mut self.large {
+            large.sweep();
         }
 
         // now we know that our freed space is just committed_memory.len() / 2 - self.free
+        self.pauses.record(instant.elapsed());
         Ok(())
     }
 
-    #[inline(always)]
-    fn get(&self, node_pointer: NodePointer) -> Option<&Node> {
-        self.committed_memory.get(usize::from(node_pointer))
-    }
-
-    #[inline(always)]
-    fn get_mut(&mut self, node_pointer: NodePointer) -> Option<&mut Node> {
-        self.committed_memory.get_mut(usize::from(node_pointer))
-    }
-
     #[inline(always)]
     fn node_pointer_from_usize(&self, idx: usize) -> NodePointer {
         NodePointer::from(self.to_space + idx)
@@ -153,8 +265,12 @@ impl MemoryManager for StopAndCopyHeap {
 
     #[inline(always)]
     fn free(&self) -> usize {
-        // `free` on stop-and-copy should be subtracted by to space
+        // `free` on stop-and-copy should be subtracted by to space, and
+        // anything allocated in the middle of an incremental copy sits at the
+        // top of to-space
         self.free - self.to_space
+            + (self.to_space + self.extent - self.top)
+            + self.large.as_ref().map_or(0, |large| large.used())
     }
 
     fn heap_size(&self) -> usize {
@@ -162,30 +278,428 @@ impl MemoryManager for StopAndCopyHeap {
     }
 }
 
-impl StopAndCopyHeap {
+impl<P: Payload, C: Children> Barriers for StopAndCopyHeap<P, C> {
+    /// Baker's read barrier. The mutator can still be holding onto pointers
+    /// into from-space, so anything that's been copied gets read from its
+    /// copy instead
+    #[inline(always)]
+    fn read_barrier(&self, node_pointer: NodePointer) -> NodePointer {
+        // the mutator can load a from-space pointer out of a node that hasn't
+        // been scanned yet and hold onto it past the end of the collection, so
+        // forwarding addresses in from-space get followed until the next flip
+        if self.incremental.is_some() && self.in_from_space(node_pointer) {
+            if let Some(forwarding_address) =
+                self.committed_memory[usize::from(node_pointer)].forwarding_address
+            {
+                return forwarding_address;
+            }
+        }
+        node_pointer
+    }
+
+    /// scanned and newly allocated nodes are never scanned again, so they
+    /// can't be left pointing into from-space
+    #[inline(always)]
+    fn write_barrier(
+        &mut self,
+        parent: NodePointer,
+        _old: Option<NodePointer>,
+        new: Option<NodePointer>,
+    ) {
+        if !self.collecting {
+            return;
+        }
+        // a large object could end up only being pointed to by nodes that
+        // have been scanned too, but it doesn't move, so it just gets marked
+        if new.is_some_and(|new| self.mark_large(new))
+            || !new.is_some_and(|new| self.in_from_space(new))
+        {
+            return;
+        }
+        let idx = usize::from(self.read_barrier(parent));
+        if idx < self.scan || idx >= self.top {
+            for i in 0..self.committed_memory[idx].children.len() {
+                let child = self.committed_memory[idx].children[i];
+                if self.in_from_space(child) {
+                    // if to-space is full, the collection's going to fail
+                    // anyway
+                    if let Ok(child) = self.copy(child) {
+                        self.committed_memory[idx].children[i] = child;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<P: Payload, C: Children> Nodes<P> for StopAndCopyHeap<P, C> {
+    type Children = C;
+
+    #[inline(always)]
+    fn get(&self, node_pointer: NodePointer) -> Option<&Node<P, C>> {
+        self.committed_memory
+            .get(usize::from(self.read_barrier(node_pointer)))
+    }
+
+    /// nodes that get changed in the middle of an incremental copy have to be
+    /// copied first, so the change doesn't get lost in from-space
+    #[inline(always)]
+    fn get_mut(&mut self, node_pointer: NodePointer) -> Option<&mut Node<P, C>> {
+        let node_pointer = if self.collecting && self.in_from_space(node_pointer) {
+            self.copy(node_pointer).ok()?
+        } else {
+            self.read_barrier(node_pointer)
+        };
+        self.committed_memory.get_mut(usize::from(node_pointer))
+    }
+}
+
+impl<P: Payload, C: Children> StopAndCopyHeap<P, C> {
+    /// allocates into the top of to-space in the middle of an incremental
+    /// copy, and into the bottom otherwise. Every allocation copies up to
+    /// `quantum` nodes' worth of children first
+    fn alloc_incremental(
+        &mut self,
+        node: Node<P>,
+        size: usize,
+        stack: &mut Stack,
+        incremental: Incremental,
+    ) -> Result<NodePointer> {
+        if !self.collecting && (self.free() >= incremental.trigger || self.free + size >= self.top)
+        {
+            let instant = Instant::now();
+            self.flip(stack)?;
+            self.pauses.record(instant.elapsed());
+        }
+        if self.collecting {
+            self.increment(stack, incremental.quantum)?;
+        }
+        // if we ran out of room before the copy could finish, the rest of it
+        // has to happen now
+        if self.free + size >= self.top {
+            self.collect(stack)?;
+        }
+        if self.free + size >= self.top {
+            return Err("gg collection didn't result in any amount of garbage collected".into());
+        }
+
+        let node_pointer = if self.collecting {
+            // nodes allocated in the middle of a copy are never scanned, so
+            // whatever they already point to has to be copied now
+            let mut node = node;
+            for child in node.children.iter_mut() {
+                *child = self.copy(*child)?;
+            }
+            self.top -= 1 + size;
+            self.write(self.top, node, size);
+            NodePointer::from(self.top)
+        } else {
+            self.write(self.free, node, size);
+            self.free += 1 + size;
+            NodePointer::from(self.free - 1 - size)
+        };
+        Ok(node_pointer)
+    }
+
+    /// bumps the free pointer past a node and `size` slots of payload
+    fn bump(&mut self, node: Node<P>, size: usize, stack: &mut Stack) -> Result<NodePointer> {
+        if let Some(incremental) = self.incremental {
+            return self.alloc_incremental(node, size, stack, incremental);
+        }
+        // check if free is going over fromspace + tospace
+        if self.free + size >= self.top {
+            log::trace!("exceeded from space, must run garbage collector");
+            // we need to run gc
+            self.collect(stack)?;
+        }
+        if self.free + size >= self.top {
+            return Err("gg collection didn't result in any amount of garbage collected".into());
+        }
+
+        // set the node id to where the top of the heap is
+        let node_pointer = NodePointer::from(self.free);
+        // add it to the heap
+        self.write(self.free, node, size);
+        // bump the free pointer
+        self.free += 1 + size;
+
+        Ok(node_pointer)
+    }
+
+    /// puts a node at `idx`, and clears out whatever was where its payload
+    /// goes
+    #[inline(always)]
+    fn write(&mut self, idx: usize, node: Node<P>, size: usize) {
+        self.committed_memory[idx] = node.with_children();
+        for payload in idx + 1..=idx + size {
+            self.committed_memory[payload] = Node::default();
+        }
+        self.sizes.set(idx, size, self.committed_memory.len());
+    }
+
+    /// first fit in the large object space, collecting if nothing fits
+    fn alloc_large(
+        &mut self,
+        node: Node<P>,
+        size: usize,
+        stack: &mut Stack,
+    ) -> Result<NodePointer> {
+        let mut node_pointer = self.large.as_mut().unwrap().alloc(size);
+        if node_pointer.is_none() {
+            // we need to run gc
+            self.collect(stack)?;
+            node_pointer = self.large.as_mut().unwrap().alloc(size);
+        }
+        let node_pointer =
+            node_pointer.ok_or("gg collection didn't result in any amount of garbage collected")?;
+        let mut node = node;
+        if self.collecting {
+            // the same goes for large objects as for anything else allocated
+            // in the middle of a copy, except that they get marked instead
+            for child in node.children.iter_mut() {
+                *child = self.copy(*child)?;
+            }
+            self.large.as_mut().unwrap().mark(node_pointer);
+        }
+        self.committed_memory[usize::from(node_pointer)] = node.with_children();
+        Ok(node_pointer)
+    }
+
+    /// starts an incremental copy by swapping the spaces and copying the roots
+    fn flip(&mut self, stack: &mut Stack) -> Result<()> {
+        std::mem::swap(&mut self.from_space, &mut self.to_space);
+        self.free = self.to_space;
+        self.scan = self.to_space;
+        self.top = self.to_space + self.extent;
+        self.collecting = true;
+        for root in &mut stack.roots {
+            for child in &mut root.children {
+                *child = self.copy(*child)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// scans up to `quantum` copied nodes, finishing the copy if there's
+    /// nothing left to scan
+    fn increment(&mut self, stack: &mut Stack, quantum: usize) -> Result<()> {
+        let instant = Instant::now();
+        for _ in 0..quantum {
+            if self.scan < self.free {
+                self.scan(NodePointer::from(self.scan))?;
+                self.scan += self.sizes.size(self.scan);
+            } else if let Some(node_pointer) = self.next_gray() {
+                self.scan(node_pointer)?;
+            } else {
+                break;
+            }
+        }
+        if self.scan >= self.free
+            && self
+                .large
+                .as_ref()
+                .is_none_or(|large| large.gray.is_empty())
+        {
+            self.finish(stack)?;
+        }
+        self.pauses.record(instant.elapsed());
+        Ok(())
+    }
+
+    /// copies whatever's left. The roots might've been changed to point into
+    /// from-space since the flip, so they get copied again first
+    fn finish(&mut self, stack: &mut Stack) -> Result<()> {
+        for root in &mut stack.roots {
+            for child in &mut root.children {
+                *child = self.copy(*child)?;
+            }
+        }
+        loop {
+            while self.scan < self.free {
+                self.scan(NodePointer::from(self.scan))?;
+                self.scan += self.sizes.size(self.scan);
+            }
+            match self.next_gray() {
+                Some(node_pointer) => self.scan(node_pointer)?,
+                None => break,
+            }
+        }
+        self.collecting = false;
+        if let Some(large) = &mut self.large {
+            large.sweep();
+        }
+        Ok(())
+    }
+
+    /// a large object that's been marked, but hasn't been scanned yet
+    #[inline(always)]
+    fn next_gray(&mut self) -> Option<NodePointer> {
+        self.large.as_mut()?.gray.pop()
+    }
+
+    /// large objects don't get copied, just marked and turned gray, so that
+    /// they get scanned once there's nothing left to scan in to-space.
+    /// Returns false if the node isn't a large object
+    #[inline(always)]
+    fn mark_large(&mut self, node_pointer: NodePointer) -> bool {
+        match &mut self.large {
+            Some(large) if large.contains(node_pointer) => {
+                if large.mark(node_pointer) {
+                    large.gray.push(node_pointer);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn in_from_space(&self, node_pointer: NodePointer) -> bool {
+        (self.from_space..self.from_space + self.extent).contains(&usize::from(node_pointer))
+    }
+
+    /// cheney's algorithm, everything between scan and free is the worklist
+    fn scan_breadth_first(&mut self, mut scan: usize) -> Result<()> {
+        // you might be wondering...
+        // how do we do `for each node in worklist`?
+        //
+        // well, so long as the scan does not catch up to free
+        // that is, so as long as we have not processed every single "copied" oject on the heap, keep on going
+        while scan < self.free {
+            self.scan(NodePointer::from(scan))?;
+            // don't forget to bump the scan pointer, past any payload too
+            scan += self.sizes.size(scan);
+        }
+        Ok(())
+    }
+
+    /// uses an explicit stack as the worklist instead, so a node's children
+    /// get copied right after it, then their children, and so on
+    fn scan_depth_first(&mut self, scan: usize) -> Result<()> {
+        // the roots have already been copied, so they start out on the stack.
+        // Reversed, so that the first root gets scanned first
+        // Payload slots get pushed too, but they don't have any children to
+        // copy
+        let mut worklist: Vec<NodePointer> =
+            (scan..self.free).rev().map(NodePointer::from).collect();
+        while let Some(node_pointer) = worklist.pop() {
+            let start = self.free;
+            self.scan(node_pointer)?;
+            // whatever got copied while scanning is pushed so that the first
+            // child is on top
+            worklist.extend((start..self.free).rev().map(NodePointer::from));
+        }
+        Ok(())
+    }
+
+    /// Wilson, Lam and Moher's hierarchical decomposition, which improves on
+    /// Moon's approximately depth-first copying. To-space is split up into
+    /// blocks, and whenever the block that we're copying into has something
+    /// unscanned in it, we scan that first. Otherwise, we fall back to a
+    /// cheney scan of the lowest block that still has something unscanned.
+    /// That way a node's descendants tend to end up in the same block as it
+    fn scan_hierarchical(&mut self, scan: usize, block_size: usize) -> Result<()> {
+        let block_size = block_size.max(1);
+        let to_space = self.to_space;
+        let block = |idx: usize| (idx - to_space) / block_size;
+        // the next node to scan in each block
+        let mut block_scan: Vec<usize> = (0..self.extent / block_size + 1)
+            .map(|b| to_space + b * block_size)
+            .collect();
+        block_scan[block(scan)] = scan;
+        // the lowest block that might still have something unscanned in it.
+        // Every block below the one we're copying into is already full
+        let mut major = block(scan);
+        while self.free > scan {
+            let current = block(self.free - 1);
+            let target = if block_scan[current] < self.free {
+                current
+            } else {
+                while major < current && block_scan[major] >= to_space + (major + 1) * block_size {
+                    major += 1;
+                }
+                if major == current {
+                    // everything's been scanned
+                    break;
+                }
+                major
+            };
+            // a block can start in the middle of a node's payload, so this
+            // steps through a slot at a time. Payload slots don't have any
+            // children to copy anyway
+            let node_pointer = NodePointer::from(block_scan[target]);
+            block_scan[target] += 1;
+            self.scan(node_pointer)?;
+        }
+        Ok(())
+    }
+
+    /// copies every child of a node, and points the node at the copies
+    #[inline(always)]
+    fn scan(&mut self, scan_node_pointer: NodePointer) -> Result<()> {
+        // get all references, or children of the object that was recently copied to tospace
+        //
+        //
+        //  ... to copy the references over,
+        let idx = usize::from(scan_node_pointer);
+        for i in 0..self.committed_memory[idx].children.len() {
+            // set the reference to whatever the forwarding address stored inside the reference is, or copy it
+            //
+            // TL;DR the reference should now be pointing to copied objects in the tospace no matter what
+            self.committed_memory[idx].children[i] =
+                self.copy(self.committed_memory[idx].children[i])?;
+            // the references get added to the worklist automatically
+        }
+        Ok(())
+    }
+
     // copy function
     #[inline(always)]
     pub fn copy(&mut self, node_pointer: NodePointer) -> Result<NodePointer> {
         // if object has a forwarding address, it means that we've already moved it over to to space, so we can just give it its reference
         // dbg!(node_pointer, stapi::value(node_pointer, self)?);
-        if let Some(forwarding_address) = self.get(node_pointer).unwrap().forwarding_address {
+        // (this goes straight to the memory, since `get` would follow the
+        // forwarding address for us in the middle of an incremental copy)
+        if self.mark_large(node_pointer) {
+            Ok(node_pointer)
+        } else if let Some(forwarding_address) =
+            self.committed_memory[usize::from(node_pointer)].forwarding_address
+        {
             Ok(forwarding_address)
+        } else if !self.in_from_space(node_pointer) {
+            // it's already in to-space
+            Ok(node_pointer)
+        } else if self.free + self.sizes.size(usize::from(node_pointer)) > self.top {
+            Err("ran out of to-space while copying".into())
         } else {
             let new_node_pointer = NodePointer::from(self.free);
+            let size = self.sizes.size(usize::from(node_pointer));
             // otherwise, the new nodepointer value of this object will be whatever free there is
-            // now use .swap() to move nodepointer current location to its new location free
-            self.committed_memory
-                .swap(usize::from(node_pointer), usize::from(new_node_pointer));
+            // now use .swap() to move nodepointer current location to its new location free,
+            // along with its payload
+            for i in 0..size {
+                self.committed_memory.swap(
+                    usize::from(node_pointer) + i,
+                    usize::from(new_node_pointer) + i,
+                );
+            }
+            self.sizes.set(
+                usize::from(new_node_pointer),
+                size - 1,
+                self.committed_memory.len(),
+            );
 
             // and remember to set the forwarding address of the moved nodepointer to none
-            self.get_mut(new_node_pointer).unwrap().forwarding_address = None;
+            self.committed_memory[usize::from(new_node_pointer)].forwarding_address = None;
 
             // now update the old forwarding address to include itself
             // keep in mind that this object in to space is complete garbage except for the forwarding address part
-            self.get_mut(node_pointer).unwrap().forwarding_address = Some(new_node_pointer);
+            self.committed_memory[usize::from(node_pointer)].forwarding_address =
+                Some(new_node_pointer);
 
             // also remember to bump free
-            self.free += 1;
+            self.free += size;
+            self.copied += size;
 
             // finally we can return the new_node_pointer
             Ok(new_node_pointer)
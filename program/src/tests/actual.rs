@@ -49,7 +49,7 @@ fn actual_garbage_collection<T: MemoryManager>(
                 heap.node_pointer_from_usize(parent),
                 heap.node_pointer_from_usize(child),
             );
-            heap.get_mut(parent).unwrap().children.push(child);
+            heap.add_child(parent, child).unwrap();
         }
     }
 
@@ -58,14 +58,10 @@ fn actual_garbage_collection<T: MemoryManager>(
         // parent to child
         log::trace!(
             "parent to delete child from from found: {:#?}",
-            heap.get(heap.node_pointer_from_usize(8000))
-                .unwrap()
-                .children
+            heap.children(heap.node_pointer_from_usize(8000))
+                .collect::<Vec<_>>()
         );
-        heap.get_mut(heap.node_pointer_from_usize(8000))
-            .unwrap()
-            .children
-            .pop();
+        heap.pop_child(heap.node_pointer_from_usize(8000)).unwrap();
         heap.alloc(Node::default(), stack).unwrap();
         log::debug!(
             "this is the size of the cleaned up heap: {}/{}",
@@ -121,6 +117,91 @@ fn stop_and_copy_actual() {
     actual_garbage_collection(&mut stack, &mut heap, HEAP_SIZE / 2).unwrap();
 }
 
+#[test]
+fn mark_sweep_actual() {
+    const STACK_SIZE: usize = 1;
+    const HEAP_SIZE: usize = 1_000_000;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = MarkSweepHeap::init(HEAP_SIZE);
+
+    actual_garbage_collection(&mut stack, &mut heap, HEAP_SIZE).unwrap();
+}
+
+#[test]
+fn generational_actual() {
+    const STACK_SIZE: usize = 1;
+    const HEAP_SIZE: usize = 1_000_000;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = GenerationalHeap::init(HEAP_SIZE);
+
+    actual_garbage_collection(&mut stack, &mut heap, HEAP_SIZE).unwrap();
+}
+
+#[test]
+fn ref_count_actual() {
+    const STACK_SIZE: usize = 1;
+    const HEAP_SIZE: usize = 1_000_000;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = RefCountHeap::init(HEAP_SIZE);
+
+    actual_garbage_collection(&mut stack, &mut heap, HEAP_SIZE).unwrap();
+}
+
+#[test]
+fn mark_region_actual() {
+    const STACK_SIZE: usize = 1;
+    const HEAP_SIZE: usize = 1_000_000;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap. The garbage is scattered a node at a time all
+    // over the heap, so lines have to be a single node for it to be reused
+    let mut heap = MarkRegionHeap::init_with_lines(HEAP_SIZE, 1, crate::mark_region::BLOCK_SIZE);
+
+    actual_garbage_collection(&mut stack, &mut heap, HEAP_SIZE).unwrap();
+}
+
+#[test]
+fn treadmill_actual() {
+    const STACK_SIZE: usize = 1;
+    const HEAP_SIZE: usize = 1_000_000;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = TreadmillHeap::init(HEAP_SIZE);
+
+    actual_garbage_collection(&mut stack, &mut heap, HEAP_SIZE).unwrap();
+}
+
+#[test]
+fn garbage_first_actual() {
+    const STACK_SIZE: usize = 1;
+    const HEAP_SIZE: usize = 1_000_000;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = GarbageFirstHeap::init(HEAP_SIZE);
+
+    actual_garbage_collection(&mut stack, &mut heap, HEAP_SIZE).unwrap();
+}
+
+#[test]
+fn adaptive_actual() {
+    const STACK_SIZE: usize = 1;
+    const HEAP_SIZE: usize = 1_000_000;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = AdaptiveHeap::init(HEAP_SIZE);
+
+    actual_garbage_collection(&mut stack, &mut heap, HEAP_SIZE).unwrap();
+}
+
 #[test]
 fn test_rng_behavior() {
     let mut rng = Pcg64::seed_from_u64(1234);
@@ -0,0 +1,97 @@
+use crate::{init_log, seed_root};
+
+use super::*;
+
+#[test]
+fn adaptive_switches_algorithms() {
+    init_log();
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    const HEAP_SIZE: usize = 100;
+    let mut heap = AdaptiveHeap::init(HEAP_SIZE);
+
+    // nothing's been measured yet, so the first collection compacts, and can
+    // use the whole heap to allocate into
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    for value in 2..=100 {
+        alloc_value(&mut stack, &mut heap, value);
+    }
+    heap.collect(&mut stack).unwrap();
+    // everything but the root died, so the next one copies
+    assert_eq!(heap.history.len(), 1);
+    let decision = heap.history[0];
+    assert_eq!(decision.algorithm, Algorithm::Compacting);
+    assert_eq!(decision.live, 1);
+    assert_eq!(decision.survival_rate, 0.01);
+    assert_eq!(decision.next, Algorithm::Copying);
+    // which can only allocate into the first half
+    assert_eq!(decision.free, 49);
+    assert_eq!(heap.free(), 1);
+
+    // which gets filled up with more garbage, and copied out of
+    for value in 2..=50 {
+        alloc_value(&mut stack, &mut heap, value);
+    }
+    let node_pointer = alloc_value(&mut stack, &mut heap, 2);
+    heap.add_child(stack.roots[0].children[0], node_pointer)
+        .unwrap();
+    assert_eq!(heap.history.len(), 2);
+    assert_eq!(heap.history[1].algorithm, Algorithm::Copying);
+    assert_eq!(heap.history[1].next, Algorithm::Copying);
+    // the root got copied into the second half
+    assert_ne!(stack.roots[0].children[0], root);
+    assert_eq!(usize::from(stack.roots[0].children[0]), 50);
+    assert_eq!(heap.free(), 2);
+
+    // then most of what gets allocated survives, so it switches back
+    let root = stack.roots[0].children[0];
+    for value in 3..=49 {
+        let node_pointer = alloc_value(&mut stack, &mut heap, value);
+        heap.add_child(root, node_pointer).unwrap();
+    }
+    let dump = stack.dump_all(&heap).unwrap();
+    heap.collect(&mut stack).unwrap();
+    let decision = heap.history[2];
+    assert_eq!(decision.algorithm, Algorithm::Copying);
+    assert_eq!(decision.live, 49);
+    assert_eq!(decision.survival_rate, 1.);
+    assert_eq!(decision.next, Algorithm::Compacting);
+    // and compacting can use the whole heap again
+    assert_eq!(decision.free, 51);
+    assert_eq!(stack.dump_all(&heap).unwrap(), dump);
+
+    // with everything back at the bottom of the heap
+    assert_eq!(usize::from(stack.roots[0].children[0]), 0);
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(heap.history[3].algorithm, Algorithm::Compacting);
+    assert_eq!(stack.dump_all(&heap).unwrap(), dump);
+    assert_eq!(heap.free(), 49);
+}
+
+#[test]
+fn adaptive_policy() {
+    init_log();
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // never copy
+    let policy = AdaptivePolicy {
+        copy_survival: 0.,
+        copy_occupancy: 0.,
+    };
+    let mut heap = AdaptiveHeap::init_with_policy(100, policy);
+
+    seed_root(&mut stack, &mut heap).unwrap();
+    for _ in 0..5 {
+        for value in 0..50 {
+            alloc_value(&mut stack, &mut heap, value);
+        }
+        heap.collect(&mut stack).unwrap();
+    }
+    assert!(heap
+        .history
+        .iter()
+        .all(|decision| decision.algorithm == Algorithm::Compacting
+            && decision.next == Algorithm::Compacting));
+    assert_eq!(heap.history.len(), 5);
+}
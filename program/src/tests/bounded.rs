@@ -0,0 +1,42 @@
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+
+use crate::{link_heap, make_garbage};
+
+use super::*;
+
+fn collected(heap: &mut MarkCompactHeap) -> (usize, u64, (u64, u64)) {
+    const STACK_SIZE: usize = 1;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    let mut rng = Pcg64::seed_from_u64(1234);
+    link_heap(&mut stack, heap, &mut rng).unwrap();
+    make_garbage(&mut stack, heap, 0.5, &mut rng).unwrap();
+    heap.collect(&mut stack).unwrap();
+    (
+        heap.free(),
+        stack.sum_bfs(heap).unwrap(),
+        stack.count(heap).unwrap(),
+    )
+}
+
+#[test]
+fn mark_stack_overflow() {
+    let heap_size: usize = 10_000;
+
+    let mut heap = MarkCompactHeap::init(heap_size);
+    let unbounded = collected(&mut heap);
+
+    // a mark stack that's big enough never overflows
+    let mut heap = MarkCompactHeap::init_bounded(heap_size, heap_size);
+    assert_eq!(collected(&mut heap), unbounded);
+    assert_eq!(heap.mark_stack_overflows, 0);
+
+    // but one that only fits a couple of nodes overflows all the time, and
+    // should still mark the same nodes
+    for capacity in [1, 2, 16] {
+        let mut heap = MarkCompactHeap::init_bounded(heap_size, capacity);
+        assert_eq!(collected(&mut heap), unbounded);
+        assert!(heap.mark_stack_overflows > 0);
+    }
+}
@@ -43,7 +43,7 @@ fn random_garbage_collection<T: MemoryManager>(stack: &mut Stack, heap: &mut T)
                 heap.node_pointer_from_usize(second),
             );
             // link child before point of removal to parent
-            heap.get_mut(first).unwrap().children.push(second);
+            heap.add_child(first, second).unwrap();
         }
     }
     log::info!("time it took to link children: {:#?}", instant.elapsed());
@@ -55,10 +55,7 @@ fn random_garbage_collection<T: MemoryManager>(stack: &mut Stack, heap: &mut T)
             // generate two random numbers
             let num = rng.gen_range(100..10_000);
             // link child before point of removal to parent
-            heap.get_mut(heap.node_pointer_from_usize(num))
-                .unwrap()
-                .children
-                .pop();
+            heap.pop_child(heap.node_pointer_from_usize(num)).unwrap();
         }
     }
     log::info!("time it took to remove children: {:#?}", instant.elapsed());
@@ -109,13 +106,133 @@ fn stop_and_copy_random() {
     random_garbage_collection(&mut stack, &mut heap).unwrap();
 }
 
+#[test]
+fn mark_sweep_random() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = env::var("HEAP_SIZE").unwrap().parse::<usize>().unwrap();
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = MarkSweepHeap::init(heap_size);
+
+    random_garbage_collection(&mut stack, &mut heap).unwrap();
+}
+
+#[test]
+fn generational_random() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = env::var("HEAP_SIZE").unwrap().parse::<usize>().unwrap();
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = GenerationalHeap::init(heap_size);
+
+    random_garbage_collection(&mut stack, &mut heap).unwrap();
+}
+
+#[test]
+fn ref_count_random() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = env::var("HEAP_SIZE").unwrap().parse::<usize>().unwrap();
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = RefCountHeap::init(heap_size);
+
+    random_garbage_collection(&mut stack, &mut heap).unwrap();
+}
+
+#[test]
+fn mark_region_random() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = env::var("HEAP_SIZE").unwrap().parse::<usize>().unwrap();
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = MarkRegionHeap::init(heap_size);
+
+    random_garbage_collection(&mut stack, &mut heap).unwrap();
+}
+
+#[test]
+fn treadmill_random() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = env::var("HEAP_SIZE").unwrap().parse::<usize>().unwrap();
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = TreadmillHeap::init(heap_size);
+
+    random_garbage_collection(&mut stack, &mut heap).unwrap();
+}
+
+#[test]
+fn garbage_first_random() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = env::var("HEAP_SIZE").unwrap().parse::<usize>().unwrap();
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = GarbageFirstHeap::init(heap_size);
+
+    random_garbage_collection(&mut stack, &mut heap).unwrap();
+}
+
+#[test]
+fn concurrent_random() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = env::var("HEAP_SIZE").unwrap().parse::<usize>().unwrap();
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = ConcurrentMarkSweepHeap::init(heap_size);
+
+    random_garbage_collection(&mut stack, &mut heap).unwrap();
+}
+
+#[test]
+fn adaptive_random() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = env::var("HEAP_SIZE").unwrap().parse::<usize>().unwrap();
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = AdaptiveHeap::init(heap_size);
+
+    random_garbage_collection(&mut stack, &mut heap).unwrap();
+}
+
+#[test]
+fn flat_random() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = env::var("HEAP_SIZE").unwrap().parse::<usize>().unwrap();
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap, with the children inline
+    let mut heap = MarkCompactHeap::<Option<u32>, Inline<ARITY>>::init_payload(heap_size);
+
+    random_garbage_collection(&mut stack, &mut heap).unwrap();
+}
+
+#[test]
+fn flat_copying_random() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = env::var("HEAP_SIZE").unwrap().parse::<usize>().unwrap() * 2;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap, with the children inline
+    let mut heap = StopAndCopyHeap::<Option<u32>, Inline<ARITY>>::init_payload(heap_size);
+
+    random_garbage_collection(&mut stack, &mut heap).unwrap();
+}
+
 fn sum_garbage_collection<T: MemoryManager>(
     stack: &mut Stack,
     heap: &mut T,
     heap_size: usize,
 ) -> Result<()> {
     let node_pointer = heap.alloc(Node::default(), stack).unwrap();
-    stack.roots[0].children.push(node_pointer);
+    stack.add_root(heap, 0, node_pointer);
 
     // dbg!(stack.dump_all(heap).unwrap());
     // 0, 1..., 8, 9
@@ -127,7 +244,7 @@ fn sum_garbage_collection<T: MemoryManager>(
         heap.node_pointer_from_usize(0),
     );
     // also add some cyclic data structures
-    heap.get_mut(start).unwrap().children.push(end);
+    heap.add_child(start, end).unwrap();
     dbg!(stack.dump_all(heap).unwrap());
     // check again
     assert_eq!(stack.sum_bfs(heap).unwrap(), 45);
@@ -158,3 +275,111 @@ fn stop_and_copy_sum() {
 
     sum_garbage_collection(&mut stack, &mut heap, heap_size / 2).unwrap();
 }
+
+#[test]
+fn mark_sweep_sum() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = 11;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = MarkSweepHeap::init(heap_size);
+
+    sum_garbage_collection(&mut stack, &mut heap, heap_size).unwrap();
+}
+
+#[test]
+fn generational_sum() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = 11;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = GenerationalHeap::init(heap_size);
+
+    sum_garbage_collection(&mut stack, &mut heap, heap_size).unwrap();
+}
+
+#[test]
+fn ref_count_sum() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = 11;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = RefCountHeap::init(heap_size);
+
+    sum_garbage_collection(&mut stack, &mut heap, heap_size).unwrap();
+}
+
+#[test]
+fn mark_region_sum() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = 11;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = MarkRegionHeap::init(heap_size);
+
+    sum_garbage_collection(&mut stack, &mut heap, heap_size).unwrap();
+}
+
+#[test]
+fn treadmill_sum() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = 11;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = TreadmillHeap::init(heap_size);
+
+    sum_garbage_collection(&mut stack, &mut heap, heap_size).unwrap();
+}
+
+#[test]
+fn garbage_first_sum() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = 11;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = GarbageFirstHeap::init(heap_size);
+
+    sum_garbage_collection(&mut stack, &mut heap, heap_size).unwrap();
+}
+
+#[test]
+fn concurrent_sum() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = 11;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = ConcurrentMarkSweepHeap::init(heap_size);
+
+    sum_garbage_collection(&mut stack, &mut heap, heap_size).unwrap();
+}
+
+#[test]
+fn adaptive_sum() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = 11;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = AdaptiveHeap::init(heap_size);
+
+    sum_garbage_collection(&mut stack, &mut heap, heap_size).unwrap();
+}
+
+#[test]
+fn flat_sum() {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = 11;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = MarkCompactHeap::<Option<u32>, Inline<ARITY>>::init_payload(heap_size);
+
+    sum_garbage_collection(&mut stack, &mut heap, heap_size).unwrap();
+}
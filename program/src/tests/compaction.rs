@@ -0,0 +1,160 @@
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+
+use crate::{init_log, link_heap, make_garbage, seed_root};
+
+use super::*;
+
+const COMPACTIONS: [Compaction; 4] = [
+    Compaction::Lisp2,
+    Compaction::TwoFinger,
+    Compaction::Threaded,
+    Compaction::Compressor,
+];
+
+/// the values of the nodes in the heap after a collection, in address order
+fn layout(compaction: Compaction) -> Vec<u32> {
+    init_log();
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    let mut heap: MarkCompactHeap = MarkCompactHeap::init_compacting(10, compaction);
+
+    // the root gets the children 2 through 6, then loses 2 and 3
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    for value in 2..=6 {
+        let node = Node {
+            value: Some(value),
+            ..Default::default()
+        };
+        let node_pointer = heap.alloc(node, &mut stack).unwrap();
+        heap.add_child(root, node_pointer).unwrap();
+    }
+    heap.remove_child(root, 0).unwrap();
+    heap.remove_child(root, 0).unwrap();
+
+    heap.collect(&mut stack).unwrap();
+    // however it's laid out, the graph should be the same
+    assert_eq!(heap.free(), 4);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 4, 5, 6");
+    heap.committed_memory[..heap.free()]
+        .iter()
+        .map(|node| node.value.unwrap())
+        .collect()
+}
+
+#[test]
+fn compaction_layout() {
+    // sliding keeps everything in order
+    assert_eq!(layout(Compaction::Lisp2), vec![1, 4, 5, 6]);
+    assert_eq!(layout(Compaction::Threaded), vec![1, 4, 5, 6]);
+    assert_eq!(layout(Compaction::Compressor), vec![1, 4, 5, 6]);
+    // two-finger fills the holes with whatever's at the top of the heap
+    assert_eq!(layout(Compaction::TwoFinger), vec![1, 6, 5, 4]);
+}
+
+#[test]
+fn compaction_same_graph() {
+    let heap_size: usize = 100_000;
+
+    let mut results = Vec::new();
+    for compaction in COMPACTIONS {
+        const STACK_SIZE: usize = 1;
+        let mut stack = Stack::new(STACK_SIZE);
+        let mut heap: MarkCompactHeap = MarkCompactHeap::init_compacting(heap_size, compaction);
+        let mut rng = Pcg64::seed_from_u64(1234);
+        link_heap(&mut stack, &mut heap, &mut rng).unwrap();
+        make_garbage(&mut stack, &mut heap, 0.5, &mut rng).unwrap();
+        heap.collect(&mut stack).unwrap();
+        heap.collect(&mut stack).unwrap();
+        assert!(heap.marks.words.iter().all(|word| *word == 0));
+        results.push((
+            heap.free(),
+            stack.sum_bfs(&heap).unwrap(),
+            stack.sum_dfs(&heap).unwrap(),
+            stack.count(&heap).unwrap(),
+        ));
+    }
+    assert!(results.windows(2).all(|w| w[0] == w[1]));
+}
+
+#[test]
+fn threaded_leaves_nodes_alone() {
+    init_log();
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    let mut heap: MarkCompactHeap = MarkCompactHeap::init_compacting(10, Compaction::Threaded);
+
+    // 1 -> 2, 3, 4
+    // 3 -> 3, 1, 4
+    // with 2 being garbage, and 4 having a parent pointer
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    let mut children = Vec::new();
+    for value in 2..=4 {
+        let node = Node {
+            value: Some(value),
+            ..Default::default()
+        };
+        children.push(heap.alloc(node, &mut stack).unwrap());
+    }
+    for child in &children {
+        heap.add_child(root, *child).unwrap();
+    }
+    for child in [children[1], root, children[2]] {
+        heap.add_child(children[1], child).unwrap();
+    }
+    heap.get_mut(children[2]).unwrap().parent = Some(children[1]);
+    heap.remove_child(root, 0).unwrap();
+
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(heap.free(), 3);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 3, 4");
+    let edges: Vec<Vec<usize>> = heap.committed_memory[..heap.free()]
+        .iter()
+        .map(|node| {
+            node.children
+                .iter()
+                .map(|child| usize::from(*child))
+                .collect()
+        })
+        .collect();
+    assert_eq!(edges, vec![vec![1, 2], vec![1, 0, 2], vec![]]);
+    // the threads went through the parent pointers, which should be back to
+    // what they were. Note that the parent pointer isn't a reference, so it
+    // doesn't get updated
+    let parents: Vec<Option<NodePointer>> = heap.committed_memory[..heap.free()]
+        .iter()
+        .map(|node| node.parent)
+        .collect();
+    assert_eq!(parents, vec![None, None, Some(children[1])]);
+    // and the forwarding addresses should never have been touched
+    assert!(heap.committed_memory[..heap.free()]
+        .iter()
+        .all(|node| node.forwarding_address.is_none()));
+}
+
+#[test]
+fn node_overhead() {
+    for compaction in COMPACTIONS {
+        println!(
+            "{:?}: {} bytes per node against {} for a Node, {} of which are for compaction",
+            compaction,
+            compaction.node_size(),
+            std::mem::size_of::<Node>(),
+            compaction.node_overhead()
+        );
+    }
+    // LISP-2 needs exactly what a node has, and threading saves the whole
+    // forwarding address, since it goes through the parent pointer instead
+    let node = std::mem::size_of::<Node>() as f64;
+    let word = std::mem::size_of::<Option<NodePointer>>() as f64;
+    assert_eq!(Compaction::Lisp2.node_size(), node);
+    assert_eq!(Compaction::TwoFinger.node_size(), node);
+    assert_eq!(Compaction::Threaded.node_overhead(), 0.0);
+    assert_eq!(Compaction::Threaded.node_size(), node - word);
+    assert!(Compaction::Compressor.node_size() < node);
+    // the Compressor only needs two bits a node: one for the mark and one for
+    // its share of the offset table
+    assert_eq!(Compaction::Compressor.node_overhead(), 0.25);
+}
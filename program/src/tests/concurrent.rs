@@ -0,0 +1,192 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::thread;
+
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+
+use crate::{init_log, seed_root};
+
+use super::*;
+
+/// what the mutator thinks every node it's allocated looks like
+type Shadow = HashMap<NodePointer, (Option<u32>, Vec<NodePointer>)>;
+
+/// allocates a node, and tells the shadow about it
+fn alloc_shadowed(
+    stack: &mut Stack,
+    heap: &mut ConcurrentMarkSweepHeap,
+    shadow: &mut Shadow,
+    value: u32,
+) -> NodePointer {
+    let node_pointer = alloc_value(stack, heap, value);
+    shadow.insert(node_pointer, (Some(value), Vec::new()));
+    node_pointer
+}
+
+/// a random node that's reachable from `root`, found the way a mutator would
+/// find one, by following edges
+fn walk(rng: &mut Pcg64, shadow: &Shadow, root: NodePointer) -> NodePointer {
+    let mut node_pointer = root;
+    for _ in 0..rng.gen_range(0..8) {
+        let children = &shadow[&node_pointer].1;
+        if children.is_empty() {
+            break;
+        }
+        node_pointer = children[rng.gen_range(0..children.len())];
+    }
+    node_pointer
+}
+
+/// checks that every node the mutator can still reach is exactly how it left
+/// it, and hasn't been swept into the free list. Returns how many there are
+fn verify(heap: &ConcurrentMarkSweepHeap, shadow: &Shadow, root: NodePointer) -> usize {
+    let free: HashSet<NodePointer> = heap.free_list.iter().copied().collect();
+    let mut visited = HashSet::new();
+    let mut worklist = VecDeque::from([root]);
+    while let Some(node_pointer) = worklist.pop_front() {
+        if !visited.insert(node_pointer) {
+            continue;
+        }
+        assert!(
+            !free.contains(&node_pointer),
+            "{:?} was swept",
+            node_pointer
+        );
+        let node = heap.get(node_pointer).unwrap();
+        let (value, children) = &shadow[&node_pointer];
+        assert_eq!(node.value, *value);
+        assert_eq!(&node.children, children);
+        worklist.extend(children.iter().copied());
+    }
+    visited.len()
+}
+
+/// builds a heap, then rewires it at random for as long as the marking thread
+/// is running, over and over, checking after every cycle that nothing live
+/// got swept
+fn rewire_while_marking(seed: u64) {
+    init_log();
+    const HEAP_SIZE: usize = 4096;
+    const CYCLES: usize = 20;
+    let mut rng = Pcg64::seed_from_u64(seed);
+    let mut stack = Stack::new(1);
+    // start a new cycle as soon as the last one's done
+    let mut heap = ConcurrentMarkSweepHeap::init_with_trigger(HEAP_SIZE, 0);
+    let mut shadow = Shadow::new();
+
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    shadow.insert(root, (Some(1), Vec::new()));
+    let mut value = 1;
+    for _ in 0..1000 {
+        value += 1;
+        let parent = walk(&mut rng, &shadow, root);
+        let child = alloc_shadowed(&mut stack, &mut heap, &mut shadow, value);
+        heap.add_child(parent, child).unwrap();
+        shadow.get_mut(&parent).unwrap().1.push(child);
+    }
+
+    for _ in 0..CYCLES {
+        let cycles = heap.cycles;
+        // this starts a cycle, if the last one didn't already start one
+        heap.alloc(Node::default(), &mut stack).unwrap();
+        assert!(heap.marking);
+
+        let mut ops = 0;
+        while ops < 100 || (heap.marking && !heap.marking_finished() && ops < 100_000) {
+            ops += 1;
+            if ops % 32 == 0 {
+                thread::yield_now();
+            }
+            let a = walk(&mut rng, &shadow, root);
+            let b = walk(&mut rng, &shadow, root);
+            let len = shadow[&a].1.len();
+            match rng.gen_range(0..5) {
+                // move one of a's children under b, which is what SATB is
+                // there for
+                0 if len > 0 => {
+                    let idx = rng.gen_range(0..len);
+                    let child = heap.remove_child(a, idx).unwrap();
+                    shadow.get_mut(&a).unwrap().1.remove(idx);
+                    heap.add_child(b, child).unwrap();
+                    shadow.get_mut(&b).unwrap().1.push(child);
+                }
+                1 if len < 4 => {
+                    heap.add_child(a, b).unwrap();
+                    shadow.get_mut(&a).unwrap().1.push(b);
+                }
+                2 if len > 0 => {
+                    let idx = rng.gen_range(0..len);
+                    heap.remove_child(a, idx).unwrap();
+                    shadow.get_mut(&a).unwrap().1.remove(idx);
+                }
+                3 if len > 0 => {
+                    let idx = rng.gen_range(0..len);
+                    heap.set_child(a, idx, b).unwrap();
+                    shadow.get_mut(&a).unwrap().1[idx] = b;
+                }
+                4 if len < 4 => {
+                    value += 1;
+                    let child = alloc_shadowed(&mut stack, &mut heap, &mut shadow, value);
+                    heap.add_child(a, child).unwrap();
+                    shadow.get_mut(&a).unwrap().1.push(child);
+                }
+                _ => {}
+            }
+        }
+        // let the cycle finish, which sweeps
+        while heap.cycles == cycles {
+            heap.alloc(Node::default(), &mut stack).unwrap();
+        }
+        verify(&heap, &shadow, root);
+    }
+
+    // and once everything is collected with the mutator stopped, exactly what
+    // the mutator can reach is left
+    heap.collect(&mut stack).unwrap();
+    let live = verify(&heap, &shadow, root);
+    assert_eq!(heap.free(), live);
+    assert!(heap.cycles >= CYCLES);
+}
+
+#[test]
+fn rewire_while_marking_stress() {
+    for seed in 0..4 {
+        rewire_while_marking(seed);
+    }
+}
+
+#[test]
+fn snapshot_keeps_floating_garbage() {
+    init_log();
+    let mut stack = Stack::new(1);
+    let mut heap = ConcurrentMarkSweepHeap::init_with_trigger(100, 3);
+    let mut shadow = Shadow::new();
+
+    // 1 -> 2 -> 3
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    let a = alloc_shadowed(&mut stack, &mut heap, &mut shadow, 2);
+    heap.add_child(root, a).unwrap();
+    let b = alloc_shadowed(&mut stack, &mut heap, &mut shadow, 3);
+    heap.add_child(a, b).unwrap();
+
+    // this allocation takes the snapshot, so 3 is in it, even once it's cut
+    // loose
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    assert!(heap.marking);
+    heap.pop_child(a).unwrap();
+
+    // the remark pause keeps it alive
+    while !heap.marking_finished() {
+        thread::yield_now();
+    }
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    assert_eq!(heap.cycles, 1);
+    assert_eq!(heap.get(b).unwrap().value, Some(3));
+    assert!(!heap.free_list.contains(&b));
+
+    // but the next collection gets it
+    heap.collect(&mut stack).unwrap();
+    assert!(heap.free_list.contains(&b));
+    assert_eq!(heap.free(), 2);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 2");
+}
@@ -0,0 +1,173 @@
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+
+use crate::stop_copy::CopyOrder;
+use crate::{
+    init_log, link_heap, link_heap_sized, make_garbage_sized, seed_root, SizeDistribution,
+};
+
+use super::*;
+
+/// the fields of a node that still has its children inline
+fn fields<const N: usize>(children: &Inline<N>) -> Vec<usize> {
+    match children {
+        Inline::Fields { .. } => children.iter().copied().map(usize::from).collect(),
+        Inline::Spilled(_) => panic!("children spilled out of the node"),
+    }
+}
+
+/// 1 -> 3, 1 -> 4 and 3 -> 4, with 2 as garbage in between 1 and 3. Once
+/// collected, each node's fields should still be inline, pointing at wherever
+/// its children ended up
+fn edges_move_with_nodes<T: MemoryManager + Nodes<Children = Inline<2>>>(
+    heap: &mut T,
+) -> [usize; 3] {
+    init_log();
+    let mut stack = Stack::new(1);
+
+    let root = seed_root(&mut stack, heap).unwrap();
+    alloc_value(&mut stack, heap, 2);
+    let a = alloc_value(&mut stack, heap, 3);
+    heap.add_child(root, a).unwrap();
+    let b = alloc_value(&mut stack, heap, 4);
+    heap.add_child(root, b).unwrap();
+    heap.add_child(a, b).unwrap();
+    let dump = stack.dump_all(heap).unwrap();
+
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(heap.free(), 3);
+    assert_eq!(stack.dump_all(heap).unwrap(), dump);
+    let root = stack.roots[0].children[0];
+    let a = *heap.child(root, 0).unwrap();
+    let b = *heap.child(root, 1).unwrap();
+    let [root, a, b] = [root, a, b].map(usize::from);
+    assert_eq!(fields(&heap.get(root.into()).unwrap().children), [a, b]);
+    assert_eq!(fields(&heap.get(a.into()).unwrap().children), [b]);
+    assert!(fields(&heap.get(b.into()).unwrap().children).is_empty());
+    [root, a, b]
+}
+
+#[test]
+fn flat_compaction() {
+    for compaction in [
+        Compaction::Lisp2,
+        Compaction::TwoFinger,
+        Compaction::Threaded,
+        Compaction::Compressor,
+    ] {
+        let mut heap = MarkCompactHeap::<Option<u32>, Inline<2>>::init_compacting(4, compaction);
+        // two finger fills the hole 2 left with the last node
+        let expected = match compaction {
+            Compaction::TwoFinger => [0, 2, 1],
+            _ => [0, 1, 2],
+        };
+        assert_eq!(
+            edges_move_with_nodes(&mut heap),
+            expected,
+            "{:?}",
+            compaction
+        );
+    }
+}
+
+#[test]
+fn flat_copying() {
+    for copy_order in [
+        CopyOrder::Bfs,
+        CopyOrder::Dfs,
+        CopyOrder::Hierarchical { block_size: 2 },
+        CopyOrder::Parallel { threads: 2 },
+    ] {
+        let mut heap = StopAndCopyHeap::<Option<u32>, Inline<2>>::init_ordered(8, copy_order);
+        let moved = edges_move_with_nodes(&mut heap);
+        // the parallel copy doesn't promise any order
+        if !matches!(copy_order, CopyOrder::Parallel { .. }) {
+            assert_eq!(moved, [4, 5, 6], "{:?}", copy_order);
+        }
+    }
+}
+
+#[test]
+fn flat_fields() {
+    init_log();
+    let mut stack = Stack::new(1);
+    let mut heap = MarkCompactHeap::<Option<u32>, Inline<2>>::init_payload(4);
+
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    let a = alloc_value(&mut stack, &mut heap, 2);
+    let b = alloc_value(&mut stack, &mut heap, 3);
+    heap.add_child(root, a).unwrap();
+    heap.add_child(root, b).unwrap();
+    assert_eq!(
+        fields(&heap.get(root).unwrap().children),
+        [a, b].map(usize::from)
+    );
+    // there's only room for two children, so the third spills them all out
+    heap.add_child(root, a).unwrap();
+    assert!(heap.get(root).unwrap().children.spilled());
+    assert_eq!(heap.children(root).collect::<Vec<_>>(), vec![a, b, a]);
+    // and so does allocating a node with too many of them
+    let node = Node {
+        children: vec![a, b, a],
+        ..Default::default()
+    };
+    let node_pointer = heap.alloc(node, &mut stack).unwrap();
+    assert!(heap.get(node_pointer).unwrap().children.spilled());
+
+    // removing a child shifts the rest of the fields down
+    let mut children = Inline::<2>::from(vec![a, b]);
+    assert_eq!(children.remove(0), a);
+    assert_eq!(fields(&children), [usize::from(b)]);
+    children[0] = a;
+    assert_eq!(children.pop(), Some(a));
+    assert_eq!(children.pop(), None);
+    assert!(!children.spilled());
+}
+
+/// links a heap with a mix of sizes, makes some garbage, and collects it,
+/// returning what's left, and how many slots it takes up
+fn mixed_sizes<T: MemoryManager>(heap: &mut T, sizes: SizeDistribution) -> (String, usize) {
+    let mut stack = Stack::new(1);
+    let mut rng = Pcg64::seed_from_u64(1234);
+    let nodes = link_heap_sized(&mut stack, heap, 1000, sizes, &mut rng).unwrap();
+    make_garbage_sized(heap, &nodes, 0.5, &mut rng).unwrap();
+    let dump = stack.dump_all(heap).unwrap();
+
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(stack.dump_all(heap).unwrap(), dump);
+    (dump, heap.free())
+}
+
+/// laying children out inline doesn't change what either collector keeps
+#[test]
+fn flat_mixed_sizes() {
+    init_log();
+    let sizes = SizeDistribution::Bimodal {
+        small: 0,
+        large: 32,
+        ratio: 0.1,
+    };
+    let size = 1000 * (1 + 32);
+    let expected = mixed_sizes(&mut MarkCompactHeap::init(size), sizes);
+    let mut heap = MarkCompactHeap::<Option<u32>, Inline<ARITY>>::init_payload(size);
+    assert_eq!(mixed_sizes(&mut heap, sizes), expected);
+    let mut heap = StopAndCopyHeap::<Option<u32>, Inline<ARITY>>::init_payload(size * 2);
+    assert_eq!(mixed_sizes(&mut heap, sizes), expected);
+}
+
+/// `link_heap` never runs out of fields, even if some nodes spill
+#[test]
+fn flat_link_heap() {
+    init_log();
+    let mut stack = Stack::new(1);
+    let mut rng = Pcg64::seed_from_u64(1234);
+    let mut heap = MarkCompactHeap::<Option<u32>, Inline<ARITY>>::init_payload(1000);
+    link_heap(&mut stack, &mut heap, &mut rng).unwrap();
+    let spilled = heap
+        .committed_memory
+        .iter()
+        .filter(|node| node.children.spilled())
+        .count();
+    // most nodes have their children inline
+    assert!(spilled < 100, "{} spilled", spilled);
+}
@@ -0,0 +1,69 @@
+use crate::{init_log, seed_root};
+
+use super::*;
+
+#[test]
+fn garbage_first_evacuation() {
+    init_log();
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // four regions of four nodes, evacuating one region per collection
+    let mut heap = GarbageFirstHeap::init_with_regions(16, 4, 1);
+
+    // the root keeps all of the first region alive, only 4 out of the second
+    // region, and 8, 9 and 10 out of the third. 2 and 8 point across regions
+    // at 4 too
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    let mut nodes = vec![root];
+    for idx in 1..12 {
+        let node = Node {
+            value: Some(idx),
+            ..Default::default()
+        };
+        let node_pointer = heap.alloc(node, &mut stack).unwrap();
+        if ![5, 6, 7, 11].contains(&idx) {
+            heap.add_child(root, node_pointer).unwrap();
+        }
+        nodes.push(node_pointer);
+    }
+    heap.add_child(nodes[2], nodes[4]).unwrap();
+    heap.add_child(nodes[8], nodes[4]).unwrap();
+    assert!(heap.regions[1].remembered_set.contains(&nodes[2]));
+    assert!(heap.regions[1].remembered_set.contains(&nodes[8]));
+    let dump = stack.dump_all(&heap).unwrap();
+    let used = |heap: &GarbageFirstHeap| {
+        heap.regions
+            .iter()
+            .map(|region| region.used())
+            .collect::<Vec<_>>()
+    };
+
+    // the second region has the most garbage, so it goes first, and 4 gets
+    // copied out into the free region at the end
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(heap.evacuated, 1);
+    assert_eq!(heap.full_collections, 0);
+    assert_eq!(used(&heap), vec![4, 0, 4, 1]);
+    assert_eq!(heap.free_regions, vec![1]);
+    // and everything that pointed at it got fixed up through the remembered
+    // set
+    let four = heap.node_pointer_from_usize(12);
+    assert_eq!(heap.get(four).unwrap().value, Some(4));
+    assert!(heap.get(nodes[2]).unwrap().children.contains(&four));
+    assert!(heap.get(nodes[8]).unwrap().children.contains(&four));
+    assert!(heap.regions[3].remembered_set.contains(&nodes[2]));
+    assert_eq!(stack.dump_all(&heap).unwrap(), dump);
+
+    // then the third region, which gets copied into the lowest free region
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(heap.evacuated, 4);
+    assert_eq!(used(&heap), vec![4, 3, 0, 1]);
+    assert_eq!(stack.dump_all(&heap).unwrap(), dump);
+    assert_eq!(heap.free(), 8);
+
+    // and with nothing left to evacuate, allocation carries on into what's
+    // left of the region it was copying into
+    let node_pointer = heap.alloc(Node::default(), &mut stack).unwrap();
+    assert_eq!(usize::from(node_pointer), 7);
+}
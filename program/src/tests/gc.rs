@@ -0,0 +1,325 @@
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+
+use crate::gc::{Gc, Object, Trace};
+use crate::{init_log, link_heap, make_garbage};
+
+use super::*;
+
+/// a singly linked list
+#[derive(Debug, Clone)]
+struct Cons {
+    head: u32,
+    tail: Option<Gc<Cons>>,
+}
+
+impl Trace for Cons {
+    fn trace(&mut self, visit: &mut dyn FnMut(&mut NodePointer)) {
+        self.tail.trace(visit);
+    }
+}
+
+/// an unbalanced binary search tree
+#[derive(Debug, Clone)]
+struct Tree {
+    key: u32,
+    value: String,
+    left: Option<Gc<Tree>>,
+    right: Option<Gc<Tree>>,
+}
+
+impl Trace for Tree {
+    fn trace(&mut self, visit: &mut dyn FnMut(&mut NodePointer)) {
+        self.left.trace(visit);
+        self.right.trace(visit);
+    }
+}
+
+/// a hash map that chains together the entries in each bucket
+#[derive(Debug, Clone)]
+struct Map {
+    buckets: Vec<Option<Gc<Entry>>>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    key: String,
+    value: u32,
+    next: Option<Gc<Entry>>,
+}
+
+impl Trace for Map {
+    fn trace(&mut self, visit: &mut dyn FnMut(&mut NodePointer)) {
+        self.buckets.trace(visit);
+    }
+}
+
+impl Trace for Entry {
+    fn trace(&mut self, visit: &mut dyn FnMut(&mut NodePointer)) {
+        self.next.trace(visit);
+    }
+}
+
+/// the handle that's been rooted as the first child of the first root
+fn rooted<T>(stack: &Stack) -> Gc<T> {
+    Gc::from(stack.roots[0].children[0])
+}
+
+/// replaces whatever's rooted with `gc`
+fn root<T, H: MemoryManager<Object> + Nodes<Object>>(stack: &mut Stack, heap: &mut H, gc: Gc<T>) {
+    if !stack.roots[0].children.is_empty() {
+        stack.remove_root(heap, 0, 0);
+    }
+    stack.add_root(heap, 0, gc.into());
+}
+
+/// pushes 0 to 99 onto the front of a list, with a bunch of garbage in
+/// between, so that the heap has to collect (and move the list) a few times
+fn list<H: MemoryManager<Object> + Nodes<Object>>(heap: &mut H) {
+    let mut stack = Stack::new(1);
+    let mut tail = None;
+    for head in 0..100 {
+        let cons = Gc::new(Cons { head, tail }, heap, &mut stack).unwrap();
+        root(&mut stack, heap, cons);
+        Gc::new(String::from("garbage"), heap, &mut stack).unwrap();
+        tail = Some(rooted(&stack));
+    }
+
+    let mut list = Some(rooted::<Cons>(&stack));
+    let mut heads = Vec::new();
+    while let Some(cons) = list {
+        let cons = cons.get(heap).unwrap();
+        heads.push(cons.head);
+        list = cons.tail;
+    }
+    assert_eq!(heads, (0..100).rev().collect::<Vec<_>>());
+    // the list's the only thing that's alive
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(heap.free(), 100);
+}
+
+/// inserts `key` into the tree rooted on the stack
+fn insert<H: MemoryManager<Object> + Nodes<Object>>(stack: &mut Stack, heap: &mut H, key: u32) {
+    let leaf = Tree {
+        key,
+        value: key.to_string(),
+        left: None,
+        right: None,
+    };
+    // allocating can move the tree, so we only walk it once the leaf's on the
+    // heap
+    let leaf = Gc::new(leaf, heap, stack).unwrap();
+    let mut tree = rooted::<Tree>(stack);
+    loop {
+        let node = tree.get(heap).unwrap();
+        let next = if key < node.key {
+            node.left
+        } else {
+            node.right
+        };
+        match next {
+            Some(next) => tree = next,
+            None => break,
+        }
+    }
+    tree.update(heap, |node| {
+        if key < node.key {
+            node.left = Some(leaf);
+        } else {
+            node.right = Some(leaf);
+        }
+    })
+    .unwrap();
+}
+
+/// every key in the tree, in order
+fn in_order<H: MemoryManager<Object> + Nodes<Object>>(
+    heap: &mut H,
+    tree: Option<Gc<Tree>>,
+    keys: &mut Vec<u32>,
+) {
+    if let Some(tree) = tree {
+        let node = tree.get(heap).unwrap().clone();
+        in_order(heap, node.left, keys);
+        assert_eq!(node.value, node.key.to_string());
+        keys.push(node.key);
+        in_order(heap, node.right, keys);
+    }
+}
+
+fn tree<H: MemoryManager<Object> + Nodes<Object>>(heap: &mut H) {
+    let mut stack = Stack::new(1);
+    let mut rng = Pcg64::seed_from_u64(1234);
+    let mut keys: Vec<u32> = (0..200).collect();
+    keys.shuffle(&mut rng);
+
+    let first = Tree {
+        key: keys[0],
+        value: keys[0].to_string(),
+        left: None,
+        right: None,
+    };
+    let tree = Gc::new(first, heap, &mut stack).unwrap();
+    root(&mut stack, heap, tree);
+    for key in &keys[1..] {
+        insert(&mut stack, heap, *key);
+        Gc::new(*key, heap, &mut stack).unwrap();
+    }
+
+    let mut sorted = Vec::new();
+    in_order(heap, Some(rooted(&stack)), &mut sorted);
+    assert_eq!(sorted, (0..200).collect::<Vec<_>>());
+    // numbers show up in dumps, but nothing else does
+    assert_eq!(stack.dump_all(heap).unwrap(), "[0] ");
+}
+
+fn bucket(key: &str, buckets: usize) -> usize {
+    key.bytes().map(usize::from).sum::<usize>() % buckets
+}
+
+/// sets `key` to `value`, whether or not it's already in the map
+fn put<H: MemoryManager<Object> + Nodes<Object>>(
+    stack: &mut Stack,
+    heap: &mut H,
+    key: &str,
+    value: u32,
+) {
+    let map = rooted::<Map>(stack);
+    let idx = bucket(key, map.get(heap).unwrap().buckets.len());
+    let mut entries = map.get(heap).unwrap().buckets[idx];
+    while let Some(entry) = entries {
+        if entry.get(heap).unwrap().key == key {
+            entry.update(heap, |entry| entry.value = value).unwrap();
+            return;
+        }
+        entries = entry.get(heap).unwrap().next;
+    }
+
+    // the new entry goes in front of the rest of the bucket
+    let entry = Entry {
+        key: key.to_string(),
+        value,
+        next: map.get(heap).unwrap().buckets[idx],
+    };
+    let entry = Gc::new(entry, heap, stack).unwrap();
+    rooted::<Map>(stack)
+        .update(heap, |map| map.buckets[idx] = Some(entry))
+        .unwrap();
+}
+
+fn get<H: MemoryManager<Object> + Nodes<Object>>(
+    stack: &Stack,
+    heap: &mut H,
+    key: &str,
+) -> Option<u32> {
+    let map = rooted::<Map>(stack);
+    let idx = bucket(key, map.get(heap).unwrap().buckets.len());
+    let mut entries = map.get(heap).unwrap().buckets[idx];
+    while let Some(entry) = entries {
+        let entry = entry.get(heap).unwrap();
+        if entry.key == key {
+            return Some(entry.value);
+        }
+        entries = entry.next;
+    }
+    None
+}
+
+fn map<H: MemoryManager<Object> + Nodes<Object>>(heap: &mut H) {
+    let mut stack = Stack::new(1);
+    let map = Map {
+        buckets: vec![None; 8],
+    };
+    let map = Gc::new(map, heap, &mut stack).unwrap();
+    root(&mut stack, heap, map);
+
+    for i in 0..100 {
+        put(&mut stack, heap, &format!("key {}", i), i);
+        Gc::new(i, heap, &mut stack).unwrap();
+    }
+    // overwriting doesn't allocate anything
+    let free = heap.free();
+    for i in 0..100 {
+        put(&mut stack, heap, &format!("key {}", i % 10), i);
+    }
+    assert_eq!(heap.free(), free);
+    for i in 0..100 {
+        let expected = if i < 10 { 90 + i } else { i };
+        assert_eq!(get(&stack, heap, &format!("key {}", i)), Some(expected));
+    }
+    assert_eq!(get(&stack, heap, "key 100"), None);
+
+    // emptying a bucket leaves its entries as garbage
+    let idx = bucket("key 0", 8);
+    rooted::<Map>(&stack)
+        .update(heap, |map| map.buckets[idx] = None)
+        .unwrap();
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(get(&stack, heap, "key 0"), None);
+    assert_eq!(get(&stack, heap, "key 1"), Some(91));
+    assert_eq!(
+        heap.free(),
+        1 + (0..100)
+            .filter(|i| bucket(&format!("key {}", i), 8) != idx)
+            .count()
+    );
+}
+
+#[test]
+fn gc_list() {
+    init_log();
+    list(&mut MarkCompactHeap::<Object>::init_payload(150));
+    list(&mut StopAndCopyHeap::<Object>::init_payload(300));
+}
+
+#[test]
+fn gc_tree() {
+    init_log();
+    tree(&mut MarkCompactHeap::<Object>::init_payload(250));
+    tree(&mut StopAndCopyHeap::<Object>::init_payload(500));
+}
+
+#[test]
+fn gc_map() {
+    init_log();
+    map(&mut MarkCompactHeap::<Object>::init_payload(150));
+    map(&mut StopAndCopyHeap::<Object>::init_payload(300));
+}
+
+#[test]
+fn gc_wrong_type() {
+    init_log();
+    let mut stack = Stack::new(1);
+    let mut heap = MarkCompactHeap::<Object>::init_payload(10);
+    let number = Gc::new(1_u32, &mut heap, &mut stack).unwrap();
+    assert_eq!(*number.get(&mut heap).unwrap(), 1);
+    assert!(Gc::<String>::from(number.node_pointer)
+        .get(&mut heap)
+        .is_err());
+}
+
+#[test]
+fn gc_workload() {
+    init_log();
+    // the numbers that the workloads allocate are objects too, so the same
+    // graph comes out the other end
+    let mut stack = Stack::new(1);
+    let mut heap = MarkCompactHeap::init(10_000);
+    let mut rng = Pcg64::seed_from_u64(1234);
+    link_heap(&mut stack, &mut heap, &mut rng).unwrap();
+    make_garbage(&mut stack, &mut heap, 0.5, &mut rng).unwrap();
+    heap.collect(&mut stack).unwrap();
+
+    let mut object_stack = Stack::new(1);
+    let mut object_heap = MarkCompactHeap::<Object>::init_payload(10_000);
+    let mut rng = Pcg64::seed_from_u64(1234);
+    link_heap(&mut object_stack, &mut object_heap, &mut rng).unwrap();
+    make_garbage(&mut object_stack, &mut object_heap, 0.5, &mut rng).unwrap();
+    object_heap.collect(&mut object_stack).unwrap();
+
+    assert_eq!(
+        object_stack.dump_all(&object_heap).unwrap(),
+        stack.dump_all(&heap).unwrap()
+    );
+    assert_eq!(object_heap.free(), heap.free());
+}
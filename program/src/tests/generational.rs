@@ -0,0 +1,94 @@
+use crate::{init_log, seed_root};
+
+use super::*;
+
+#[test]
+fn minor_collection() {
+    init_log();
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = GenerationalHeap::init_with_nursery(100, 10);
+
+    // put a root and a child of the root into the old generation
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    let node = Node {
+        value: Some(2),
+        ..Default::default()
+    };
+    let old_node_pointer = heap.alloc(node, &mut stack).unwrap();
+    heap.add_child(root, old_node_pointer).unwrap();
+    heap.minor_collect(&mut stack).unwrap();
+    assert_eq!(heap.boundary, 2);
+
+    // now the old child becomes garbage
+    heap.pop_child(root).unwrap();
+
+    // a young node that's only reachable from the old generation, and a young
+    // node that's garbage
+    let node = Node {
+        value: Some(3),
+        ..Default::default()
+    };
+    let young_node_pointer = heap.alloc(node, &mut stack).unwrap();
+    heap.add_child(root, young_node_pointer).unwrap();
+    // the write barrier should have caught the old root pointing into the
+    // nursery
+    assert_eq!(heap.remembered_set, vec![root]);
+    heap.alloc(Node::default(), &mut stack).unwrap();
+
+    // the remembered set should keep the young node alive, but a minor
+    // collection never looks at the old garbage
+    heap.minor_collect(&mut stack).unwrap();
+    assert_eq!(heap.free(), 3);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 3");
+
+    // which only goes away in a major collection
+    heap.major_collect(&mut stack).unwrap();
+    assert_eq!(heap.free(), 2);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 3");
+    assert_eq!((heap.minor_collections, heap.major_collections), (2, 1));
+}
+
+#[test]
+fn overwritten_old_pointer() {
+    init_log();
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = GenerationalHeap::init_with_nursery(100, 10);
+
+    // an old root pointing to an old child
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    let node = Node {
+        value: Some(2),
+        ..Default::default()
+    };
+    let old_node_pointer = heap.alloc(node, &mut stack).unwrap();
+    heap.add_child(root, old_node_pointer).unwrap();
+    heap.minor_collect(&mut stack).unwrap();
+    assert!(heap.remembered_set.is_empty());
+
+    // overwriting the old child with a young one has to remember the root,
+    // even though the barrier only runs once the store has happened
+    let node = Node {
+        value: Some(3),
+        ..Default::default()
+    };
+    let young_node_pointer = heap.alloc(node, &mut stack).unwrap();
+    assert_eq!(
+        heap.set_child(root, 0, young_node_pointer).unwrap(),
+        old_node_pointer
+    );
+    assert_eq!(heap.remembered_set, vec![root]);
+
+    // so the young node gets promoted, instead of the slot getting reused
+    heap.minor_collect(&mut stack).unwrap();
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 3");
+    heap.major_collect(&mut stack).unwrap();
+    assert_eq!(heap.free(), 2);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 3");
+}
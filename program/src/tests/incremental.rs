@@ -0,0 +1,268 @@
+use crate::{init_log, seed_root};
+
+use super::*;
+
+#[test]
+fn mutation_between_slices() {
+    init_log();
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap, scanning one gray node per allocation once 4
+    // slots are in use
+    const HEAP_SIZE: usize = 100;
+    let mut heap: MarkCompactHeap = MarkCompactHeap::init_incremental(
+        HEAP_SIZE,
+        Incremental {
+            quantum: 1,
+            trigger: 4,
+        },
+    );
+
+    //     1
+    //    / \
+    //   2   3
+    //        \
+    //         4
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    let a = alloc_value(&mut stack, &mut heap, 2);
+    heap.add_child(root, a).unwrap();
+    let b = alloc_value(&mut stack, &mut heap, 3);
+    heap.add_child(root, b).unwrap();
+    let c = alloc_value(&mut stack, &mut heap, 4);
+    heap.add_child(b, c).unwrap();
+
+    // this allocation starts marking and scans the root, and the next one
+    // scans 2, so now 2 is black, 3 is gray, and 4 is white
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    assert!(heap.marking);
+    assert_eq!(heap.gray, [b]);
+
+    // move 4 from under 3 to under 2. If the barrier didn't shade it, nothing
+    // would ever scan it
+    heap.add_child(a, c).unwrap();
+    heap.remove_child(b, 0).unwrap();
+
+    // one allocation scans 3, and the next one scans 4, which finishes marking
+    // and compacts
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    assert!(!heap.marking);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 2, 3, 4");
+    // the three nodes allocated during marking were allocated black, so they
+    // only go away in the next collection, along with the one allocated after
+    assert_eq!(heap.free(), 8);
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(heap.free(), 4);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 2, 3, 4");
+}
+
+/// running out of memory in the middle of marking finishes the mark that's
+/// in progress, instead of starting over
+#[test]
+fn collect_mid_mark() {
+    init_log();
+    let mut stack = Stack::new(1);
+    let mut heap: MarkCompactHeap = MarkCompactHeap::init_incremental(
+        100,
+        Incremental {
+            quantum: 1,
+            trigger: 4,
+        },
+    );
+
+    // the same graph as above, with 2 black, 3 gray and 4 white
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    let a = alloc_value(&mut stack, &mut heap, 2);
+    heap.add_child(root, a).unwrap();
+    let b = alloc_value(&mut stack, &mut heap, 3);
+    heap.add_child(root, b).unwrap();
+    let c = alloc_value(&mut stack, &mut heap, 4);
+    heap.add_child(b, c).unwrap();
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    heap.add_child(a, c).unwrap();
+    heap.remove_child(b, 0).unwrap();
+
+    heap.collect(&mut stack).unwrap();
+    assert!(!heap.marking);
+    assert!(heap.gray.is_empty());
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 2, 3, 4");
+    // the two nodes allocated black made it through, which they wouldn't
+    // have if marking had started over
+    assert_eq!(heap.free(), 6);
+}
+
+#[test]
+fn copying_mid_collection() {
+    init_log();
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap, scanning one copied node per allocation once 13
+    // slots are in use
+    const HEAP_SIZE: usize = 200;
+    let mut heap = StopAndCopyHeap::init_incremental(
+        HEAP_SIZE,
+        Incremental {
+            quantum: 1,
+            trigger: 13,
+        },
+    );
+
+    // 1 -> 2..=11, 100
+    // 2 -> 3 -> ... -> 11 -> 1
+    // 100 -> 200
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    let chain: Vec<NodePointer> = (2..=11)
+        .map(|value| alloc_value(&mut stack, &mut heap, value))
+        .collect();
+    for (i, node_pointer) in chain.iter().enumerate() {
+        heap.add_child(root, *node_pointer).unwrap();
+        heap.add_child(*node_pointer, *chain.get(i + 1).unwrap_or(&root))
+            .unwrap();
+    }
+    let x = alloc_value(&mut stack, &mut heap, 100);
+    let y = alloc_value(&mut stack, &mut heap, 200);
+    heap.add_child(root, x).unwrap();
+    heap.add_child(x, y).unwrap();
+
+    let dump = "[0] 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 100, 200";
+    let expected = |stack: &Stack, heap: &StopAndCopyHeap| {
+        assert_eq!(stack.dump_all(heap).unwrap(), dump);
+        assert_eq!(stack.sum_bfs(heap).unwrap(), 366);
+        assert_eq!(stack.sum_dfs(heap).unwrap(), 366);
+        assert_eq!(stack.count(heap).unwrap(), (13, 23));
+    };
+    expected(&stack, &heap);
+
+    // this allocation flips and scans the root, so everything but 200 has
+    // been copied, and only the root has been scanned
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    assert!(heap.collecting);
+    assert_eq!(heap.scan, heap.to_space + 1);
+    expected(&stack, &heap);
+
+    // move 200 from under 100 to under the root, using the pointers from
+    // before the flip. If the barrier didn't copy it, nothing would ever scan
+    // it
+    heap.pop_child(x).unwrap();
+    heap.add_child(root, y).unwrap();
+    heap.add_child(y, y).unwrap();
+    heap.remove_child(y, 0).unwrap();
+    assert_eq!(stack.dump_all(&heap).unwrap(), dump);
+
+    // the traversals should be right after every slice
+    while heap.collecting {
+        heap.alloc(Node::default(), &mut stack).unwrap();
+        assert_eq!(stack.dump_all(&heap).unwrap(), dump);
+        assert_eq!(stack.sum_bfs(&heap).unwrap(), 366);
+        assert_eq!(stack.sum_dfs(&heap).unwrap(), 366);
+    }
+    assert!(heap.pauses.count > 1);
+    // and once it's finished, only what was allocated while copying is left
+    // as garbage
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(heap.free(), 13);
+    assert_eq!(stack.dump_all(&heap).unwrap(), dump);
+}
+
+#[test]
+fn overwrite_between_slices() {
+    init_log();
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap, scanning one gray node per allocation once 4
+    // slots are in use
+    const HEAP_SIZE: usize = 100;
+    let mut heap: MarkCompactHeap = MarkCompactHeap::init_incremental(
+        HEAP_SIZE,
+        Incremental {
+            quantum: 1,
+            trigger: 4,
+        },
+    );
+
+    //     1
+    //    / \
+    //   2   3
+    //        \
+    //         4
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    let a = alloc_value(&mut stack, &mut heap, 2);
+    heap.add_child(root, a).unwrap();
+    let b = alloc_value(&mut stack, &mut heap, 3);
+    heap.add_child(root, b).unwrap();
+    let c = alloc_value(&mut stack, &mut heap, 4);
+    heap.add_child(b, c).unwrap();
+
+    // now the root and 2 are black, 3 is gray, and 4 is white
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    assert_eq!(heap.gray, [b]);
+
+    // overwrite 2 with 4 in the black root, then cut 4 out from under 3. The
+    // barrier runs after the store, and still has to shade 4
+    assert_eq!(heap.set_child(root, 0, c).unwrap(), a);
+    assert_eq!(heap.gray, [b, c]);
+    heap.pop_child(b).unwrap();
+
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    assert!(!heap.marking);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 4, 3");
+    // 2 was already black when it got overwritten, so it only goes away in
+    // the next collection
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(heap.free(), 3);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 4, 3");
+}
+
+#[test]
+fn pointer_loaded_mid_collection() {
+    init_log();
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap, scanning one copied node per allocation once 4
+    // slots are in use
+    const HEAP_SIZE: usize = 100;
+    let mut heap: StopAndCopyHeap = StopAndCopyHeap::init_incremental(
+        HEAP_SIZE,
+        Incremental {
+            quantum: 1,
+            trigger: 4,
+        },
+    );
+
+    // 1 -> 2 -> 3 -> 4
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    let mut parent = root;
+    for value in 2..=4 {
+        let child = alloc_value(&mut stack, &mut heap, value);
+        heap.add_child(parent, child).unwrap();
+        parent = child;
+    }
+
+    // this allocation flips and scans the root, so 2 has been copied but not
+    // scanned, and still points at 3 in from-space
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    assert!(heap.collecting);
+    let a = *heap.child(stack.roots[0].children[0], 0).unwrap();
+    let b = *heap.child(a, 0).unwrap();
+    assert!(heap.in_from_space(b));
+
+    // once the collection's done, 3's old slot in from-space has been
+    // swapped for whatever was in to-space, but the mutator should still
+    // find 3 (and 4) through the pointer it loaded
+    while heap.collecting {
+        heap.alloc(Node::default(), &mut stack).unwrap();
+    }
+    assert_eq!(heap.value(b), Some(3));
+    assert_eq!(heap.dump(b).unwrap(), "3, 4");
+    heap.add_child(b, a).unwrap();
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 2, 3, 4");
+    assert_eq!(stack.count(&heap).unwrap(), (4, 5));
+}
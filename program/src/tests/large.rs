@@ -0,0 +1,226 @@
+use crate::mark_compact::Incremental;
+use crate::stop_copy::CopyOrder;
+use crate::{init_log, seed_root};
+
+use super::*;
+
+fn alloc_sized<T: MemoryManager>(
+    stack: &mut Stack,
+    heap: &mut T,
+    value: u32,
+    size: usize,
+) -> NodePointer {
+    let node = Node {
+        value: Some(value),
+        ..Default::default()
+    };
+    heap.alloc_sized(node, size, stack).unwrap()
+}
+
+/// 1 -> 3 -> 4, where 3 has enough payload to go in the large object space,
+/// along with 5, which is garbage. 2 is garbage in between 1 and 4. Every
+/// heap here has a threshold of 4 slots, and 20 slots of large object space
+/// starting at `start`. Heaps that move nodes move 1 and 4 to the start of
+/// `to_space`, and the rest leave them where they are
+fn large_objects_stay_put<T: MemoryManager>(mut heap: T, start: usize, to_space: Option<usize>) {
+    init_log();
+    let mut stack = Stack::new(1);
+
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    alloc_value(&mut stack, &mut heap, 2);
+    let big = alloc_sized(&mut stack, &mut heap, 3, 5);
+    heap.add_child(root, big).unwrap();
+    let small = alloc_value(&mut stack, &mut heap, 4);
+    heap.add_child(big, small).unwrap();
+    let moved = to_space.map_or(usize::from(small), |to_space| to_space + 1);
+    let garbage = alloc_sized(&mut stack, &mut heap, 5, 4);
+    assert_eq!([big, garbage].map(usize::from), [start, start + 6]);
+    // 3 nodes in the rest of the heap, and 6 and 5 slots of large objects
+    assert_eq!(heap.free(), 14);
+
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(heap.free(), 8);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 3, 4");
+    // the big node didn't go anywhere, but what it points to did
+    assert_eq!(*heap.child(stack.roots[0].children[0], 0).unwrap(), big);
+    assert_eq!(usize::from(*heap.child(big, 0).unwrap()), moved);
+
+    // the garbage got swept, along with the free chunk after it, which is
+    // exactly big enough for this
+    let node_pointer = alloc_sized(&mut stack, &mut heap, 6, 13);
+    assert_eq!(usize::from(node_pointer), start + 6);
+    let small = *heap.child(big, 0).unwrap();
+    heap.add_child(small, node_pointer).unwrap();
+    assert!(heap.alloc_sized(Node::default(), 13, &mut stack).is_err());
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 3, 4, 6");
+}
+
+#[test]
+fn large_compaction() {
+    for compaction in [
+        Compaction::Lisp2,
+        Compaction::TwoFinger,
+        Compaction::Threaded,
+        Compaction::Compressor,
+    ] {
+        let heap: MarkCompactHeap = MarkCompactHeap {
+            compaction,
+            ..MarkCompactHeap::init_large(5, 4, 20)
+        };
+        large_objects_stay_put(heap, 5, Some(0));
+    }
+}
+
+#[test]
+fn large_marking() {
+    for mark_order in [
+        MarkOrder::Dfs,
+        MarkOrder::PointerReversal,
+        MarkOrder::Bounded { capacity: 1 },
+        MarkOrder::Parallel { threads: 4 },
+    ] {
+        let heap: MarkCompactHeap = MarkCompactHeap {
+            mark_order,
+            ..MarkCompactHeap::init_large(5, 4, 20)
+        };
+        large_objects_stay_put(heap, 5, Some(0));
+    }
+}
+
+#[test]
+fn large_copying() {
+    for copy_order in [
+        CopyOrder::Bfs,
+        CopyOrder::Dfs,
+        CopyOrder::Hierarchical { block_size: 2 },
+        CopyOrder::Parallel { threads: 4 },
+    ] {
+        let heap: StopAndCopyHeap = StopAndCopyHeap {
+            copy_order,
+            ..StopAndCopyHeap::init_large(10, 4, 20)
+        };
+        large_objects_stay_put(heap, 10, Some(5));
+    }
+}
+
+#[test]
+fn large_mark_sweep() {
+    large_objects_stay_put(MarkSweepHeap::init_large(5, 4, 20), 5, None);
+    let heap = ConcurrentMarkSweepHeap {
+        // marking concurrently would sweep 2 before the fixture gets to
+        trigger: 100,
+        ..ConcurrentMarkSweepHeap::init_large(5, 4, 20)
+    };
+    large_objects_stay_put(heap, 5, None);
+    let heap = TreadmillHeap {
+        incremental: Incremental {
+            quantum: 1,
+            trigger: 100,
+        },
+        ..TreadmillHeap::init_large(5, 4, 20)
+    };
+    large_objects_stay_put(heap, 5, None);
+}
+
+#[test]
+fn large_regions() {
+    // the only block isn't fragmented, so nothing gets evacuated
+    large_objects_stay_put(MarkRegionHeap::init_large(5, 4, 20), 5, None);
+    // and the only region can't be evacuated, so it gets compacted
+    large_objects_stay_put(GarbageFirstHeap::init_large(5, 4, 20), 5, Some(0));
+}
+
+#[test]
+fn large_generational() {
+    let heap = GenerationalHeap {
+        // the whole thing fits in the nursery, so only the major collection
+        // runs
+        nursery_size: 5,
+        ..GenerationalHeap::init_large(5, 4, 20)
+    };
+    large_objects_stay_put(heap, 5, Some(0));
+}
+
+#[test]
+fn large_adaptive() {
+    large_objects_stay_put(AdaptiveHeap::init_large(5, 4, 20), 5, Some(0));
+}
+
+/// a large object in the old generation that gets a nursery node as a child
+/// is remembered, so a minor collection keeps the child alive and updates
+/// the large object to wherever it got promoted to
+#[test]
+fn large_objects_remembered() {
+    init_log();
+    let mut stack = Stack::new(1);
+    let mut heap = GenerationalHeap::init_large(8, 4, 20);
+    heap.nursery_size = 3;
+
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    let big = alloc_sized(&mut stack, &mut heap, 2, 5);
+    heap.add_child(root, big).unwrap();
+    // fills the nursery up, so that 1 gets promoted
+    alloc_value(&mut stack, &mut heap, 3);
+    alloc_value(&mut stack, &mut heap, 4);
+    let young = alloc_value(&mut stack, &mut heap, 5);
+    assert_eq!(heap.minor_collections, 1);
+    heap.add_child(big, young).unwrap();
+    let node = Node {
+        value: Some(6),
+        children: vec![young],
+        ..Default::default()
+    };
+    let other = heap.alloc_sized(node, 5, &mut stack).unwrap();
+    heap.add_child(big, other).unwrap();
+    assert_eq!(heap.remembered_set, vec![big, other]);
+
+    heap.minor_collect(&mut stack).unwrap();
+    assert_eq!(heap.major_collections, 0);
+    // 5 got promoted right after 1
+    assert_eq!(usize::from(*heap.child(big, 0).unwrap()), 1);
+    assert_eq!(usize::from(*heap.child(other, 0).unwrap()), 1);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 2, 5, 6");
+    assert_eq!(heap.free(), 2 + 6 + 6);
+}
+
+/// 1 -> 2 -> 3, where 3 is a large object. Once 1 has been scanned, 3 gets
+/// moved over to 1 before 2 is scanned, so the only thing pointing at it is
+/// a node that's never going to be scanned again
+#[test]
+fn large_object_moved_mid_copy() {
+    init_log();
+    let mut stack = Stack::new(1);
+    let incremental = Incremental {
+        quantum: 1,
+        trigger: 3,
+    };
+    let mut heap: StopAndCopyHeap = StopAndCopyHeap {
+        incremental: Some(incremental),
+        ..StopAndCopyHeap::init_large(20, 4, 20)
+    };
+
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    let middle = alloc_value(&mut stack, &mut heap, 2);
+    heap.add_child(root, middle).unwrap();
+    let big = alloc_sized(&mut stack, &mut heap, 3, 5);
+    heap.add_child(middle, big).unwrap();
+
+    // this flips, and scans 1, which copies 2
+    alloc_value(&mut stack, &mut heap, 4);
+    assert!(heap.collecting);
+    let root = stack.roots[0].children[0];
+    heap.add_child(root, big).unwrap();
+    let middle = *heap.child(root, 0).unwrap();
+    heap.pop_child(middle).unwrap();
+
+    // scans 2, and then 3, which finishes the copy
+    alloc_value(&mut stack, &mut heap, 5);
+    alloc_value(&mut stack, &mut heap, 6);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 2, 3");
+    assert!(heap
+        .large
+        .as_ref()
+        .unwrap()
+        .objects
+        .contains_key(&usize::from(big)));
+}
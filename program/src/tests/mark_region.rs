@@ -0,0 +1,62 @@
+use crate::{init_log, seed_root};
+
+use super::*;
+
+#[test]
+fn lines_and_holes() {
+    init_log();
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // four blocks of four lines of four nodes
+    let mut heap = MarkRegionHeap::init_with_lines(64, 4, 16);
+
+    // the root keeps 9, 17, 25 and all of 32 to 47 alive, which leaves the
+    // first two blocks with two holes each, and the last block empty
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    for idx in 1..64 {
+        let node = Node {
+            value: Some(idx),
+            ..Default::default()
+        };
+        let node_pointer = heap.alloc(node, &mut stack).unwrap();
+        if [9, 17, 25].contains(&idx) || (32..48).contains(&idx) {
+            heap.add_child(root, node_pointer).unwrap();
+        }
+    }
+    let dump = stack.dump_all(&heap).unwrap();
+
+    // nothing's fragmented yet, so nothing moves
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(heap.free(), 20);
+    assert_eq!(heap.evacuated, 0);
+    assert_eq!(heap.holes, vec![2, 2, 0, 1]);
+    let used = |heap: &MarkRegionHeap| {
+        heap.lines
+            .iter()
+            .map(|used| if *used { '#' } else { '.' })
+            .collect::<String>()
+    };
+    assert_eq!(used(&heap), "#.#.#.#.####....");
+    assert_eq!(stack.dump_all(&heap).unwrap(), dump);
+
+    // the next collection evacuates the first two blocks into the last one,
+    // breadth-first
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(heap.free(), 20);
+    assert_eq!(heap.evacuated, 4);
+    assert_eq!(used(&heap), "........#####...");
+    assert_eq!(usize::from(stack.roots[0].children[0]), 48);
+    assert_eq!(stack.dump_all(&heap).unwrap(), dump);
+
+    // and allocation bump allocates through the holes, lowest first
+    let node_pointer = heap.alloc(Node::default(), &mut stack).unwrap();
+    assert_eq!(usize::from(node_pointer), 0);
+    for _ in 1..32 {
+        heap.alloc(Node::default(), &mut stack).unwrap();
+    }
+    // the first two blocks are full, so the next node goes after what got
+    // evacuated
+    let node_pointer = heap.alloc(Node::default(), &mut stack).unwrap();
+    assert_eq!(usize::from(node_pointer), 52);
+}
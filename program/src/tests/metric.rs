@@ -54,6 +54,54 @@ fn bfs_dfs() -> Result<()> {
         // println!("summation of bfs is equal to summation of dfs");
         assert_eq!(res.unwrap(), res_2);
     }
+    {
+        const STACK_SIZE: usize = 1;
+        let heap_size: usize = 1_000_000;
+        // initializing the stack
+        let mut stack = Stack::new(STACK_SIZE);
+        // initializing the heap
+        let mut heap = MarkSweepHeap::init(heap_size);
+
+        let mut rng = Pcg64::seed_from_u64(1234);
+        // now initialize the heap one way
+        link_heap(&mut stack, &mut heap, &mut rng).unwrap();
+        make_garbage(&mut stack, &mut heap, 0.2, &mut rng).unwrap();
+        let res_3 = stack.sum_bfs(&heap).unwrap();
+        assert_eq!(res_3, stack.sum_dfs(&heap).unwrap());
+        assert_eq!(res.unwrap(), res_3);
+    }
+    {
+        const STACK_SIZE: usize = 1;
+        let heap_size: usize = 1_000_000;
+        // initializing the stack
+        let mut stack = Stack::new(STACK_SIZE);
+        // initializing the heap
+        let mut heap = GenerationalHeap::init(heap_size);
+
+        let mut rng = Pcg64::seed_from_u64(1234);
+        // now initialize the heap one way
+        link_heap(&mut stack, &mut heap, &mut rng).unwrap();
+        make_garbage(&mut stack, &mut heap, 0.2, &mut rng).unwrap();
+        let res_4 = stack.sum_bfs(&heap).unwrap();
+        assert_eq!(res_4, stack.sum_dfs(&heap).unwrap());
+        assert_eq!(res.unwrap(), res_4);
+    }
+    {
+        const STACK_SIZE: usize = 1;
+        let heap_size: usize = 1_000_000;
+        // initializing the stack
+        let mut stack = Stack::new(STACK_SIZE);
+        // initializing the heap
+        let mut heap = MarkRegionHeap::init(heap_size);
+
+        let mut rng = Pcg64::seed_from_u64(1234);
+        // now initialize the heap one way
+        link_heap(&mut stack, &mut heap, &mut rng).unwrap();
+        make_garbage(&mut stack, &mut heap, 0.2, &mut rng).unwrap();
+        let res_5 = stack.sum_bfs(&heap).unwrap();
+        assert_eq!(res_5, stack.sum_dfs(&heap).unwrap());
+        assert_eq!(res.unwrap(), res_5);
+    }
     Ok(())
 }
 
@@ -106,3 +154,125 @@ fn metrics() -> Result<()> {
     }
     Ok(())
 }
+
+/// compares the longest pause of the atomic mark-compact collector against the
+/// incremental one, on the same workload
+#[test]
+fn pauses() -> Result<()> {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = 100_000;
+
+    let atomic = MarkCompactHeap::init(heap_size);
+    let incremental = MarkCompactHeap::init_incremental(
+        heap_size,
+        Incremental {
+            quantum: 32,
+            trigger: heap_size / 2,
+        },
+    );
+
+    let mut sums = Vec::new();
+    for mut heap in [atomic, incremental] {
+        // initializing the stack
+        let mut stack = Stack::new(STACK_SIZE);
+        let mut rng = Pcg64::seed_from_u64(1234);
+        link_heap(&mut stack, &mut heap, &mut rng).unwrap();
+        make_garbage(&mut stack, &mut heap, 0.2, &mut rng).unwrap();
+        // churn through the heap a few times
+        for _ in 0..heap_size * 3 {
+            heap.alloc(Node::default(), &mut stack).unwrap();
+        }
+        println!(
+            "incremental: {}, max pause: {:?}, over {} pauses",
+            heap.incremental.is_some(),
+            heap.pauses.max,
+            heap.pauses.count
+        );
+        sums.push(stack.sum_bfs(&heap).unwrap());
+    }
+    // both should have kept exactly the same nodes alive
+    assert_eq!(sums[0], sums[1]);
+
+    // and the same goes for copying. Copying only starts once the heap is
+    // full, since the tree that `link_heap` builds holds onto pointers while
+    // it's allocating
+    let atomic = StopAndCopyHeap::init(heap_size * 2);
+    let incremental = StopAndCopyHeap::init_incremental(
+        heap_size * 2,
+        Incremental {
+            quantum: 32,
+            trigger: heap_size,
+        },
+    );
+    for mut heap in [atomic, incremental] {
+        let mut stack = Stack::new(STACK_SIZE);
+        let mut rng = Pcg64::seed_from_u64(1234);
+        link_heap(&mut stack, &mut heap, &mut rng).unwrap();
+        make_garbage(&mut stack, &mut heap, 0.2, &mut rng).unwrap();
+        for _ in 0..heap_size * 3 {
+            heap.alloc(Node::default(), &mut stack).unwrap();
+        }
+        println!(
+            "incremental copying: {}, max pause: {:?}, over {} pauses",
+            heap.incremental.is_some(),
+            heap.pauses.max,
+            heap.pauses.count
+        );
+        sums.push(stack.sum_bfs(&heap).unwrap());
+    }
+    assert_eq!(sums[0], sums[2]);
+    assert_eq!(sums[0], sums[3]);
+    Ok(())
+}
+
+/// how long garbage-first pauses for depending on how many regions it's
+/// allowed to evacuate at once, against full mark-compact collections
+#[test]
+fn evacuation_budget() -> Result<()> {
+    const STACK_SIZE: usize = 1;
+    let heap_size: usize = 100_000;
+
+    let mut sums = Vec::new();
+    let mut run = |heap: &mut dyn FnMut(&mut Stack, &mut Pcg64) -> u64| {
+        let mut stack = Stack::new(STACK_SIZE);
+        let mut rng = Pcg64::seed_from_u64(1234);
+        sums.push(heap(&mut stack, &mut rng));
+    };
+
+    run(&mut |stack, rng| {
+        let mut heap = MarkCompactHeap::init(heap_size);
+        link_heap(stack, &mut heap, rng).unwrap();
+        make_garbage(stack, &mut heap, 0.2, rng).unwrap();
+        for _ in 0..heap_size * 3 {
+            heap.alloc(Node::default(), stack).unwrap();
+        }
+        println!(
+            "mark-compact, max pause: {:?}, total: {:?}, over {} pauses",
+            heap.pauses.max, heap.pauses.total, heap.pauses.count
+        );
+        stack.sum_bfs(&heap).unwrap()
+    });
+    for budget in [1, 4, 16, usize::MAX] {
+        run(&mut |stack, rng| {
+            let mut heap = GarbageFirstHeap::init_with_budget(heap_size, budget);
+            link_heap(stack, &mut heap, rng).unwrap();
+            make_garbage(stack, &mut heap, 0.2, rng).unwrap();
+            for _ in 0..heap_size * 3 {
+                heap.alloc(Node::default(), stack).unwrap();
+            }
+            println!(
+                "garbage-first, budget: {} regions, max pause: {:?}, total: {:?}, max evacuation: {:?}, over {} pauses, {} of them full",
+                budget,
+                heap.pauses.max,
+                heap.pauses.total,
+                heap.evacuation_pauses.max,
+                heap.pauses.count,
+                heap.full_collections
+            );
+            stack.sum_bfs(&heap).unwrap()
+        });
+    }
+    // every heap should have kept exactly the same nodes alive
+    assert!(sums.iter().all(|sum| *sum == sums[0]));
+    Ok(())
+}
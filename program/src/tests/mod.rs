@@ -1,9 +1,45 @@
+use crate::adaptive::*;
+use crate::concurrent::ConcurrentMarkSweepHeap;
+use crate::flat::*;
+use crate::garbage_first::GarbageFirstHeap;
+use crate::generational::GenerationalHeap;
 use crate::mark_compact::*;
+use crate::mark_region::MarkRegionHeap;
+use crate::mark_sweep::MarkSweepHeap;
+use crate::ref_count::RefCountHeap;
 use crate::shared::*;
 use crate::stop_copy::StopAndCopyHeap;
+use crate::treadmill::TreadmillHeap;
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
 mod actual;
+mod adaptive;
+mod bounded;
 mod collection;
+mod compaction;
+mod concurrent;
+mod flat;
+mod garbage_first;
+mod gc;
+mod generational;
+mod incremental;
+mod large;
+mod mark_region;
 mod metric;
+mod order;
+mod parallel;
+mod payload;
+mod pointer_reversal;
+mod ref_count;
 mod sanity;
+mod sized;
+mod treadmill;
+
+/// allocates a node that holds onto `value`, with no children
+fn alloc_value<T: MemoryManager>(stack: &mut Stack, heap: &mut T, value: u32) -> NodePointer {
+    let node = Node {
+        value: Some(value),
+        ..Default::default()
+    };
+    heap.alloc(node, stack).unwrap()
+}
@@ -0,0 +1,121 @@
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+
+use crate::mark_compact::MarkOrder;
+use crate::stop_copy::CopyOrder;
+use crate::{init_log, link_heap, make_garbage, seed_root};
+
+use super::*;
+
+/// the values of the nodes in to-space after a collection, in address order
+fn layout(copy_order: CopyOrder) -> Vec<u32> {
+    init_log();
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    let mut heap: StopAndCopyHeap = StopAndCopyHeap::init_ordered(20, copy_order);
+
+    //     1
+    //    / \
+    //   2   3
+    //   |   |
+    //   4   6
+    //   |
+    //   5
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    let two = alloc_value(&mut stack, &mut heap, 2);
+    let three = alloc_value(&mut stack, &mut heap, 3);
+    let four = alloc_value(&mut stack, &mut heap, 4);
+    let five = alloc_value(&mut stack, &mut heap, 5);
+    let six = alloc_value(&mut stack, &mut heap, 6);
+    heap.add_child(root, two).unwrap();
+    heap.add_child(root, three).unwrap();
+    heap.add_child(two, four).unwrap();
+    heap.add_child(four, five).unwrap();
+    heap.add_child(three, six).unwrap();
+
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(heap.free(), 6);
+    (0..heap.free())
+        .map(|i| {
+            heap.get(heap.node_pointer_from_usize(i))
+                .unwrap()
+                .value
+                .unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn copy_order_layout() {
+    assert_eq!(layout(CopyOrder::Bfs), vec![1, 2, 3, 4, 6, 5]);
+    assert_eq!(layout(CopyOrder::Dfs), vec![1, 2, 3, 4, 5, 6]);
+    // 3 and 6 share a block, so 6 gets copied before going back to the first
+    // block for 2's children
+    assert_eq!(
+        layout(CopyOrder::Hierarchical { block_size: 2 }),
+        vec![1, 2, 3, 6, 4, 5]
+    );
+}
+
+#[test]
+fn copy_order_same_graph() {
+    let heap_size: usize = 200_000;
+
+    let mut sums = Vec::new();
+    for copy_order in [
+        CopyOrder::Bfs,
+        CopyOrder::Dfs,
+        CopyOrder::Hierarchical { block_size: 64 },
+    ] {
+        const STACK_SIZE: usize = 1;
+        let mut stack = Stack::new(STACK_SIZE);
+        let mut heap: StopAndCopyHeap = StopAndCopyHeap::init_ordered(heap_size, copy_order);
+        let mut rng = Pcg64::seed_from_u64(1234);
+        link_heap(&mut stack, &mut heap, &mut rng).unwrap();
+        make_garbage(&mut stack, &mut heap, 0.5, &mut rng).unwrap();
+        heap.collect(&mut stack).unwrap();
+        // collecting again flips back to the first space
+        heap.collect(&mut stack).unwrap();
+        sums.push((
+            heap.free(),
+            stack.sum_bfs(&heap).unwrap(),
+            stack.sum_dfs(&heap).unwrap(),
+            stack.count(&heap).unwrap(),
+        ));
+    }
+    // the order only changes where things end up, not what survives
+    assert!(sums.windows(2).all(|w| w[0] == w[1]));
+}
+
+#[test]
+fn mark_order_same_graph() {
+    let heap_size: usize = 100_000;
+
+    let mut results = Vec::new();
+    for mark_order in [MarkOrder::Bfs, MarkOrder::Dfs] {
+        const STACK_SIZE: usize = 1;
+        let mut stack = Stack::new(STACK_SIZE);
+        let mut heap: MarkCompactHeap = MarkCompactHeap::init_ordered(heap_size, mark_order);
+        let mut rng = Pcg64::seed_from_u64(1234);
+        link_heap(&mut stack, &mut heap, &mut rng).unwrap();
+        make_garbage(&mut stack, &mut heap, 0.5, &mut rng).unwrap();
+        heap.collect(&mut stack).unwrap();
+        heap.collect(&mut stack).unwrap();
+        // the mark bits should all be cleared again by compaction, and nothing
+        // should be left with a forwarding address
+        assert!(heap.marks.words.iter().all(|word| *word == 0));
+        assert!(heap.committed_memory[..heap.free()]
+            .iter()
+            .all(|node| node.forwarding_address.is_none()));
+        assert_eq!(heap.mark_phases.count, 2);
+        results.push((
+            heap.free(),
+            stack.sum_bfs(&heap).unwrap(),
+            stack.count(&heap).unwrap(),
+        ));
+    }
+    // mark-compact slides nodes in address order, so the traversal order
+    // doesn't even change the layout
+    assert_eq!(results[0], results[1]);
+}
@@ -0,0 +1,53 @@
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+
+use crate::parallel::LAB_SIZE;
+use crate::{link_heap, make_garbage};
+
+use super::*;
+
+/// links up a heap, makes some garbage, collects it twice (so that copying
+/// collectors flip back and forth), and returns the sum and the number of
+/// nodes and edges of whatever's left
+fn collected<T: MemoryManager + Clone>(heap: &mut T) -> (u64, (u64, u64)) {
+    const STACK_SIZE: usize = 1;
+    // initializing the stack
+    let mut stack = Stack::new(STACK_SIZE);
+    let mut rng = Pcg64::seed_from_u64(1234);
+    link_heap(&mut stack, heap, &mut rng).unwrap();
+    make_garbage(&mut stack, heap, 0.5, &mut rng).unwrap();
+    heap.collect(&mut stack).unwrap();
+    heap.collect(&mut stack).unwrap();
+    let sum = stack.sum_bfs(heap).unwrap();
+    assert_eq!(sum, stack.sum_dfs(heap).unwrap());
+    (sum, stack.count(heap).unwrap())
+}
+
+#[test]
+fn parallel_mark() {
+    let heap_size: usize = 100_000;
+
+    let mut serial_heap = MarkCompactHeap::init(heap_size);
+    let serial = collected(&mut serial_heap);
+    for threads in [2, 4, 8] {
+        let mut heap: MarkCompactHeap = MarkCompactHeap::init_parallel(heap_size, threads);
+        assert_eq!(serial, collected(&mut heap));
+        assert_eq!(serial_heap.free(), heap.free());
+    }
+}
+
+#[test]
+fn parallel_copy() {
+    let heap_size: usize = 200_000;
+
+    let mut serial_heap = StopAndCopyHeap::init(heap_size);
+    let serial = collected(&mut serial_heap);
+    for threads in [2, 4, 8] {
+        let mut heap: StopAndCopyHeap = StopAndCopyHeap::init_parallel(heap_size, threads);
+        assert_eq!(serial, collected(&mut heap));
+        // the only difference should be the holes left over in each thread's
+        // last LAB
+        assert!(heap.free() >= serial_heap.free());
+        assert!(heap.free() <= serial_heap.free() + threads * LAB_SIZE);
+    }
+}
@@ -0,0 +1,112 @@
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+
+use crate::stop_copy::CopyOrder;
+use crate::{init_log, link_heap, make_garbage, seed_root};
+
+use super::*;
+
+/// links up a heap, makes some garbage, collects it twice (so that copying
+/// collectors flip back and forth), and returns a dump and the sum of
+/// whatever's left
+fn collected<P: Payload, T: MemoryManager<P> + Clone>(heap: &mut T) -> (String, u64) {
+    let mut stack = Stack::new(1);
+    let mut rng = Pcg64::seed_from_u64(1234);
+    link_heap(&mut stack, heap, &mut rng).unwrap();
+    make_garbage(&mut stack, heap, 0.5, &mut rng).unwrap();
+    heap.collect(&mut stack).unwrap();
+    heap.collect(&mut stack).unwrap();
+    let sum = stack.sum_bfs(heap).unwrap();
+    assert_eq!(sum, stack.sum_dfs(heap).unwrap());
+    (stack.dump_all(heap).unwrap(), sum)
+}
+
+#[test]
+fn padded_nodes_are_bigger() {
+    assert_eq!(
+        std::mem::size_of::<Node<Padded<64>>>(),
+        std::mem::size_of::<Node>() + 64
+    );
+    let node: Node<Padded<64>> = Node {
+        value: Some(3).into(),
+        ..Default::default()
+    };
+    assert_eq!(node.value.value(), Some(3));
+}
+
+#[test]
+fn payload_mark_compact() {
+    init_log();
+    let heap_size = 10_000;
+
+    let expected = collected(&mut MarkCompactHeap::init(heap_size));
+    // the payload shouldn't change the graph, only how much gets moved around
+    assert_eq!(
+        collected(&mut MarkCompactHeap::<Padded<0>>::init_payload(heap_size)),
+        expected
+    );
+    assert_eq!(
+        collected(&mut MarkCompactHeap::<Padded<64>>::init_payload(heap_size)),
+        expected
+    );
+}
+
+#[test]
+fn payload_stop_copy() {
+    init_log();
+    let heap_size = 20_000;
+
+    let expected = collected(&mut StopAndCopyHeap::init(heap_size));
+    assert_eq!(
+        collected(&mut StopAndCopyHeap::<Padded<0>>::init_payload(heap_size)),
+        expected
+    );
+    assert_eq!(
+        collected(&mut StopAndCopyHeap::<Padded<64>>::init_payload(heap_size)),
+        expected
+    );
+}
+
+#[test]
+fn payload_survives_collection() {
+    init_log();
+    let mut stack = Stack::new(1);
+    let mut heap = MarkCompactHeap::<Padded<16>>::init_payload(3);
+
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    let node = Node {
+        value: Padded {
+            value: Some(2),
+            padding: [7; 16],
+        },
+        ..Default::default()
+    };
+    let node_pointer = heap.alloc(node, &mut stack).unwrap();
+    heap.add_child(root, node_pointer).unwrap();
+
+    heap.collect(&mut stack).unwrap();
+    // the garbage in between got slid over, padding and all
+    let node_pointer = *heap.child(stack.roots[0].children[0], 0).unwrap();
+    assert_eq!(usize::from(node_pointer), 1);
+    assert_eq!(heap.get(node_pointer).unwrap().value.padding, [7; 16]);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 2");
+}
+
+#[test]
+fn payload_with_other_modes() {
+    init_log();
+    let heap_size = 10_000;
+
+    let compaction = Compaction::TwoFinger;
+    let mut heap: MarkCompactHeap = MarkCompactHeap::init_compacting(heap_size, compaction);
+    let expected = collected(&mut heap);
+    let mut heap = MarkCompactHeap::<Padded<64>>::init_compacting(heap_size, compaction);
+    assert_eq!(collected(&mut heap), expected);
+
+    let copy_order = CopyOrder::Dfs;
+    let mut heap: StopAndCopyHeap = StopAndCopyHeap::init_ordered(heap_size * 2, copy_order);
+    let expected = collected(&mut heap);
+    let mut heap = StopAndCopyHeap::<Padded<64>>::init_ordered(heap_size * 2, copy_order);
+    assert_eq!(collected(&mut heap), expected);
+}
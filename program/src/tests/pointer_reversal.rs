@@ -0,0 +1,87 @@
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+
+use crate::{init_log, link_heap, make_garbage, seed_root};
+
+use super::*;
+
+/// the children of every node in use, in address order
+fn edges(heap: &MarkCompactHeap) -> Vec<Vec<NodePointer>> {
+    heap.committed_memory[..heap.free()]
+        .iter()
+        .map(|node| node.children.clone())
+        .collect()
+}
+
+#[test]
+fn graph_restored() {
+    init_log();
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    let mut heap = MarkCompactHeap::init_ordered(200, MarkOrder::PointerReversal);
+
+    // 1 -> 2
+    // 2 -> 2, 3     (a node that points at itself)
+    // 3 -> 1, 4     (a cycle back up to the root)
+    // 4 -> 5..=104, 2, 4, 5     (lots of children, some of them shared)
+    // 55 -> 5
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    let two = alloc_value(&mut stack, &mut heap, 2);
+    let three = alloc_value(&mut stack, &mut heap, 3);
+    let four = alloc_value(&mut stack, &mut heap, 4);
+    heap.add_child(root, two).unwrap();
+    heap.add_child(two, two).unwrap();
+    heap.add_child(two, three).unwrap();
+    heap.add_child(three, root).unwrap();
+    heap.add_child(three, four).unwrap();
+    let mut wide = Vec::new();
+    for value in 5..=104 {
+        wide.push(alloc_value(&mut stack, &mut heap, value));
+    }
+    for child in wide.iter().chain(&[two, four, wide[0]]) {
+        heap.add_child(four, *child).unwrap();
+    }
+    heap.add_child(wide[50], wide[0]).unwrap();
+
+    let before = edges(&heap);
+    let dump = stack.dump_all(&heap).unwrap();
+
+    // everything is live, so compaction doesn't move anything and every edge
+    // should be exactly where it was
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(heap.free(), 104);
+    assert_eq!(edges(&heap), before);
+    assert_eq!(stack.dump_all(&heap).unwrap(), dump);
+    // and the forwarding addresses that got borrowed should be cleared out
+    assert!(heap.committed_memory[..heap.free()]
+        .iter()
+        .all(|node| node.forwarding_address.is_none()));
+
+    // dropping the edge into the wide node makes it, and everything only it
+    // points to, garbage
+    heap.remove_child(three, 1).unwrap();
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(heap.free(), 3);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 2, 3");
+}
+
+#[test]
+fn same_as_breadth_first() {
+    let heap_size: usize = 100_000;
+
+    let mut results = Vec::new();
+    for mark_order in [MarkOrder::Bfs, MarkOrder::PointerReversal] {
+        const STACK_SIZE: usize = 1;
+        let mut stack = Stack::new(STACK_SIZE);
+        let mut heap = MarkCompactHeap::init_ordered(heap_size, mark_order);
+        let mut rng = Pcg64::seed_from_u64(1234);
+        link_heap(&mut stack, &mut heap, &mut rng).unwrap();
+        make_garbage(&mut stack, &mut heap, 0.5, &mut rng).unwrap();
+        heap.collect(&mut stack).unwrap();
+        results.push((heap.free(), edges(&heap), stack.sum_dfs(&heap).unwrap()));
+    }
+    // compaction is in address order, so the same marks mean the exact same
+    // heap
+    assert_eq!(results[0], results[1]);
+}
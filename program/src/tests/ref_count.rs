@@ -0,0 +1,66 @@
+use crate::{init_log, seed_root};
+
+use super::*;
+
+#[test]
+fn freed_when_count_hits_zero() {
+    init_log();
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = RefCountHeap::init(10);
+
+    // 1 -> 2 -> 3
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    let a = alloc_value(&mut stack, &mut heap, 2);
+    heap.add_child(root, a).unwrap();
+    let b = alloc_value(&mut stack, &mut heap, 3);
+    heap.add_child(a, b).unwrap();
+    assert_eq!(heap.free(), 3);
+
+    // both 2 and 3 go away without ever collecting
+    heap.pop_child(root).unwrap();
+    assert_eq!(heap.free(), 1);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1");
+
+    // and so does the root once it's off the stack
+    stack.remove_root(&mut heap, 0, 0);
+    assert_eq!(heap.free(), 0);
+}
+
+#[test]
+fn garbage_cycles() {
+    init_log();
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    let mut heap = RefCountHeap::init(10);
+
+    //   1 ---> 4
+    //   |      |
+    //   2 <--> 3
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    let a = alloc_value(&mut stack, &mut heap, 2);
+    let b = alloc_value(&mut stack, &mut heap, 3);
+    let c = alloc_value(&mut stack, &mut heap, 4);
+    heap.add_child(root, a).unwrap();
+    heap.add_child(a, b).unwrap();
+    heap.add_child(b, a).unwrap();
+    heap.add_child(root, c).unwrap();
+    heap.add_child(c, b).unwrap();
+
+    // 4 still points into the cycle, so trial deletion has to put it back
+    heap.remove_child(root, 0).unwrap();
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(heap.free(), 4);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 4, 3, 2");
+
+    // now the cycle is garbage, but counting alone can't tell
+    heap.pop_child(c).unwrap();
+    assert_eq!(heap.free(), 4);
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(heap.free(), 2);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 4");
+}
@@ -24,10 +24,7 @@ fn sanity_garbage_collection<T: MemoryManager>(stack: &mut Stack, heap: &mut T,
     // which should free up *3* slots after garbage collection
     log::trace!("now removing children of one node");
 
-    heap.get_mut(stack.roots[0].children[0])
-        .unwrap()
-        .children
-        .remove(0);
+    heap.remove_child(stack.roots[0].children[0], 0).unwrap();
 
     log::trace!("{}", stack.dump_all(heap).unwrap());
 
@@ -68,3 +65,124 @@ fn stop_and_copy_sanity() {
 
     sanity_garbage_collection(&mut stack, &mut heap, HEAP_SIZE / 2);
 }
+
+#[test]
+fn mark_sweep_sanity() {
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    const HEAP_SIZE: usize = 5;
+    let mut heap = MarkSweepHeap::init(HEAP_SIZE);
+
+    sanity_garbage_collection(&mut stack, &mut heap, HEAP_SIZE);
+}
+
+#[test]
+fn generational_sanity() {
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    const HEAP_SIZE: usize = 5;
+    let mut heap = GenerationalHeap::init(HEAP_SIZE);
+
+    sanity_garbage_collection(&mut stack, &mut heap, HEAP_SIZE);
+}
+
+#[test]
+fn ref_count_sanity() {
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    const HEAP_SIZE: usize = 5;
+    let mut heap = RefCountHeap::init(HEAP_SIZE);
+
+    sanity_garbage_collection(&mut stack, &mut heap, HEAP_SIZE);
+}
+
+#[test]
+fn mark_region_sanity() {
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap. The garbage is in the middle of a line with live
+    // nodes in it, so lines have to be a single node for it to be reused
+    const HEAP_SIZE: usize = 5;
+    let mut heap = MarkRegionHeap::init_with_lines(HEAP_SIZE, 1, 4);
+
+    sanity_garbage_collection(&mut stack, &mut heap, HEAP_SIZE);
+}
+
+#[test]
+fn treadmill_sanity() {
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    const HEAP_SIZE: usize = 5;
+    let mut heap = TreadmillHeap::init(HEAP_SIZE);
+
+    sanity_garbage_collection(&mut stack, &mut heap, HEAP_SIZE);
+}
+
+#[test]
+fn garbage_first_sanity() {
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    const HEAP_SIZE: usize = 5;
+    let mut heap = GarbageFirstHeap::init(HEAP_SIZE);
+
+    sanity_garbage_collection(&mut stack, &mut heap, HEAP_SIZE);
+}
+
+#[test]
+fn concurrent_sanity() {
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    const HEAP_SIZE: usize = 5;
+    let mut heap = ConcurrentMarkSweepHeap::init(HEAP_SIZE);
+
+    sanity_garbage_collection(&mut stack, &mut heap, HEAP_SIZE);
+}
+
+#[test]
+fn adaptive_sanity() {
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    const HEAP_SIZE: usize = 5;
+    let mut heap = AdaptiveHeap::init(HEAP_SIZE);
+
+    sanity_garbage_collection(&mut stack, &mut heap, HEAP_SIZE);
+}
+
+#[test]
+fn flat_sanity() {
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    const HEAP_SIZE: usize = 5;
+    let mut heap = MarkCompactHeap::<Option<u32>, Inline<ARITY>>::init_payload(HEAP_SIZE);
+
+    sanity_garbage_collection(&mut stack, &mut heap, HEAP_SIZE);
+}
+
+#[test]
+fn flat_copying_sanity() {
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap
+    const HEAP_SIZE: usize = 10;
+    let mut heap = StopAndCopyHeap::<Option<u32>, Inline<ARITY>>::init_payload(HEAP_SIZE);
+
+    sanity_garbage_collection(&mut stack, &mut heap, HEAP_SIZE / 2);
+}
@@ -0,0 +1,169 @@
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+
+use crate::stop_copy::CopyOrder;
+use crate::{init_log, link_heap_sized, make_garbage_sized, seed_root, SizeDistribution};
+
+use super::*;
+
+fn alloc_sized<T: MemoryManager>(
+    stack: &mut Stack,
+    heap: &mut T,
+    value: u32,
+    size: usize,
+) -> NodePointer {
+    let node = Node {
+        value: Some(value),
+        ..Default::default()
+    };
+    heap.alloc_sized(node, size, stack).unwrap()
+}
+
+/// 1 -> 3 -> 4, with 2 as garbage in between 1 and 3, where 1 doesn't have
+/// any payload, and the rest have a different amount each. Returns where the
+/// nodes were before collecting, and where they are afterwards
+fn objects_move_by_size<T: MemoryManager>(heap: &mut T) -> ([usize; 2], [usize; 3]) {
+    init_log();
+    let mut stack = Stack::new(1);
+
+    let root = seed_root(&mut stack, heap).unwrap();
+    alloc_sized(&mut stack, heap, 2, 3);
+    let a = alloc_sized(&mut stack, heap, 3, 2);
+    heap.add_child(root, a).unwrap();
+    let b = alloc_sized(&mut stack, heap, 4, 1);
+    heap.add_child(a, b).unwrap();
+    assert_eq!(heap.free(), 10);
+    let before = [a, b].map(usize::from);
+
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(heap.free(), 6);
+    assert_eq!(stack.dump_all(heap).unwrap(), "[0] 1, 3, 4");
+    let root = stack.roots[0].children[0];
+    let a = *heap.child(root, 0).unwrap();
+    let b = *heap.child(a, 0).unwrap();
+    (before, [root, a, b].map(usize::from))
+}
+
+#[test]
+fn sized_compaction() {
+    for compaction in [
+        Compaction::Lisp2,
+        Compaction::Threaded,
+        Compaction::Compressor,
+    ] {
+        let mut heap: MarkCompactHeap = MarkCompactHeap::init_compacting(10, compaction);
+        let (before, after) = objects_move_by_size(&mut heap);
+        assert_eq!(before, [5, 8]);
+        // each node is right after the last one, however big that one was
+        assert_eq!(after, [0, 1, 4]);
+        assert_eq!(
+            after.map(|idx| heap.sizes.size(idx)),
+            [1, 3, 2],
+            "{:?}",
+            compaction
+        );
+        // 3 and its payload, then 4 and its payload
+        assert_eq!(heap.copied, 5);
+    }
+    let mut heap: MarkCompactHeap = MarkCompactHeap::init_incremental(
+        10,
+        Incremental {
+            quantum: 1,
+            trigger: 100,
+        },
+    );
+    assert_eq!(objects_move_by_size(&mut heap).1, [0, 1, 4]);
+}
+
+#[test]
+fn sized_copying() {
+    for copy_order in [
+        CopyOrder::Bfs,
+        CopyOrder::Dfs,
+        CopyOrder::Hierarchical { block_size: 2 },
+    ] {
+        let mut heap: StopAndCopyHeap = StopAndCopyHeap::init_ordered(20, copy_order);
+        let (before, after) = objects_move_by_size(&mut heap);
+        assert_eq!(before, [5, 8]);
+        assert_eq!(after, [10, 11, 14]);
+        assert_eq!(after.map(|idx| heap.sizes.size(idx)), [1, 3, 2]);
+        assert_eq!(heap.copied, 6);
+    }
+    let mut heap: StopAndCopyHeap = StopAndCopyHeap::init_incremental(
+        20,
+        Incremental {
+            quantum: 1,
+            trigger: 100,
+        },
+    );
+    assert_eq!(objects_move_by_size(&mut heap).1, [10, 11, 14]);
+}
+
+/// the two collectors that can't move a node along with its payload say so
+/// up front
+#[test]
+fn sized_unsupported() {
+    init_log();
+    let mut stack = Stack::new(1);
+    let mut heap: MarkCompactHeap = MarkCompactHeap::init_compacting(10, Compaction::TwoFinger);
+    assert!(heap.alloc_sized(Node::default(), 2, &mut stack).is_err());
+    heap.alloc_sized(Node::default(), 0, &mut stack).unwrap();
+    assert_eq!(heap.free(), 1);
+
+    let mut heap: StopAndCopyHeap = StopAndCopyHeap::init_parallel(20, 2);
+    assert!(heap.alloc_sized(Node::default(), 2, &mut stack).is_err());
+    heap.alloc_sized(Node::default(), 0, &mut stack).unwrap();
+    assert_eq!(heap.free(), 1);
+}
+
+#[test]
+fn fixed_size_heaps_refuse_payload() {
+    init_log();
+    let mut stack = Stack::new(1);
+    let mut heap = MarkSweepHeap::init(10);
+    let node = Node::default();
+    assert!(heap.alloc_sized(node, 4, &mut stack).is_err());
+    assert_eq!(heap.free(), 0);
+    // but there's nothing to ignore without any payload
+    heap.alloc_sized(Node::default(), 0, &mut stack).unwrap();
+    assert_eq!(heap.free(), 1);
+}
+
+/// links a heap with a mix of sizes, makes some garbage, and collects it,
+/// returning what's left, and how many slots it takes up
+fn mixed_sizes<T: MemoryManager>(heap: &mut T, sizes: SizeDistribution) -> (String, usize) {
+    let mut stack = Stack::new(1);
+    let mut rng = Pcg64::seed_from_u64(1234);
+    let nodes = link_heap_sized(&mut stack, heap, 1000, sizes, &mut rng).unwrap();
+    make_garbage_sized(heap, &nodes, 0.5, &mut rng).unwrap();
+    let dump = stack.dump_all(heap).unwrap();
+
+    heap.collect(&mut stack).unwrap();
+    assert_eq!(stack.dump_all(heap).unwrap(), dump);
+    (dump, heap.free())
+}
+
+#[test]
+fn mixed_sizes_same_graph() {
+    init_log();
+    let sizes = SizeDistribution::Bimodal {
+        small: 0,
+        large: 32,
+        ratio: 0.1,
+    };
+    // with room for all of them, however big they are
+    let size = 1000 * (1 + 32);
+    let expected = mixed_sizes(&mut MarkCompactHeap::init(size), sizes);
+    for compaction in [Compaction::Threaded, Compaction::Compressor] {
+        let mut heap: MarkCompactHeap = MarkCompactHeap::init_compacting(size, compaction);
+        assert_eq!(mixed_sizes(&mut heap, sizes), expected);
+    }
+    for copy_order in [
+        CopyOrder::Bfs,
+        CopyOrder::Dfs,
+        CopyOrder::Hierarchical { block_size: 64 },
+    ] {
+        let mut heap: StopAndCopyHeap = StopAndCopyHeap::init_ordered(size * 2, copy_order);
+        assert_eq!(mixed_sizes(&mut heap, sizes), expected);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{init_log, seed_root};
+
+use super::*;
+
+/// walks once around the treadmill from `bottom`, checking that every segment
+/// is as long as its count says, that it ends where the next pointer is, and
+/// that the links go both ways. Free nodes keep whatever mark they had, so
+/// they aren't checked
+fn check_segments(heap: &TreadmillHeap) {
+    let black_count = heap.heap_size() - heap.free_count - heap.white_count - heap.gray_count;
+    let mut idx = heap.bottom;
+    for (white, count, end) in [
+        (Some(true), heap.white_count, heap.top),
+        (Some(false), heap.gray_count, heap.scan),
+        (Some(false), black_count, heap.free),
+        (None, heap.free_count, heap.bottom),
+    ] {
+        for _ in 0..count {
+            if let Some(white) = white {
+                assert_eq!(heap.is_white(idx), white);
+            }
+            assert_eq!(heap.prev[heap.next[idx]], idx);
+            idx = heap.next[idx];
+        }
+        assert_eq!(idx, end);
+    }
+}
+
+#[test]
+fn segments_and_barrier() {
+    init_log();
+    // initializing the stack
+    const STACK_SIZE: usize = 1;
+    let mut stack = Stack::new(STACK_SIZE);
+    // initializing the heap, scanning one gray node per allocation once 4
+    // slots are in use
+    const HEAP_SIZE: usize = 100;
+    let mut heap = TreadmillHeap::init_incremental(
+        HEAP_SIZE,
+        Incremental {
+            quantum: 1,
+            trigger: 4,
+        },
+    );
+    check_segments(&heap);
+
+    //     1
+    //    / \
+    //   2   3
+    //        \
+    //         4
+    let root = seed_root(&mut stack, &mut heap).unwrap();
+    let a = alloc_value(&mut stack, &mut heap, 2);
+    heap.add_child(root, a).unwrap();
+    let b = alloc_value(&mut stack, &mut heap, 3);
+    heap.add_child(root, b).unwrap();
+    let c = alloc_value(&mut stack, &mut heap, 4);
+    heap.add_child(b, c).unwrap();
+    assert_eq!(heap.white_count, 4);
+    check_segments(&heap);
+
+    // this allocation shades and scans the root, and the next one scans 2, so
+    // now 2 is black, 3 is gray, and 4 is white
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    check_segments(&heap);
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    check_segments(&heap);
+    assert!(heap.collecting);
+    assert_eq!(heap.gray_count, 1);
+    assert_eq!(heap.prev[heap.scan], usize::from(b));
+    assert!(heap.is_white(usize::from(c)));
+
+    // move 4 from under 3 to under 2. If the barrier didn't shade it, nothing
+    // would ever scan it
+    heap.add_child(a, c).unwrap();
+    heap.remove_child(b, 0).unwrap();
+    assert_eq!(heap.gray_count, 2);
+    assert_eq!(heap.top, usize::from(c));
+    check_segments(&heap);
+
+    // one allocation scans 3, and the next one scans 4, which finishes the
+    // cycle
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    heap.alloc(Node::default(), &mut stack).unwrap();
+    assert!(!heap.collecting);
+    check_segments(&heap);
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 2, 3, 4");
+    // the three nodes allocated during the cycle were allocated black, so they
+    // only go away in the next collection, along with the one allocated after
+    assert_eq!(heap.free(), 8);
+    heap.collect(&mut stack).unwrap();
+    check_segments(&heap);
+    assert_eq!(heap.free(), 4);
+
+    // nothing ever moved
+    assert_eq!(stack.roots[0].children[0], root);
+    assert_eq!(usize::from(c), 3);
+    assert_eq!(heap.get(c).unwrap().value, Some(4));
+    assert_eq!(stack.dump_all(&heap).unwrap(), "[0] 1, 2, 3, 4");
+
+    // and the slots that got freed are back on the free segment, so the whole
+    // rest of the heap can be filled up
+    assert_eq!(heap.free_count, HEAP_SIZE - 4);
+    for value in 0..HEAP_SIZE - 4 {
+        let node_pointer = alloc_value(&mut stack, &mut heap, value as u32);
+        heap.add_child(root, node_pointer).unwrap();
+    }
+    check_segments(&heap);
+    assert!(heap.alloc(Node::default(), &mut stack).is_err());
+}
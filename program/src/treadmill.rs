@@ -0,0 +1,431 @@
+use std::time::Instant;
+
+use crate::large::LargeObjectSpace;
+use crate::mark_compact::Incremental;
+use crate::shared::{Barriers, MemoryManager, Node, NodePointer, Nodes, Pauses, Stack};
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// This is Baker's Treadmill, a non-moving incremental collector. Every slot
+/// is linked into one cyclic doubly-linked list, which is split up into four
+/// segments by four pointers. Going around the list, it looks like
+///
+/// ```text
+/// -> [ white | gray | black | free ] ->
+///    bottom  top    scan    free
+/// ```
+///
+/// Collecting never moves anything, it just unlinks a node from one segment
+/// and links it into another. Shading a white node moves it into the gray
+/// segment, scanning the gray node next to `scan` turns it black by moving
+/// `scan` back over it, and allocating in the middle of a cycle moves `free`
+/// forward, so the new node is black. Once nothing is gray, the whole white
+/// segment is garbage, so it becomes free just by moving `bottom` up to `top`,
+/// and the black segment becomes the white segment for the next cycle. Every
+/// node has a mark bit, and flipping what a set bit means is what turns black
+/// into white without touching any of them.
+///
+/// Outside of a cycle, nodes get allocated white from the other end of the
+/// free segment, so that the next cycle can free them.
+///
+/// Large objects aren't on the treadmill. They get shaded by marking them in
+/// the large object space, and sit on its gray list until they're scanned.
+/// Finishing a cycle sweeps whatever in there didn't get marked
+#[derive(Clone)]
+pub struct TreadmillHeap {
+    pub committed_memory: Vec<Node>,
+    pub next: Vec<usize>,
+    pub prev: Vec<usize>,
+    // a node is white if its mark doesn't match `black`. Gray and black nodes
+    // are only told apart by which segment they're in
+    pub marks: Vec<bool>,
+    pub black: bool,
+    // the first slot of each segment
+    pub bottom: usize,
+    pub top: usize,
+    pub scan: usize,
+    pub free: usize,
+    // segments can be empty or take up the whole list, which look the same,
+    // so we keep count of how big they are too
+    pub free_count: usize,
+    pub white_count: usize,
+    pub gray_count: usize,
+    pub incremental: Incremental,
+    // whether we're in the middle of a cycle
+    pub collecting: bool,
+    pub pauses: Pauses,
+    // nodes bigger than its threshold go in here instead, which comes after
+    // the treadmill's slots
+    pub large: Option<LargeObjectSpace>,
+}
+
+impl TreadmillHeap {
+    /// starts a cycle once half of the heap is in use, and scans 32 nodes
+    /// every allocation
+    pub fn init(size: usize) -> Self {
+        Self::init_incremental(
+            size,
+            Incremental {
+                quantum: 32,
+                trigger: size / 2,
+            },
+        )
+    }
+
+    pub fn init_incremental(size: usize, incremental: Incremental) -> Self {
+        let mut committed_memory: Vec<Node> = Vec::new();
+        for _ in 0..size {
+            committed_memory.push(Node::default());
+        }
+        // white nodes get allocated from the end of the free segment, so the
+        // list runs backwards, so that the first allocation lands on slot 0,
+        // just like the bump allocators
+        let next = (0..size).map(|idx| (idx + size - 1) % size).collect();
+        let prev = (0..size).map(|idx| (idx + 1) % size).collect();
+        // every segment starts at the same place, and the free segment takes
+        // up the whole list
+        let start = size.saturating_sub(1);
+        Self {
+            committed_memory,
+            next,
+            prev,
+            marks: vec![false; size],
+            black: true,
+            bottom: start,
+            top: start,
+            scan: start,
+            free: start,
+            free_count: size,
+            white_count: 0,
+            gray_count: 0,
+            incremental,
+            collecting: false,
+            pauses: Pauses::default(),
+            large: None,
+        }
+    }
+
+    /// the same as `init`, along with a large object space of `large_size`
+    /// slots, for nodes taking up more than `threshold` slots
+    pub fn init_large(size: usize, threshold: usize, large_size: usize) -> Self {
+        let mut heap = Self::init(size);
+        heap.committed_memory
+            .resize_with(size + large_size, Node::default);
+        heap.large = Some(LargeObjectSpace::init(threshold, size, large_size));
+        heap
+    }
+
+    /// where the treadmill's slots end, and the large object space starts
+    #[inline(always)]
+    pub fn end(&self) -> usize {
+        self.next.len()
+    }
+
+    /// whether there's anything gray left, on the treadmill or in the large
+    /// object space
+    #[inline(always)]
+    fn has_gray(&self) -> bool {
+        self.gray_count > 0
+            || self
+                .large
+                .as_ref()
+                .is_some_and(|large| !large.gray.is_empty())
+    }
+
+    #[inline(always)]
+    fn unlink(&mut self, idx: usize) {
+        let (prev, next) = (self.prev[idx], self.next[idx]);
+        self.next[prev] = next;
+        self.prev[next] = prev;
+    }
+
+    #[inline(always)]
+    fn link_before(&mut self, idx: usize, at: usize) {
+        let prev = self.prev[at];
+        self.next[prev] = idx;
+        self.prev[idx] = prev;
+        self.next[idx] = at;
+        self.prev[at] = idx;
+    }
+
+    #[inline(always)]
+    pub fn is_white(&self, idx: usize) -> bool {
+        self.marks[idx] != self.black
+    }
+
+    /// moves a white node to the front of the gray segment
+    #[inline]
+    fn shade(&mut self, node_pointer: NodePointer) {
+        if let Some(large) = &mut self.large {
+            if large.contains(node_pointer) {
+                if large.mark(node_pointer) {
+                    large.gray.push(node_pointer);
+                }
+                return;
+            }
+        }
+        let idx = usize::from(node_pointer);
+        if !self.is_white(idx) {
+            return;
+        }
+        self.marks[idx] = self.black;
+        self.white_count -= 1;
+        self.gray_count += 1;
+        let next = self.next[idx];
+        if idx == self.top {
+            // everything is white, so all four pointers are sitting on this
+            // node, and it's already where the gray segment starts
+            self.bottom = next;
+            self.scan = next;
+            self.free = next;
+            return;
+        }
+        if idx == self.bottom {
+            self.bottom = next;
+        }
+        // if it's the last white node, it's already right in front of the gray
+        // segment
+        if next != self.top {
+            self.unlink(idx);
+            self.link_before(idx, self.top);
+        }
+        self.top = idx;
+        if self.white_count == 0 {
+            self.bottom = self.top;
+        }
+    }
+
+    fn shade_roots(&mut self, stack: &Stack) {
+        for root in &stack.roots {
+            for child in &root.children {
+                self.shade(*child);
+            }
+        }
+    }
+
+    /// starts a cycle. Everything allocated since the last one is white
+    fn start(&mut self, stack: &Stack) {
+        self.collecting = true;
+        self.shade_roots(stack);
+    }
+
+    /// scans up to `quantum` gray nodes, finishing the cycle if there aren't
+    /// any left
+    fn increment(&mut self, stack: &Stack, quantum: usize) {
+        let instant = Instant::now();
+        for _ in 0..quantum {
+            if !self.has_gray() {
+                break;
+            }
+            self.scan_one();
+        }
+        if !self.has_gray() {
+            self.finish(stack);
+        }
+        self.pauses.record(instant.elapsed());
+    }
+
+    /// turns a gray large object black, or if there aren't any, the gray node
+    /// right behind `scan`
+    #[inline]
+    fn scan_one(&mut self) {
+        if let Some(node_pointer) = self.large.as_mut().and_then(|large| large.gray.pop()) {
+            let idx = usize::from(node_pointer);
+            for i in 0..self.committed_memory[idx].children.len() {
+                self.shade(self.committed_memory[idx].children[i]);
+            }
+            return;
+        }
+        let idx = self.prev[self.scan];
+        self.scan = idx;
+        self.gray_count -= 1;
+        for i in 0..self.committed_memory[idx].children.len() {
+            self.shade(self.committed_memory[idx].children[i]);
+        }
+    }
+
+    /// scans everything that's left, frees the white segment, and flips black
+    /// to white for the next cycle
+    fn finish(&mut self, stack: &Stack) {
+        // not every root gets changed through `Stack::add_root`, so they could
+        // be pointing at something white
+        self.shade_roots(stack);
+        while self.has_gray() {
+            self.scan_one();
+        }
+        // the large objects don't have a segment that can just become free,
+        // so they get swept instead
+        if let Some(large) = &mut self.large {
+            large.sweep();
+        }
+
+        // white becomes free without touching any of it, since it already sits
+        // right after the free segment. Its marks get fixed up when it's
+        // allocated again. Then black becomes white by flipping what a mark
+        // means, and gray and black start out empty, right where the free
+        // segment starts
+        self.free_count += self.white_count;
+        self.white_count = self.end() - self.free_count;
+        self.black = !self.black;
+        self.bottom = self.scan;
+        self.top = self.free;
+        self.scan = self.free;
+        self.collecting = false;
+    }
+
+    /// what every allocation does before it allocates. Starts a cycle if
+    /// enough of the heap is in use, and scans a slice of the one in progress
+    fn step(&mut self, stack: &Stack) {
+        if !self.collecting && self.free() >= self.incremental.trigger {
+            self.start(stack);
+        }
+        if self.collecting {
+            self.increment(stack, self.incremental.quantum);
+        }
+    }
+
+    /// first fit in the large object space, collecting if nothing fits
+    fn alloc_large(&mut self, node: Node, size: usize, stack: &mut Stack) -> Result<NodePointer> {
+        self.step(stack);
+        let mut node_pointer = self.large.as_mut().unwrap().alloc(size);
+        if node_pointer.is_none() {
+            self.collect(stack)?;
+            node_pointer = self.large.as_mut().unwrap().alloc(size);
+        }
+        let node_pointer =
+            node_pointer.ok_or("gg collection didn't result in any amount of garbage collected")?;
+        self.committed_memory[usize::from(node_pointer)] = node;
+
+        // in the middle of a cycle it's black, just like any other node
+        // allocated then, so whatever it already points to has to be shaded
+        if self.collecting {
+            self.large.as_mut().unwrap().mark(node_pointer);
+            let idx = usize::from(node_pointer);
+            for i in 0..self.committed_memory[idx].children.len() {
+                self.shade(self.committed_memory[idx].children[i]);
+            }
+        }
+        Ok(node_pointer)
+    }
+}
+
+impl MemoryManager for TreadmillHeap {
+    // takes a node off of one end of the free segment
+    fn alloc(&mut self, node: Node, stack: &mut Stack) -> Result<NodePointer> {
+        self.step(stack);
+        // if there's nothing left on the free segment
+        if self.free_count == 0 {
+            // we need to run gc
+            self.collect(stack)?;
+        }
+        if self.free_count == 0 {
+            return Err("gg collection didn't result in any amount of garbage collected".into());
+        }
+
+        self.free_count -= 1;
+        let idx = if self.collecting {
+            // the node right at `free` joins the black segment
+            let idx = self.free;
+            self.free = self.next[idx];
+            self.marks[idx] = self.black;
+            idx
+        } else {
+            // the node right before `bottom` joins the white segment
+            let idx = self.prev[self.bottom];
+            self.bottom = idx;
+            self.marks[idx] = !self.black;
+            self.white_count += 1;
+            idx
+        };
+        self.committed_memory[idx] = node;
+
+        // black nodes never get scanned, so whatever they already point to
+        // has to be shaded
+        if self.collecting {
+            for i in 0..self.committed_memory[idx].children.len() {
+                self.shade(self.committed_memory[idx].children[i]);
+            }
+        }
+
+        Ok(NodePointer::from(idx))
+    }
+
+    // nodes are all the same size, unless they're big enough for the large
+    // object space
+    fn alloc_sized(&mut self, node: Node, size: usize, stack: &mut Stack) -> Result<NodePointer> {
+        match &self.large {
+            Some(large) if 1 + size > large.threshold => self.alloc_large(node, 1 + size, stack),
+            _ if size > 0 => Err("this heap's nodes are all the same size".into()),
+            _ => self.alloc(node, stack),
+        }
+    }
+
+    /// finishes the cycle that's in progress, if there is one, then runs a
+    /// whole cycle from scratch, since the cycle in progress could've missed
+    /// anything that became garbage after it started
+    fn collect(&mut self, stack: &mut Stack) -> Result<()> {
+        let instant = Instant::now();
+        if self.collecting {
+            self.finish(stack);
+        }
+        self.start(stack);
+        self.finish(stack);
+        self.pauses.record(instant.elapsed());
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn node_pointer_from_usize(&self, idx: usize) -> NodePointer {
+        NodePointer::from(idx)
+    }
+
+    #[inline(always)]
+    fn free(&self) -> usize {
+        // there's no free pointer, so report the number of slots in use
+        self.end() - self.free_count + self.large.as_ref().map_or(0, |large| large.used())
+    }
+
+    fn heap_size(&self) -> usize {
+        self.end()
+    }
+}
+
+impl Barriers for TreadmillHeap {
+    /// Dijkstra's insertion barrier, the same as the incremental mark-compact
+    /// heap's
+    #[inline(always)]
+    fn write_barrier(
+        &mut self,
+        _parent: NodePointer,
+        _old: Option<NodePointer>,
+        new: Option<NodePointer>,
+    ) {
+        if self.collecting {
+            if let Some(new) = new {
+                self.shade(new);
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn root_barrier(&mut self, _old: Option<NodePointer>, new: Option<NodePointer>) {
+        if self.collecting {
+            if let Some(new) = new {
+                self.shade(new);
+            }
+        }
+    }
+}
+
+impl Nodes for TreadmillHeap {
+    type Children = Vec<NodePointer>;
+
+    #[inline(always)]
+    fn get(&self, node_pointer: NodePointer) -> Option<&Node> {
+        self.committed_memory.get(usize::from(node_pointer))
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self, node_pointer: NodePointer) -> Option<&mut Node> {
+        self.committed_memory.get_mut(usize::from(node_pointer))
+    }
+}